@@ -0,0 +1,25 @@
+ALTER TABLE shipment ADD COLUMN time_received TIMESTAMP WITH TIME ZONE;
+
+ALTER TABLE shipment_item ADD COLUMN received REAL NOT NULL DEFAULT 0;
+ALTER TABLE shipment_item ADD CONSTRAINT shipment_item_received_check CHECK (received >= 0 AND received <= amount);
+
+ALTER TYPE shipment_listing ADD ATTRIBUTE received REAL;
+
+CREATE OR REPLACE VIEW current_state AS
+SELECT
+    stored_item.item,
+    stored_item.storage,
+    stored_item.container,
+    STATE(
+        stored_item.amount,
+        stored_item.min,
+        stored_item.max,
+        EXISTS(
+            SELECT 1
+            FROM shipment_item
+            WHERE
+                shipment_item.item = stored_item.item AND
+                shipment_item.received < shipment_item.amount
+        )
+    ) as "state"
+FROM stored_item;
@@ -0,0 +1,61 @@
+CREATE TYPE shipment_state AS ENUM ('ordered', 'shipped', 'partially_received', 'received', 'cancelled');
+
+CREATE TYPE shipment_event_listing AS (
+    state shipment_state,
+    time TIMESTAMP WITH TIME ZONE,
+    user_ TEXT
+);
+
+ALTER TABLE shipment ADD COLUMN state shipment_state NOT NULL DEFAULT 'ordered';
+
+CREATE TABLE "shipment_event" (
+    shipment UUID NOT NULL,
+    state shipment_state NOT NULL,
+    time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT clock_timestamp(),
+    user_ TEXT NOT NULL,
+    PRIMARY KEY (shipment, time),
+    FOREIGN KEY (shipment) REFERENCES shipment (id) ON DELETE CASCADE ON UPDATE CASCADE,
+    CHECK (TRIM(user_) <> '')
+);
+
+UPDATE shipment
+SET state = 'partially_received'
+WHERE EXISTS (
+    SELECT 1
+    FROM shipment_item
+    WHERE shipment_item.shipment = shipment.id AND shipment_item.received > 0
+);
+
+UPDATE shipment
+SET state = 'received'
+WHERE time_received IS NOT NULL;
+
+INSERT INTO shipment_event (shipment, state, time, user_)
+SELECT id, 'ordered', time_created, 'unknown'
+FROM shipment;
+
+INSERT INTO shipment_event (shipment, state, time, user_)
+SELECT id, state, COALESCE(time_received, CURRENT_TIMESTAMP), 'unknown'
+FROM shipment
+WHERE state <> 'ordered';
+
+CREATE OR REPLACE VIEW current_state AS
+SELECT
+    stored_item.item,
+    stored_item.storage,
+    stored_item.container,
+    STATE(
+        stored_item.amount,
+        stored_item.min,
+        stored_item.max,
+        EXISTS(
+            SELECT 1
+            FROM shipment_item
+            JOIN shipment ON shipment.id = shipment_item.shipment
+            WHERE
+                shipment_item.item = stored_item.item AND
+                shipment_item.received < shipment_item.amount AND
+                shipment.state <> 'cancelled'
+        )
+    ) as "state"
+FROM stored_item;
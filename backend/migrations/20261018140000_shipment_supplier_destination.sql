@@ -0,0 +1,42 @@
+ALTER TABLE shipment ADD COLUMN supplier TEXT;
+ALTER TABLE shipment ADD CONSTRAINT shipment_supplier_fkey
+    FOREIGN KEY (supplier) REFERENCES supplier (name) ON DELETE SET NULL ON UPDATE CASCADE;
+
+ALTER TABLE shipment_item ADD COLUMN storage TEXT;
+ALTER TABLE shipment_item ADD COLUMN container TEXT;
+ALTER TABLE shipment_item ADD CONSTRAINT shipment_item_destination_fkey
+    FOREIGN KEY (storage, container) REFERENCES container (storage, name) ON DELETE SET NULL ON UPDATE CASCADE;
+ALTER TABLE shipment_item ADD CONSTRAINT shipment_item_destination_check
+    CHECK ((storage IS NULL AND container IS NULL) OR (storage IS NOT NULL AND container IS NOT NULL));
+
+ALTER TYPE shipment_listing ADD ATTRIBUTE storage TEXT;
+ALTER TYPE shipment_listing ADD ATTRIBUTE container TEXT;
+
+-- Lines without a destination still mark every location holding the item as incoming
+CREATE OR REPLACE VIEW current_state AS
+SELECT
+    stored_item.item,
+    stored_item.storage,
+    stored_item.container,
+    STATE(
+        stored_item.amount,
+        stored_item.min,
+        stored_item.max,
+        EXISTS(
+            SELECT 1
+            FROM shipment_item
+            JOIN shipment ON shipment.id = shipment_item.shipment
+            WHERE
+                shipment_item.item = stored_item.item AND
+                shipment_item.received < shipment_item.amount AND
+                shipment.state <> 'cancelled' AND
+                (
+                    shipment_item.storage IS NULL OR
+                    (
+                        shipment_item.storage = stored_item.storage AND
+                        shipment_item.container = stored_item.container
+                    )
+                )
+        )
+    ) as "state"
+FROM stored_item;
@@ -0,0 +1,72 @@
+-- Every stored item below its order floor, with what is already on its way subtracted.
+-- Shipment lines with a destination only count towards that location, lines without one
+-- are spread over the short locations of the item in order.
+CREATE VIEW shortage AS
+WITH incoming AS (
+    SELECT
+        shipment_item.item,
+        shipment_item.storage,
+        shipment_item.container,
+        SUM(shipment_item.amount - shipment_item.received) AS amount,
+        MIN(shipment.time_arive) AS arrival
+    FROM shipment_item
+    JOIN shipment ON shipment.id = shipment_item.shipment
+    WHERE
+        shipment.state <> 'cancelled' AND
+        shipment_item.received < shipment_item.amount
+    GROUP BY shipment_item.item, shipment_item.storage, shipment_item.container
+),
+short AS (
+    SELECT
+        stored_item.item,
+        stored_item.storage,
+        stored_item.container,
+        stored_item.amount,
+        storage.protected,
+        COALESCE(directed.amount, 0) AS directed,
+        directed.arrival AS directed_arrival,
+        GREATEST(stored_item.max - stored_item.amount - COALESCE(directed.amount, 0), 0) AS gap
+    FROM stored_item
+    JOIN storage ON storage.name = stored_item.storage
+    LEFT JOIN incoming directed ON
+        directed.item = stored_item.item AND
+        directed.storage = stored_item.storage AND
+        directed.container = stored_item.container
+    WHERE stored_item.amount <= stored_item.min
+),
+allocated AS (
+    SELECT
+        short.*,
+        LEAST(
+            short.gap,
+            GREATEST(
+                COALESCE(undirected.amount, 0) - COALESCE(
+                    SUM(short.gap) OVER (
+                        PARTITION BY short.item
+                        ORDER BY short.storage, short.container
+                        ROWS BETWEEN UNBOUNDED PRECEDING AND 1 PRECEDING
+                    ),
+                    0
+                ),
+                0
+            )
+        ) AS undirected,
+        undirected.arrival AS undirected_arrival
+    FROM short
+    LEFT JOIN incoming undirected ON
+        undirected.item = short.item AND
+        undirected.storage IS NULL
+)
+SELECT
+    item,
+    storage,
+    container,
+    amount,
+    protected,
+    directed + undirected AS incoming,
+    gap - undirected AS amount_to_buy,
+    CASE
+        WHEN undirected > 0 THEN LEAST(directed_arrival, undirected_arrival)
+        ELSE directed_arrival
+    END AS expected_arrival
+FROM allocated;
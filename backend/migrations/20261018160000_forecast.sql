@@ -0,0 +1,91 @@
+DROP VIEW avrage_consuption;
+
+-- Average daily consumption per stored item over the last 90 days. Between two log entries
+-- the amount is expected to change by what was moved in and out, anything missing on top of
+-- that was consumed and anything extra was restocked.
+CREATE VIEW consumption AS
+WITH entries AS (
+    SELECT
+        item,
+        storage,
+        container,
+        time,
+        amount,
+        LAG(amount) OVER location AS prev_amount,
+        LAG(time) OVER location AS prev_time
+    FROM log
+    WHERE time > CURRENT_TIMESTAMP - INTERVAL '90 days'
+    WINDOW location AS (PARTITION BY item, storage, container ORDER BY time)
+),
+changes AS (
+    SELECT
+        entries.item,
+        entries.storage,
+        entries.container,
+        entries.time,
+        entries.prev_time,
+        GREATEST(
+            entries.prev_amount
+            + COALESCE((
+                SELECT SUM(move_log.amount)
+                FROM move_log
+                WHERE
+                    move_log.item = entries.item AND
+                    move_log.to_storage = entries.storage AND
+                    move_log.to_container = entries.container AND
+                    move_log.time > entries.prev_time AND
+                    move_log.time <= entries.time
+            ), 0)
+            - COALESCE((
+                SELECT SUM(move_log.amount)
+                FROM move_log
+                WHERE
+                    move_log.item = entries.item AND
+                    move_log.from_storage = entries.storage AND
+                    move_log.from_container = entries.container AND
+                    move_log.time > entries.prev_time AND
+                    move_log.time <= entries.time
+            ), 0)
+            - entries.amount,
+            0
+        ) AS consumed
+    FROM entries
+    WHERE entries.prev_amount IS NOT NULL
+)
+SELECT
+    item,
+    storage,
+    container,
+    (
+        SUM(consumed) / GREATEST(EXTRACT(EPOCH FROM MAX(time) - MIN(prev_time)) / 86400, 1)
+    )::REAL AS per_day
+FROM changes
+GROUP BY item, storage, container;
+
+CREATE VIEW forecast AS
+SELECT
+    stored_item.item,
+    stored_item.storage,
+    stored_item.container,
+    stored_item.amount,
+    stored_item.min,
+    consumption.per_day,
+    CASE WHEN consumption.per_day > 0 THEN
+        CURRENT_TIMESTAMP + make_interval(
+            secs => (stored_item.amount / consumption.per_day * 86400)::DOUBLE PRECISION
+        )
+    END AS stockout,
+    CASE WHEN consumption.per_day > 0 AND stored_item.min IS NOT NULL THEN
+        CURRENT_TIMESTAMP + make_interval(
+            secs => (GREATEST(stored_item.amount - stored_item.min, 0) / consumption.per_day * 86400)::DOUBLE PRECISION
+        )
+    END AS below_min
+FROM stored_item
+LEFT JOIN consumption ON
+    consumption.item = stored_item.item AND
+    consumption.storage = stored_item.storage AND
+    consumption.container = stored_item.container;
+
+ALTER TYPE storage_listing ADD ATTRIBUTE consumption REAL;
+ALTER TYPE storage_listing ADD ATTRIBUTE stockout TIMESTAMP WITH TIME ZONE;
+ALTER TYPE storage_listing ADD ATTRIBUTE below_min TIMESTAMP WITH TIME ZONE;
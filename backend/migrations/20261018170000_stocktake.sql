@@ -0,0 +1,40 @@
+CREATE TYPE stocktake_state AS ENUM ('open', 'finalized', 'abandoned');
+
+CREATE TYPE stocktake_count_listing AS (
+    user_ TEXT,
+    amount REAL,
+    time TIMESTAMP WITH TIME ZONE
+);
+
+CREATE TABLE "stocktake" (
+    id UUID NOT NULL DEFAULT gen_random_uuid(),
+    storage TEXT NOT NULL,
+    state stocktake_state NOT NULL DEFAULT 'open',
+    time_created TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    time_closed TIMESTAMP WITH TIME ZONE,
+    user_ TEXT NOT NULL,
+    closed_by TEXT,
+    PRIMARY KEY (id),
+    FOREIGN KEY (storage) REFERENCES storage (name) ON DELETE CASCADE ON UPDATE CASCADE,
+    CHECK (TRIM(user_) <> ''),
+    CHECK (TRIM(closed_by) <> '')
+);
+
+-- Only one session can be counting a storage at a time
+CREATE UNIQUE INDEX stocktake_open_storage ON stocktake (storage) WHERE state = 'open';
+
+-- A count per person, counting the same item again replaces the previous count
+CREATE TABLE "stocktake_count" (
+    stocktake UUID NOT NULL,
+    item TEXT NOT NULL,
+    storage TEXT NOT NULL,
+    container TEXT NOT NULL,
+    amount REAL NOT NULL,
+    user_ TEXT NOT NULL,
+    time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT clock_timestamp(),
+    PRIMARY KEY (stocktake, item, container, user_),
+    FOREIGN KEY (stocktake) REFERENCES stocktake (id) ON DELETE CASCADE,
+    FOREIGN KEY (item, storage, container) REFERENCES stored_item (item, storage, container) ON DELETE CASCADE ON UPDATE CASCADE,
+    CHECK (amount >= 0),
+    CHECK (TRIM(user_) <> '')
+);
@@ -0,0 +1,10 @@
+-- Hive API tokens that have made requests, changes made with a token are logged with the user
+-- 'token:' followed by its id
+CREATE TABLE "api_token" (
+    id TEXT NOT NULL,
+    name TEXT,
+    owner TEXT,
+    last_used TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (id),
+    CHECK (TRIM(id) <> '')
+);
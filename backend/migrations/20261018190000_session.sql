@@ -0,0 +1,14 @@
+-- Every login gets a session, the id is a claim in the session cookie and is checked on every
+-- request so sessions can be ended before the cookie expires
+CREATE TABLE "session" (
+    id UUID NOT NULL DEFAULT gen_random_uuid(),
+    user_ TEXT NOT NULL,
+    time_created TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    time_revoked TIMESTAMP WITH TIME ZONE,
+    revoked_by TEXT,
+    PRIMARY KEY (id),
+    CHECK (TRIM(user_) <> ''),
+    CHECK (TRIM(revoked_by) <> '')
+);
+
+CREATE INDEX session_active_by_user ON session (user_) WHERE time_revoked IS NULL;
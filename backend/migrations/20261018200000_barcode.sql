@@ -0,0 +1,11 @@
+-- Barcodes that identify an item when scanned, ex. EAN-13, UPC or codes of our own. An item can
+-- have several but a code belongs to one item. UPC-A codes are stored as the EAN-13 they equal.
+CREATE TABLE "barcode" (
+    code TEXT NOT NULL,
+    item TEXT NOT NULL,
+    PRIMARY KEY (code),
+    FOREIGN KEY (item) REFERENCES item (name) ON DELETE CASCADE ON UPDATE CASCADE,
+    CHECK (TRIM(code) <> '')
+);
+
+CREATE INDEX barcode_item ON barcode (item);
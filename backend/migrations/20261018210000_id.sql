@@ -0,0 +1,67 @@
+-- Items, storages, containers and suppliers get ids that stay the same when they are renamed
+-- and become their primary keys. The names stay unique, so the tables that refer to them by
+-- name keep their history as it is and follow renames through the foreign keys.
+ALTER TABLE storage ADD COLUMN id UUID NOT NULL DEFAULT gen_random_uuid();
+ALTER TABLE container ADD COLUMN id UUID NOT NULL DEFAULT gen_random_uuid();
+ALTER TABLE item ADD COLUMN id UUID NOT NULL DEFAULT gen_random_uuid();
+ALTER TABLE supplier ADD COLUMN id UUID NOT NULL DEFAULT gen_random_uuid();
+
+-- The foreign keys depend on the primary keys, they are added back on the unique names
+ALTER TABLE container DROP CONSTRAINT container_storage_fkey;
+ALTER TABLE stocktake DROP CONSTRAINT stocktake_storage_fkey;
+ALTER TABLE stored_item DROP CONSTRAINT stored_item_storage_container_fkey;
+ALTER TABLE move_log DROP CONSTRAINT move_log_to_storage_to_container_fkey;
+ALTER TABLE shipment_item DROP CONSTRAINT shipment_item_destination_fkey;
+ALTER TABLE stored_item DROP CONSTRAINT stored_item_item_fkey;
+ALTER TABLE supplier_item DROP CONSTRAINT supplier_item_item_fkey;
+ALTER TABLE move_log DROP CONSTRAINT move_log_item_fkey;
+ALTER TABLE shipment_item DROP CONSTRAINT shipment_item_item_fkey;
+ALTER TABLE barcode DROP CONSTRAINT barcode_item_fkey;
+ALTER TABLE supplier_item DROP CONSTRAINT supplier_item_supplier_fkey;
+ALTER TABLE shipment DROP CONSTRAINT shipment_supplier_fkey;
+
+ALTER TABLE storage DROP CONSTRAINT storage_pkey;
+ALTER TABLE storage ALTER COLUMN name SET NOT NULL;
+ALTER TABLE storage ADD PRIMARY KEY (id);
+ALTER TABLE storage ADD CONSTRAINT storage_name_key UNIQUE (name);
+
+ALTER TABLE container DROP CONSTRAINT container_pkey;
+ALTER TABLE container ALTER COLUMN name SET NOT NULL;
+ALTER TABLE container ALTER COLUMN storage SET NOT NULL;
+ALTER TABLE container ADD PRIMARY KEY (id);
+ALTER TABLE container ADD CONSTRAINT container_name_storage_key UNIQUE (name, storage);
+
+ALTER TABLE item DROP CONSTRAINT item_pkey;
+ALTER TABLE item ALTER COLUMN name SET NOT NULL;
+ALTER TABLE item ADD PRIMARY KEY (id);
+ALTER TABLE item ADD CONSTRAINT item_name_key UNIQUE (name);
+
+ALTER TABLE supplier DROP CONSTRAINT supplier_pkey;
+ALTER TABLE supplier ALTER COLUMN name SET NOT NULL;
+ALTER TABLE supplier ADD PRIMARY KEY (id);
+ALTER TABLE supplier ADD CONSTRAINT supplier_name_key UNIQUE (name);
+
+ALTER TABLE container ADD CONSTRAINT container_storage_fkey
+    FOREIGN KEY (storage) REFERENCES storage (name) ON UPDATE CASCADE ON DELETE RESTRICT;
+ALTER TABLE stocktake ADD CONSTRAINT stocktake_storage_fkey
+    FOREIGN KEY (storage) REFERENCES storage (name) ON UPDATE CASCADE ON DELETE CASCADE;
+ALTER TABLE stored_item ADD CONSTRAINT stored_item_storage_container_fkey
+    FOREIGN KEY (storage, container) REFERENCES container (storage, name) ON UPDATE CASCADE ON DELETE RESTRICT;
+ALTER TABLE move_log ADD CONSTRAINT move_log_to_storage_to_container_fkey
+    FOREIGN KEY (to_storage, to_container) REFERENCES container (storage, name) ON UPDATE CASCADE ON DELETE CASCADE;
+ALTER TABLE shipment_item ADD CONSTRAINT shipment_item_destination_fkey
+    FOREIGN KEY (storage, container) REFERENCES container (storage, name) ON UPDATE CASCADE ON DELETE SET NULL;
+ALTER TABLE stored_item ADD CONSTRAINT stored_item_item_fkey
+    FOREIGN KEY (item) REFERENCES item (name) ON UPDATE CASCADE ON DELETE CASCADE;
+ALTER TABLE supplier_item ADD CONSTRAINT supplier_item_item_fkey
+    FOREIGN KEY (item) REFERENCES item (name) ON UPDATE CASCADE ON DELETE CASCADE;
+ALTER TABLE move_log ADD CONSTRAINT move_log_item_fkey
+    FOREIGN KEY (item) REFERENCES item (name) ON UPDATE CASCADE ON DELETE CASCADE;
+ALTER TABLE shipment_item ADD CONSTRAINT shipment_item_item_fkey
+    FOREIGN KEY (item) REFERENCES item (name) ON UPDATE CASCADE ON DELETE CASCADE;
+ALTER TABLE barcode ADD CONSTRAINT barcode_item_fkey
+    FOREIGN KEY (item) REFERENCES item (name) ON UPDATE CASCADE ON DELETE CASCADE;
+ALTER TABLE supplier_item ADD CONSTRAINT supplier_item_supplier_fkey
+    FOREIGN KEY (supplier) REFERENCES supplier (name) ON UPDATE CASCADE ON DELETE CASCADE;
+ALTER TABLE shipment ADD CONSTRAINT shipment_supplier_fkey
+    FOREIGN KEY (supplier) REFERENCES supplier (name) ON UPDATE CASCADE ON DELETE SET NULL;
@@ -0,0 +1,46 @@
+-- Categories items are sorted into, ex. drinks with the subcategory soda. A category without a
+-- parent is at the top of the tree.
+CREATE TABLE "category" (
+    name TEXT NOT NULL,
+    parent TEXT,
+    PRIMARY KEY (name),
+    FOREIGN KEY (parent) REFERENCES category (name) ON UPDATE CASCADE,
+    CHECK (TRIM(name) <> ''),
+    CHECK (parent <> name)
+);
+
+ALTER TABLE item ADD COLUMN category TEXT;
+ALTER TABLE item ADD CONSTRAINT item_category_fkey
+    FOREIGN KEY (category) REFERENCES category (name) ON UPDATE CASCADE;
+
+CREATE INDEX item_category ON item (category);
+
+-- Free-form labels on items, an item can have any number of them
+CREATE TABLE "item_tag" (
+    item TEXT NOT NULL,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (item, tag),
+    FOREIGN KEY (item) REFERENCES item (name) ON DELETE CASCADE ON UPDATE CASCADE,
+    CHECK (TRIM(tag) <> '')
+);
+
+CREATE INDEX item_tag_tag ON item_tag (tag);
+
+-- If the category c is the category ancestor or one of its subcategories
+CREATE OR REPLACE FUNCTION in_category(c TEXT, ancestor TEXT)
+RETURNS BOOLEAN
+AS $$
+WITH RECURSIVE ancestors AS (
+    SELECT name, parent
+    FROM category
+    WHERE name = c
+    UNION
+    SELECT category.name, category.parent
+    FROM category
+    JOIN ancestors ON category.name = ancestors.parent
+)
+SELECT EXISTS (SELECT 1 FROM ancestors WHERE name = ancestor);
+$$
+LANGUAGE SQL;
+
+ALTER TYPE shortage_item ADD ATTRIBUTE category TEXT;
@@ -0,0 +1,33 @@
+-- Lots of a stored item, ex. the packs of milk that arrived together and expire together. Lots
+-- are optional, whatever part of the stored amount is in no lot has no known best-before date.
+CREATE TABLE "lot" (
+    id UUID NOT NULL DEFAULT gen_random_uuid(),
+    item TEXT NOT NULL,
+    storage TEXT NOT NULL,
+    container TEXT NOT NULL,
+    amount REAL NOT NULL,
+    best_before DATE,
+    arrived TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (id),
+    FOREIGN KEY (item, storage, container) REFERENCES stored_item (item, storage, container) ON DELETE CASCADE ON UPDATE CASCADE,
+    CHECK (amount > 0)
+);
+
+CREATE INDEX lot_stored_item ON lot (item, storage, container);
+CREATE INDEX lot_best_before ON lot (best_before);
+
+-- Amounts taken out of storage without being used, ex. expired milk that was thrown away
+CREATE TABLE "write_off" (
+    item TEXT NOT NULL,
+    storage TEXT NOT NULL,
+    container TEXT NOT NULL,
+    time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    amount REAL NOT NULL,
+    best_before DATE,
+    reason TEXT NOT NULL,
+    user_ TEXT NOT NULL,
+    FOREIGN KEY (item, storage, container) REFERENCES stored_item (item, storage, container) ON DELETE CASCADE ON UPDATE CASCADE,
+    CHECK (amount > 0),
+    CHECK (TRIM(reason) <> ''),
+    CHECK (TRIM(user_) <> '')
+);
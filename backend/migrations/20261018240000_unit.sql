@@ -0,0 +1,20 @@
+-- Units an item can be counted in besides its own, ex. a soda counted in cans that is also
+-- bought in 24-packs. The factor is how many of the items own unit one of the unit is, amounts
+-- are always stored in the items own unit.
+CREATE TABLE "item_unit" (
+    item TEXT NOT NULL,
+    name TEXT NOT NULL,
+    factor REAL NOT NULL,
+    PRIMARY KEY (item, name),
+    FOREIGN KEY (item) REFERENCES item (name) ON DELETE CASCADE ON UPDATE CASCADE,
+    CHECK (TRIM(name) <> ''),
+    CHECK (factor > 0)
+);
+
+CREATE TYPE unit_listing AS (
+    name TEXT,
+    factor REAL
+);
+
+-- Shipment lines show the unit their amounts are counted in
+ALTER TYPE shipment_listing ADD ATTRIBUTE unit TEXT;
@@ -0,0 +1,499 @@
+-- Everything that refers to an item, storage, container or supplier does it by its id, so a rename
+-- only changes the name and the history keeps pointing at the same thing. A container is referred
+-- to by its id alone, the storage it is in follows from it. The history of a stored item still
+-- follows it when it is moved to another container.
+
+-- The views depend on the columns that are replaced, they are added back at the end
+DROP VIEW forecast;
+DROP VIEW consumption;
+DROP VIEW shortage;
+DROP VIEW current_state;
+DROP FUNCTION next_inventory(TEXT, TEXT, TEXT);
+
+ALTER TABLE container ADD COLUMN storage_id UUID;
+UPDATE container SET storage_id = storage.id FROM storage WHERE storage.name = container.storage;
+
+ALTER TABLE stored_item ADD COLUMN item_id UUID;
+ALTER TABLE stored_item ADD COLUMN container_id UUID;
+UPDATE stored_item SET item_id = item.id FROM item WHERE item.name = stored_item.item;
+UPDATE stored_item SET container_id = container.id
+FROM container
+WHERE container.storage = stored_item.storage AND container.name = stored_item.container;
+
+ALTER TABLE supplier_item ADD COLUMN supplier_id UUID;
+ALTER TABLE supplier_item ADD COLUMN item_id UUID;
+UPDATE supplier_item SET supplier_id = supplier.id FROM supplier WHERE supplier.name = supplier_item.supplier;
+UPDATE supplier_item SET item_id = item.id FROM item WHERE item.name = supplier_item.item;
+
+ALTER TABLE log ADD COLUMN item_id UUID;
+ALTER TABLE log ADD COLUMN container_id UUID;
+UPDATE log SET item_id = item.id FROM item WHERE item.name = log.item;
+UPDATE log SET container_id = container.id
+FROM container
+WHERE container.storage = log.storage AND container.name = log.container;
+
+-- Where an item was moved from is not kept when that container is removed, as before
+ALTER TABLE move_log ADD COLUMN item_id UUID;
+ALTER TABLE move_log ADD COLUMN from_container_id UUID;
+ALTER TABLE move_log ADD COLUMN to_container_id UUID;
+UPDATE move_log SET item_id = item.id FROM item WHERE item.name = move_log.item;
+UPDATE move_log SET from_container_id = container.id
+FROM container
+WHERE container.storage = move_log.from_storage AND container.name = move_log.from_container;
+UPDATE move_log SET to_container_id = container.id
+FROM container
+WHERE container.storage = move_log.to_storage AND container.name = move_log.to_container;
+
+ALTER TABLE shipment ADD COLUMN supplier_id UUID;
+UPDATE shipment SET supplier_id = supplier.id FROM supplier WHERE supplier.name = shipment.supplier;
+
+ALTER TABLE shipment_item ADD COLUMN item_id UUID;
+ALTER TABLE shipment_item ADD COLUMN container_id UUID;
+UPDATE shipment_item SET item_id = item.id FROM item WHERE item.name = shipment_item.item;
+UPDATE shipment_item SET container_id = container.id
+FROM container
+WHERE container.storage = shipment_item.storage AND container.name = shipment_item.container;
+
+ALTER TABLE stocktake ADD COLUMN storage_id UUID;
+UPDATE stocktake SET storage_id = storage.id FROM storage WHERE storage.name = stocktake.storage;
+
+ALTER TABLE stocktake_count ADD COLUMN item_id UUID;
+ALTER TABLE stocktake_count ADD COLUMN container_id UUID;
+UPDATE stocktake_count SET item_id = item.id FROM item WHERE item.name = stocktake_count.item;
+UPDATE stocktake_count SET container_id = container.id
+FROM container
+WHERE container.storage = stocktake_count.storage AND container.name = stocktake_count.container;
+
+ALTER TABLE barcode ADD COLUMN item_id UUID;
+UPDATE barcode SET item_id = item.id FROM item WHERE item.name = barcode.item;
+
+ALTER TABLE item_tag ADD COLUMN item_id UUID;
+UPDATE item_tag SET item_id = item.id FROM item WHERE item.name = item_tag.item;
+
+ALTER TABLE item_unit ADD COLUMN item_id UUID;
+UPDATE item_unit SET item_id = item.id FROM item WHERE item.name = item_unit.item;
+
+ALTER TABLE lot ADD COLUMN item_id UUID;
+ALTER TABLE lot ADD COLUMN container_id UUID;
+UPDATE lot SET item_id = item.id FROM item WHERE item.name = lot.item;
+UPDATE lot SET container_id = container.id
+FROM container
+WHERE container.storage = lot.storage AND container.name = lot.container;
+
+ALTER TABLE write_off ADD COLUMN item_id UUID;
+ALTER TABLE write_off ADD COLUMN container_id UUID;
+UPDATE write_off SET item_id = item.id FROM item WHERE item.name = write_off.item;
+UPDATE write_off SET container_id = container.id
+FROM container
+WHERE container.storage = write_off.storage AND container.name = write_off.container;
+
+-- The name columns go, and with them the foreign keys and keys on them
+ALTER TABLE stored_item
+    DROP COLUMN item CASCADE,
+    DROP COLUMN storage CASCADE,
+    DROP COLUMN container CASCADE;
+ALTER TABLE supplier_item DROP COLUMN supplier, DROP COLUMN item;
+ALTER TABLE log DROP COLUMN item, DROP COLUMN storage, DROP COLUMN container;
+ALTER TABLE move_log
+    DROP COLUMN item,
+    DROP COLUMN from_storage,
+    DROP COLUMN from_container,
+    DROP COLUMN to_storage,
+    DROP COLUMN to_container;
+ALTER TABLE shipment DROP COLUMN supplier;
+ALTER TABLE shipment_item DROP COLUMN item, DROP COLUMN storage, DROP COLUMN container;
+ALTER TABLE stocktake DROP COLUMN storage;
+ALTER TABLE stocktake_count DROP COLUMN item, DROP COLUMN storage, DROP COLUMN container;
+ALTER TABLE barcode DROP COLUMN item;
+ALTER TABLE item_tag DROP COLUMN item;
+ALTER TABLE item_unit DROP COLUMN item;
+ALTER TABLE lot DROP COLUMN item, DROP COLUMN storage, DROP COLUMN container;
+ALTER TABLE write_off DROP COLUMN item, DROP COLUMN storage, DROP COLUMN container;
+ALTER TABLE container DROP COLUMN storage CASCADE;
+
+ALTER TABLE container ALTER COLUMN storage_id SET NOT NULL;
+ALTER TABLE container ADD CONSTRAINT container_storage_id_name_key UNIQUE (storage_id, name);
+ALTER TABLE container ADD CONSTRAINT container_storage_id_fkey
+    FOREIGN KEY (storage_id) REFERENCES storage (id) ON DELETE RESTRICT;
+
+ALTER TABLE stored_item ALTER COLUMN item_id SET NOT NULL;
+ALTER TABLE stored_item ALTER COLUMN container_id SET NOT NULL;
+ALTER TABLE stored_item ADD PRIMARY KEY (item_id, container_id);
+ALTER TABLE stored_item ADD CONSTRAINT stored_item_item_id_fkey
+    FOREIGN KEY (item_id) REFERENCES item (id) ON DELETE CASCADE;
+ALTER TABLE stored_item ADD CONSTRAINT stored_item_container_id_fkey
+    FOREIGN KEY (container_id) REFERENCES container (id) ON DELETE RESTRICT;
+CREATE INDEX stored_item_container ON stored_item (container_id);
+
+ALTER TABLE supplier_item ALTER COLUMN supplier_id SET NOT NULL;
+ALTER TABLE supplier_item ALTER COLUMN item_id SET NOT NULL;
+ALTER TABLE supplier_item ADD PRIMARY KEY (supplier_id, item_id);
+ALTER TABLE supplier_item ADD CONSTRAINT supplier_item_supplier_id_fkey
+    FOREIGN KEY (supplier_id) REFERENCES supplier (id) ON DELETE CASCADE;
+ALTER TABLE supplier_item ADD CONSTRAINT supplier_item_item_id_fkey
+    FOREIGN KEY (item_id) REFERENCES item (id) ON DELETE CASCADE;
+
+ALTER TABLE log ALTER COLUMN item_id SET NOT NULL;
+ALTER TABLE log ALTER COLUMN container_id SET NOT NULL;
+ALTER TABLE log ADD PRIMARY KEY (item_id, time);
+ALTER TABLE log ADD CONSTRAINT log_stored_item_fkey
+    FOREIGN KEY (item_id, container_id) REFERENCES stored_item (item_id, container_id)
+    ON UPDATE CASCADE ON DELETE CASCADE;
+
+ALTER TABLE move_log ALTER COLUMN item_id SET NOT NULL;
+ALTER TABLE move_log ALTER COLUMN to_container_id SET NOT NULL;
+ALTER TABLE move_log ADD PRIMARY KEY (item_id, time);
+ALTER TABLE move_log ADD CONSTRAINT move_log_item_id_fkey
+    FOREIGN KEY (item_id) REFERENCES item (id) ON DELETE CASCADE;
+ALTER TABLE move_log ADD CONSTRAINT move_log_from_container_id_fkey
+    FOREIGN KEY (from_container_id) REFERENCES container (id) ON DELETE SET NULL;
+ALTER TABLE move_log ADD CONSTRAINT move_log_to_container_id_fkey
+    FOREIGN KEY (to_container_id) REFERENCES container (id) ON DELETE CASCADE;
+
+ALTER TABLE shipment ADD CONSTRAINT shipment_supplier_id_fkey
+    FOREIGN KEY (supplier_id) REFERENCES supplier (id) ON DELETE SET NULL;
+
+ALTER TABLE shipment_item ALTER COLUMN item_id SET NOT NULL;
+ALTER TABLE shipment_item ADD PRIMARY KEY (shipment, item_id);
+ALTER TABLE shipment_item ADD CONSTRAINT shipment_item_item_id_fkey
+    FOREIGN KEY (item_id) REFERENCES item (id) ON DELETE CASCADE;
+ALTER TABLE shipment_item ADD CONSTRAINT shipment_item_container_id_fkey
+    FOREIGN KEY (container_id) REFERENCES container (id) ON DELETE SET NULL;
+
+ALTER TABLE stocktake ALTER COLUMN storage_id SET NOT NULL;
+ALTER TABLE stocktake ADD CONSTRAINT stocktake_storage_id_fkey
+    FOREIGN KEY (storage_id) REFERENCES storage (id) ON DELETE CASCADE;
+CREATE UNIQUE INDEX stocktake_open_storage ON stocktake (storage_id) WHERE state = 'open';
+
+ALTER TABLE stocktake_count ALTER COLUMN item_id SET NOT NULL;
+ALTER TABLE stocktake_count ALTER COLUMN container_id SET NOT NULL;
+ALTER TABLE stocktake_count ADD PRIMARY KEY (stocktake, item_id, container_id, user_);
+ALTER TABLE stocktake_count ADD CONSTRAINT stocktake_count_stored_item_fkey
+    FOREIGN KEY (item_id, container_id) REFERENCES stored_item (item_id, container_id)
+    ON UPDATE CASCADE ON DELETE CASCADE;
+
+ALTER TABLE barcode ALTER COLUMN item_id SET NOT NULL;
+ALTER TABLE barcode ADD CONSTRAINT barcode_item_id_fkey
+    FOREIGN KEY (item_id) REFERENCES item (id) ON DELETE CASCADE;
+CREATE INDEX barcode_item ON barcode (item_id);
+
+ALTER TABLE item_tag ALTER COLUMN item_id SET NOT NULL;
+ALTER TABLE item_tag ADD PRIMARY KEY (item_id, tag);
+ALTER TABLE item_tag ADD CONSTRAINT item_tag_item_id_fkey
+    FOREIGN KEY (item_id) REFERENCES item (id) ON DELETE CASCADE;
+
+ALTER TABLE item_unit ALTER COLUMN item_id SET NOT NULL;
+ALTER TABLE item_unit ADD PRIMARY KEY (item_id, name);
+ALTER TABLE item_unit ADD CONSTRAINT item_unit_item_id_fkey
+    FOREIGN KEY (item_id) REFERENCES item (id) ON DELETE CASCADE;
+
+ALTER TABLE lot ALTER COLUMN item_id SET NOT NULL;
+ALTER TABLE lot ALTER COLUMN container_id SET NOT NULL;
+ALTER TABLE lot ADD CONSTRAINT lot_stored_item_fkey
+    FOREIGN KEY (item_id, container_id) REFERENCES stored_item (item_id, container_id)
+    ON UPDATE CASCADE ON DELETE CASCADE;
+CREATE INDEX lot_stored_item ON lot (item_id, container_id);
+
+ALTER TABLE write_off ALTER COLUMN item_id SET NOT NULL;
+ALTER TABLE write_off ALTER COLUMN container_id SET NOT NULL;
+ALTER TABLE write_off ADD CONSTRAINT write_off_stored_item_fkey
+    FOREIGN KEY (item_id, container_id) REFERENCES stored_item (item_id, container_id)
+    ON UPDATE CASCADE ON DELETE CASCADE;
+
+-- The API takes a name or an id in the same place, so a name can't look like an id. Names that
+-- already do get " (renamed)" added to them.
+CREATE OR REPLACE FUNCTION looks_like_id(name TEXT)
+RETURNS BOOLEAN
+AS $$
+SELECT name ~* '^(urn:uuid:|\{)?[0-9a-f]{8}-?[0-9a-f]{4}-?[0-9a-f]{4}-?[0-9a-f]{4}-?[0-9a-f]{12}\}?$';
+$$
+LANGUAGE SQL IMMUTABLE;
+
+UPDATE item SET name = name || ' (renamed)' WHERE looks_like_id(name);
+UPDATE storage SET name = name || ' (renamed)' WHERE looks_like_id(name);
+UPDATE container SET name = name || ' (renamed)' WHERE looks_like_id(name);
+UPDATE supplier SET name = name || ' (renamed)' WHERE looks_like_id(name);
+
+ALTER TABLE item ADD CHECK (NOT looks_like_id(name));
+ALTER TABLE storage ADD CHECK (NOT looks_like_id(name));
+ALTER TABLE container ADD CHECK (NOT looks_like_id(name));
+ALTER TABLE supplier ADD CHECK (NOT looks_like_id(name));
+
+-- The ids of what a write refers to by name. Unlike a subquery these fail like a foreign key
+-- when there is nothing with the name, no name gives no id.
+CREATE OR REPLACE FUNCTION item_ref(item_name TEXT)
+RETURNS UUID
+AS $$
+DECLARE
+    found UUID;
+BEGIN
+    SELECT id INTO found FROM item WHERE name = item_name;
+    IF found IS NULL THEN
+        RAISE foreign_key_violation USING MESSAGE = 'no item ' || item_name, TABLE = 'item';
+    END IF;
+    RETURN found;
+END;
+$$
+LANGUAGE plpgsql STABLE STRICT;
+
+CREATE OR REPLACE FUNCTION storage_ref(storage_name TEXT)
+RETURNS UUID
+AS $$
+DECLARE
+    found UUID;
+BEGIN
+    SELECT id INTO found FROM storage WHERE name = storage_name;
+    IF found IS NULL THEN
+        RAISE foreign_key_violation USING MESSAGE = 'no storage ' || storage_name, TABLE = 'storage';
+    END IF;
+    RETURN found;
+END;
+$$
+LANGUAGE plpgsql STABLE STRICT;
+
+CREATE OR REPLACE FUNCTION container_ref(storage_name TEXT, container_name TEXT)
+RETURNS UUID
+AS $$
+DECLARE
+    found UUID;
+BEGIN
+    SELECT container.id INTO found
+    FROM container
+    JOIN storage ON storage.id = container.storage_id
+    WHERE storage.name = storage_name AND container.name = container_name;
+    IF found IS NULL THEN
+        RAISE foreign_key_violation
+            USING MESSAGE = 'no container ' || container_name || ' in ' || storage_name,
+            TABLE = 'container';
+    END IF;
+    RETURN found;
+END;
+$$
+LANGUAGE plpgsql STABLE STRICT;
+
+CREATE OR REPLACE FUNCTION supplier_ref(supplier_name TEXT)
+RETURNS UUID
+AS $$
+DECLARE
+    found UUID;
+BEGIN
+    SELECT id INTO found FROM supplier WHERE name = supplier_name;
+    IF found IS NULL THEN
+        RAISE foreign_key_violation USING MESSAGE = 'no supplier ' || supplier_name, TABLE = 'supplier';
+    END IF;
+    RETURN found;
+END;
+$$
+LANGUAGE plpgsql STABLE STRICT;
+
+-- A container with the name of the storage it is in, for reading the names of a location
+CREATE VIEW location AS
+SELECT
+    container.id,
+    container.storage_id,
+    storage.name AS storage,
+    container.name AS container,
+    storage.protected
+FROM container
+JOIN storage ON storage.id = container.storage_id;
+
+CREATE OR REPLACE FUNCTION next_inventory(i UUID, c UUID)
+RETURNS TIMESTAMP WITH TIME ZONE
+AS $$
+SELECT
+    MAX(log.time) +
+        LEAST(
+            storage.inventory_interval,
+            container.inventory_interval,
+            item.inventory_interval
+        ) as "time"
+FROM log
+JOIN item ON item.id = log.item_id
+JOIN container ON container.id = log.container_id
+JOIN storage ON storage.id = container.storage_id
+WHERE
+    log.item_id = i AND
+    log.container_id = c
+GROUP BY
+    storage.inventory_interval,
+    container.inventory_interval,
+    item.inventory_interval;
+$$
+LANGUAGE SQL;
+
+-- Lines without a destination still mark every location holding the item as incoming
+CREATE VIEW current_state AS
+SELECT
+    stored_item.item_id,
+    stored_item.container_id,
+    STATE(
+        stored_item.amount,
+        stored_item.min,
+        stored_item.max,
+        EXISTS(
+            SELECT 1
+            FROM shipment_item
+            JOIN shipment ON shipment.id = shipment_item.shipment
+            WHERE
+                shipment_item.item_id = stored_item.item_id AND
+                shipment_item.received < shipment_item.amount AND
+                shipment.state <> 'cancelled' AND
+                (
+                    shipment_item.container_id IS NULL OR
+                    shipment_item.container_id = stored_item.container_id
+                )
+        )
+    ) as "state"
+FROM stored_item;
+
+-- Every stored item below its order floor, with what is already on its way subtracted.
+-- Shipment lines with a destination only count towards that location, lines without one
+-- are spread over the short locations of the item in order.
+CREATE VIEW shortage AS
+WITH incoming AS (
+    SELECT
+        shipment_item.item_id,
+        shipment_item.container_id,
+        SUM(shipment_item.amount - shipment_item.received) AS amount,
+        MIN(shipment.time_arive) AS arrival
+    FROM shipment_item
+    JOIN shipment ON shipment.id = shipment_item.shipment
+    WHERE
+        shipment.state <> 'cancelled' AND
+        shipment_item.received < shipment_item.amount
+    GROUP BY shipment_item.item_id, shipment_item.container_id
+),
+short AS (
+    SELECT
+        stored_item.item_id,
+        stored_item.container_id,
+        location.storage,
+        location.container,
+        stored_item.amount,
+        location.protected,
+        COALESCE(directed.amount, 0) AS directed,
+        directed.arrival AS directed_arrival,
+        GREATEST(stored_item.max - stored_item.amount - COALESCE(directed.amount, 0), 0) AS gap
+    FROM stored_item
+    JOIN location ON location.id = stored_item.container_id
+    LEFT JOIN incoming directed ON
+        directed.item_id = stored_item.item_id AND
+        directed.container_id = stored_item.container_id
+    WHERE stored_item.amount <= stored_item.min
+),
+allocated AS (
+    SELECT
+        short.*,
+        LEAST(
+            short.gap,
+            GREATEST(
+                COALESCE(undirected.amount, 0) - COALESCE(
+                    SUM(short.gap) OVER (
+                        PARTITION BY short.item_id
+                        ORDER BY short.storage, short.container
+                        ROWS BETWEEN UNBOUNDED PRECEDING AND 1 PRECEDING
+                    ),
+                    0
+                ),
+                0
+            )
+        ) AS undirected,
+        undirected.arrival AS undirected_arrival
+    FROM short
+    LEFT JOIN incoming undirected ON
+        undirected.item_id = short.item_id AND
+        undirected.container_id IS NULL
+)
+SELECT
+    item_id,
+    container_id,
+    storage,
+    container,
+    amount,
+    protected,
+    directed + undirected AS incoming,
+    gap - undirected AS amount_to_buy,
+    CASE
+        WHEN undirected > 0 THEN LEAST(directed_arrival, undirected_arrival)
+        ELSE directed_arrival
+    END AS expected_arrival
+FROM allocated;
+
+-- Average daily consumption per stored item over the last 90 days. Between two log entries
+-- the amount is expected to change by what was moved in and out, anything missing on top of
+-- that was consumed and anything extra was restocked.
+CREATE VIEW consumption AS
+WITH entries AS (
+    SELECT
+        item_id,
+        container_id,
+        time,
+        amount,
+        LAG(amount) OVER location AS prev_amount,
+        LAG(time) OVER location AS prev_time
+    FROM log
+    WHERE time > CURRENT_TIMESTAMP - INTERVAL '90 days'
+    WINDOW location AS (PARTITION BY item_id, container_id ORDER BY time)
+),
+changes AS (
+    SELECT
+        entries.item_id,
+        entries.container_id,
+        entries.time,
+        entries.prev_time,
+        GREATEST(
+            entries.prev_amount
+            + COALESCE((
+                SELECT SUM(move_log.amount)
+                FROM move_log
+                WHERE
+                    move_log.item_id = entries.item_id AND
+                    move_log.to_container_id = entries.container_id AND
+                    move_log.time > entries.prev_time AND
+                    move_log.time <= entries.time
+            ), 0)
+            - COALESCE((
+                SELECT SUM(move_log.amount)
+                FROM move_log
+                WHERE
+                    move_log.item_id = entries.item_id AND
+                    move_log.from_container_id = entries.container_id AND
+                    move_log.time > entries.prev_time AND
+                    move_log.time <= entries.time
+            ), 0)
+            - entries.amount,
+            0
+        ) AS consumed
+    FROM entries
+    WHERE entries.prev_amount IS NOT NULL
+)
+SELECT
+    item_id,
+    container_id,
+    (
+        SUM(consumed) / GREATEST(EXTRACT(EPOCH FROM MAX(time) - MIN(prev_time)) / 86400, 1)
+    )::REAL AS per_day
+FROM changes
+GROUP BY item_id, container_id;
+
+CREATE VIEW forecast AS
+SELECT
+    stored_item.item_id,
+    stored_item.container_id,
+    stored_item.amount,
+    stored_item.min,
+    consumption.per_day,
+    CASE WHEN consumption.per_day > 0 THEN
+        CURRENT_TIMESTAMP + make_interval(
+            secs => (stored_item.amount / consumption.per_day * 86400)::DOUBLE PRECISION
+        )
+    END AS stockout,
+    CASE WHEN consumption.per_day > 0 AND stored_item.min IS NOT NULL THEN
+        CURRENT_TIMESTAMP + make_interval(
+            secs => (GREATEST(stored_item.amount - stored_item.min, 0) / consumption.per_day * 86400)::DOUBLE PRECISION
+        )
+    END AS below_min
+FROM stored_item
+LEFT JOIN consumption ON
+    consumption.item_id = stored_item.item_id AND
+    consumption.container_id = stored_item.container_id;
@@ -0,0 +1,1875 @@
+use actix_web::{
+    dev::{ServiceFactory, ServiceRequest},
+    web::Data,
+};
+use sqlx::{Pool, Postgres};
+use utoipa_actix_web::{scope, UtoipaApp};
+
+use crate::{
+    auth::{
+        self,
+        provider::{IdentityProvider, PermissionProvider},
+        types::{AuthMiddleware, SessionConfig},
+    },
+    category, item, logging, lot, shipment, shortage, stats, stocktake, storage, supplier,
+};
+
+/// Everything the routes need, shared by every worker
+#[derive(Clone)]
+pub(crate) struct AppData {
+    pub(crate) db: Data<Pool<Postgres>>,
+    pub(crate) identity: Data<dyn IdentityProvider>,
+    pub(crate) permissions: Data<dyn PermissionProvider>,
+    pub(crate) session: SessionConfig,
+}
+
+/// Adds the login routes and the api to the app, the same way for the server and the tests
+pub(crate) fn api<T>(app: UtoipaApp<T>, data: &AppData) -> UtoipaApp<T>
+where
+    T: ServiceFactory<ServiceRequest, Config = (), Error = actix_web::Error, InitError = ()>,
+{
+    app.map(|app| {
+        app.app_data(data.db.clone())
+            .app_data(data.identity.clone())
+            .app_data(data.permissions.clone())
+    })
+    .service(scope("/auth").configure(auth::config()))
+    .service(
+        scope("/api") // Every thing in this scope requires the user to be logged in using sso
+            .wrap(AuthMiddleware::new(
+                String::from(auth::LOGIN_PATH),
+                data.session,
+            ))
+            .configure(item::config())
+            .configure(supplier::config())
+            .configure(storage::config())
+            .configure(shortage::config())
+            .configure(stats::config())
+            .configure(logging::config())
+            .configure(shipment::config())
+            .configure(stocktake::config())
+            .configure(category::config())
+            .configure(lot::config())
+            .service(auth::user_info)
+            .service(auth::invalidate_cache)
+            .service(auth::revoke_sessions),
+    )
+}
+
+/// The whole app against a test database, with fake identities instead of OIDC and Hive
+#[cfg(test)]
+pub(crate) mod harness {
+    use std::{cell::RefCell, collections::HashMap, env, sync::Arc};
+
+    use actix_http::Request;
+    use actix_web::{
+        body::MessageBody,
+        cookie::Cookie,
+        dev::{Service, ServiceResponse},
+        http::{header, StatusCode},
+        test::{self, TestRequest},
+        web::Data,
+        App,
+    };
+    use serde_json::{json, Value};
+    use sqlx::{Pool, Postgres};
+    use utoipa_actix_web::AppExt;
+
+    use super::{api, AppData};
+    use crate::auth::{provider::DevProvider, types::SessionConfig};
+
+    /// Allowed everything
+    pub(crate) const ADMIN: &str = "admin";
+    /// Writes to meta and is in the group that owns its suppliers
+    pub(crate) const META: &str = "meta";
+    /// Reads the protected storage spritis
+    pub(crate) const SPRITIS: &str = "spritis";
+    /// Writes to the protected storage spritis
+    pub(crate) const SPRITIS_WRITER: &str = "spritis-writer";
+    /// Reads meta, but nothing protected
+    pub(crate) const META_READER: &str = "meta-reader";
+    /// Writes to the container tejplåda, but not the rest of meta
+    pub(crate) const TEJPLADA: &str = "tejplada";
+    /// Logged in, but allowed nothing
+    pub(crate) const NOBODY: &str = "nobody";
+    /// The API token, reads spritis
+    pub(crate) const TOKEN: &str = "token";
+
+    pub(crate) const GROUP: &str = "metadorerna@datasektionen.se";
+
+    fn identities() -> DevProvider {
+        serde_json::from_value(json!({
+            "users": {
+                ADMIN: { "permissions": [{ "id": "admin", "scope": "" }] },
+                META: {
+                    "permissions": [{ "id": "write", "scope": "meta" }],
+                    "groups": [GROUP]
+                },
+                SPRITIS: { "permissions": [{ "id": "read", "scope": "spritis" }] },
+                SPRITIS_WRITER: { "permissions": [{ "id": "write", "scope": "spritis" }] },
+                META_READER: { "permissions": [{ "id": "read", "scope": "meta" }] },
+                TEJPLADA: { "permissions": [{ "id": "write", "scope": "tejplåda" }] },
+                NOBODY: {}
+            },
+            "tokens": {
+                TOKEN: {
+                    "id": "1",
+                    "name": "test token",
+                    "permissions": [{ "id": "read", "scope": "spritis" }]
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    pub(crate) struct TestApp<S> {
+        service: S,
+        /// Session cookies of the users that have logged in
+        cookies: RefCell<HashMap<String, Cookie<'static>>>,
+    }
+
+    pub(crate) async fn init(
+        db: Pool<Postgres>,
+    ) -> TestApp<
+        impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = actix_web::Error>,
+    > {
+        env::set_var("APP_SECRET", "1234567890abcdefabcdef");
+
+        let dev = Arc::new(identities());
+        let data = AppData {
+            db: Data::new(db),
+            identity: Data::from(dev.clone() as Arc<_>),
+            permissions: Data::from(dev as Arc<_>),
+            session: SessionConfig::default(),
+        };
+
+        let service = test::init_service(api(App::new().into_utoipa_app(), &data).into_app()).await;
+
+        TestApp {
+            service,
+            cookies: RefCell::new(HashMap::new()),
+        }
+    }
+
+    impl<S, B> TestApp<S>
+    where
+        S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+        B: MessageBody,
+    {
+        /// Logs in through /auth the way a browser would and returns the session cookie
+        async fn login(&self, user: &str) -> Cookie<'static> {
+            if let Some(cookie) = self.cookies.borrow().get(user) {
+                return cookie.clone();
+            }
+
+            let response = test::call_service(
+                &self.service,
+                TestRequest::get()
+                    .uri(&format!("/auth/oidc/login?user={user}"))
+                    .to_request(),
+            )
+            .await;
+            let callback = response.headers().get(header::LOCATION).unwrap();
+            let callback = callback.to_str().unwrap().to_string();
+
+            let response = test::call_service(
+                &self.service,
+                TestRequest::get().uri(&callback).to_request(),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+            let cookie = response
+                .response()
+                .cookies()
+                .find(|cookie| cookie.name() == "token")
+                .expect("a session cookie")
+                .into_owned();
+
+            self.cookies
+                .borrow_mut()
+                .insert(user.to_string(), cookie.clone());
+            cookie
+        }
+
+        /// Sends the request as the user and fails the test unless the response has the
+        /// expected status. TOKEN sends it with the API token and None without credentials.
+        /// Returns the body, as a string if it is not json.
+        pub(crate) async fn expect(
+            &self,
+            user: Option<&str>,
+            req: TestRequest,
+            status: StatusCode,
+        ) -> Value {
+            let (route, actual, body) = self.send(user, req).await;
+            assert_eq!(actual, status, "{route} as {user:?}: {body}");
+            body
+        }
+
+        async fn send(&self, user: Option<&str>, req: TestRequest) -> (String, StatusCode, Value) {
+            let req = match user {
+                Some(TOKEN) => {
+                    req.insert_header((header::AUTHORIZATION, format!("Bearer {TOKEN}")))
+                }
+                Some(user) => req.cookie(self.login(user).await),
+                None => req,
+            };
+            let req = req.to_request();
+            let route = format!("{} {}", req.method(), req.uri());
+
+            let response = test::call_service(&self.service, req).await;
+
+            let status = response.status();
+            let body = test::read_body(response).await;
+
+            (
+                route,
+                status,
+                serde_json::from_slice(&body)
+                    .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&body).into_owned())),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use actix_http::Request;
+    use actix_web::{
+        body::MessageBody,
+        dev::{Service, ServiceResponse},
+        http::{header, Method, StatusCode},
+        test::TestRequest,
+    };
+    use serde_json::{json, Value};
+    use sqlx::{types::chrono::Utc, Pool, Postgres};
+
+    use super::harness::{
+        self, TestApp, ADMIN, GROUP, META, META_READER, NOBODY, SPRITIS, SPRITIS_WRITER, TEJPLADA,
+        TOKEN,
+    };
+    use crate::auth::LOGIN_PATH;
+
+    const OK: StatusCode = StatusCode::OK;
+    const BAD_REQUEST: StatusCode = StatusCode::BAD_REQUEST;
+    const UNAUTHORIZED: StatusCode = StatusCode::UNAUTHORIZED;
+    const FORBIDDEN: StatusCode = StatusCode::FORBIDDEN;
+    const NOT_FOUND: StatusCode = StatusCode::NOT_FOUND;
+    const CONFLICT: StatusCode = StatusCode::CONFLICT;
+
+    /// Every route under /api
+    const ROUTES: [(&str, &str); 59] = [
+        ("GET", "/api/items"),
+        ("GET", "/api/item"),
+        ("POST", "/api/item"),
+        ("PATCH", "/api/item"),
+        ("PUT", "/api/item"),
+        ("DELETE", "/api/item"),
+        ("PATCH", "/api/item/move"),
+        ("GET", "/api/item/scan"),
+        ("POST", "/api/item/barcode"),
+        ("DELETE", "/api/item/barcode"),
+        ("POST", "/api/item/unit"),
+        ("DELETE", "/api/item/unit"),
+        ("POST", "/api/supply"),
+        ("PATCH", "/api/supply"),
+        ("DELETE", "/api/supply"),
+        ("GET", "/api/supplier"),
+        ("POST", "/api/supplier"),
+        ("PATCH", "/api/supplier"),
+        ("DELETE", "/api/supplier"),
+        ("GET", "/api/storages"),
+        ("GET", "/api/storages/containers"),
+        ("GET", "/api/storages/containers/items"),
+        ("POST", "/api/storage"),
+        ("PATCH", "/api/storage"),
+        ("DELETE", "/api/storage"),
+        ("POST", "/api/container"),
+        ("PATCH", "/api/container"),
+        ("PATCH", "/api/container/move"),
+        ("DELETE", "/api/container"),
+        ("GET", "/api/shortage"),
+        ("GET", "/api/shortage/order"),
+        ("POST", "/api/shortage/order"),
+        ("GET", "/api/forecast"),
+        ("GET", "/api/inventory"),
+        ("POST", "/api/inventory"),
+        ("GET", "/api/stats"),
+        ("GET", "/api/log"),
+        ("GET", "/api/shipment"),
+        ("POST", "/api/shipment"),
+        ("POST", "/api/shipment/receive"),
+        ("PATCH", "/api/shipment/state"),
+        ("DELETE", "/api/shipment"),
+        ("GET", "/api/stocktake"),
+        ("GET", "/api/stocktake/report"),
+        ("POST", "/api/stocktake"),
+        ("POST", "/api/stocktake/count"),
+        ("POST", "/api/stocktake/finalize"),
+        ("DELETE", "/api/stocktake"),
+        ("GET", "/api/categories"),
+        ("POST", "/api/category"),
+        ("PATCH", "/api/category"),
+        ("DELETE", "/api/category"),
+        ("GET", "/api/lots"),
+        ("GET", "/api/lots/expiring"),
+        ("POST", "/api/lot"),
+        ("POST", "/api/lot/write-off"),
+        ("GET", "/api/userinfo"),
+        ("DELETE", "/api/sessions"),
+        ("DELETE", "/api/permissions/cache"),
+    ];
+
+    fn get(uri: &str) -> TestRequest {
+        TestRequest::get().uri(uri)
+    }
+
+    fn delete(uri: &str) -> TestRequest {
+        TestRequest::delete().uri(uri)
+    }
+
+    fn send(method: Method, uri: &str, body: Value) -> TestRequest {
+        TestRequest::default()
+            .method(method)
+            .uri(uri)
+            .set_payload(body.to_string())
+    }
+
+    fn names(list: &Value) -> Vec<&str> {
+        list.as_array()
+            .unwrap_or_else(|| panic!("expected a list, got {list}"))
+            .iter()
+            .map(|entry| entry["name"].as_str().unwrap())
+            .collect()
+    }
+
+    /// meta with the container tejplåda holding tejp, and the protected spritis holding gin
+    async fn setup<S, B>(app: &TestApp<S>)
+    where
+        S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+        B: MessageBody,
+    {
+        for (name, protected) in [("meta", false), ("spritis", true)] {
+            let storage = json!({ "name": name, "protected": protected });
+            app.expect(
+                Some(META),
+                send(Method::POST, "/api/storage", storage.clone()),
+                FORBIDDEN,
+            )
+            .await;
+            app.expect(Some(ADMIN), send(Method::POST, "/api/storage", storage), OK)
+                .await;
+        }
+
+        let container = json!({ "name": "tejplåda", "storage": "meta" });
+        app.expect(
+            Some(SPRITIS),
+            send(Method::POST, "/api/container", container.clone()),
+            FORBIDDEN,
+        )
+        .await;
+        app.expect(
+            Some(META),
+            send(Method::POST, "/api/container", container),
+            OK,
+        )
+        .await;
+
+        let tejp = json!({
+            "storage": "meta",
+            "container": "tejplåda",
+            "name": "tejp",
+            "min": 2.0,
+            "max": 10.0,
+            "amount": 3.0,
+            "unit": "st"
+        });
+        app.expect(
+            Some(NOBODY),
+            send(Method::POST, "/api/item", tejp.clone()),
+            FORBIDDEN,
+        )
+        .await;
+        app.expect(Some(META), send(Method::POST, "/api/item", tejp), OK)
+            .await;
+
+        let gin = json!({
+            "storage": "spritis",
+            "container": "",
+            "name": "gin",
+            "min": 5.0,
+            "max": 10.0,
+            "amount": 1.0,
+            "unit": "flaskor"
+        });
+        app.expect(
+            Some(META),
+            send(Method::POST, "/api/item", gin.clone()),
+            FORBIDDEN,
+        )
+        .await;
+        app.expect(Some(ADMIN), send(Method::POST, "/api/item", gin), OK)
+            .await;
+
+        let supplier = json!({ "name": "Systembolaget", "group": GROUP });
+        app.expect(
+            Some(SPRITIS),
+            send(Method::POST, "/api/supplier", supplier.clone()),
+            FORBIDDEN,
+        )
+        .await;
+        app.expect(
+            Some(META),
+            send(Method::POST, "/api/supplier", supplier),
+            OK,
+        )
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn routes_require_login(db: Pool<Postgres>) {
+        let app = harness::init(db).await;
+
+        for (method, uri) in ROUTES {
+            let method = Method::from_bytes(method.as_bytes()).unwrap();
+            let body = app
+                .expect(None, send(method, uri, json!({})), UNAUTHORIZED)
+                .await;
+            assert_eq!(body["error"], "unauthorized", "{uri}");
+            assert_eq!(body["message"], "Not logged in", "{uri}");
+            assert_eq!(body["login"], LOGIN_PATH, "{uri}");
+        }
+    }
+
+    #[sqlx::test]
+    async fn malformed_bodies(db: Pool<Postgres>) {
+        let app = harness::init(db).await;
+
+        for (method, uri) in ROUTES {
+            if method == "GET" || method == "DELETE" {
+                continue;
+            }
+            let method = Method::from_bytes(method.as_bytes()).unwrap();
+            let req = TestRequest::default()
+                .method(method)
+                .uri(uri)
+                .set_payload("{ not json");
+            app.expect(Some(ADMIN), req, BAD_REQUEST).await;
+        }
+    }
+
+    #[sqlx::test]
+    async fn item_routes(db: Pool<Postgres>) {
+        let app = harness::init(db).await;
+        setup(&app).await;
+
+        let items = app.expect(Some(NOBODY), get("/api/items"), OK).await;
+        assert_eq!(names(&items), ["tejp"]);
+        let items = app.expect(Some(SPRITIS), get("/api/items"), OK).await;
+        assert_eq!(names(&items).len(), 2);
+        let items = app
+            .expect(Some(TOKEN), get("/api/items?name=gin"), OK)
+            .await;
+        assert_eq!(names(&items), ["gin"]);
+
+        let item = app
+            .expect(Some(NOBODY), get("/api/item?name=tejp"), OK)
+            .await;
+        assert_eq!(item["name"], "tejp");
+        assert_eq!(item["unit"], "st");
+        assert_eq!(item["storage"][0]["container"], "tejplåda");
+        app.expect(Some(NOBODY), get("/api/item?name=gin"), NOT_FOUND)
+            .await;
+        app.expect(Some(NOBODY), get("/api/item?name=rom"), NOT_FOUND)
+            .await;
+        app.expect(Some(NOBODY), get("/api/item"), BAD_REQUEST)
+            .await;
+
+        let supply = json!({ "supplier": "Systembolaget", "name": "tejp", "prefered": true });
+        for method in [Method::POST, Method::PATCH] {
+            app.expect(
+                Some(SPRITIS),
+                send(method.clone(), "/api/supply", supply.clone()),
+                FORBIDDEN,
+            )
+            .await;
+            app.expect(Some(META), send(method, "/api/supply", supply.clone()), OK)
+                .await;
+        }
+        let item = app.expect(Some(META), get("/api/item?name=tejp"), OK).await;
+        assert_eq!(item["supplier"].as_array().unwrap().len(), 1);
+
+        let change = json!({ "name": "tejp", "unit": "rullar" });
+        app.expect(
+            Some(NOBODY),
+            send(Method::PATCH, "/api/item", change.clone()),
+            FORBIDDEN,
+        )
+        .await;
+        app.expect(Some(META), send(Method::PATCH, "/api/item", change), OK)
+            .await;
+
+        let stored = json!({
+            "storage": "meta",
+            "container": "tejplåda",
+            "name": "tejp",
+            "min": 1.0,
+            "max": 8.0,
+            "amount": 4.0
+        });
+        app.expect(
+            Some(NOBODY),
+            send(Method::PUT, "/api/item", stored.clone()),
+            FORBIDDEN,
+        )
+        .await;
+        app.expect(Some(META), send(Method::PUT, "/api/item", stored), OK)
+            .await;
+
+        let item_move = json!({
+            "name": "tejp",
+            "amount": 4.0,
+            "from_storage": "meta",
+            "from_container": "tejplåda",
+            "to_storage": "spritis",
+            "to_container": ""
+        });
+        let body = app
+            .expect(
+                Some(META),
+                send(Method::PATCH, "/api/item/move", item_move.clone()),
+                FORBIDDEN,
+            )
+            .await;
+        assert_eq!(
+            body["refused"],
+            json!({ "check": "move_item", "permission": "write", "scope": ["spritis"] })
+        );
+        app.expect(
+            Some(ADMIN),
+            send(Method::PATCH, "/api/item/move", item_move),
+            OK,
+        )
+        .await;
+        let item = app
+            .expect(Some(ADMIN), get("/api/item?name=tejp"), OK)
+            .await;
+        assert_eq!(item["storage"][0]["storage"], "spritis");
+
+        let item_move = json!({
+            "name": "tejp",
+            "amount": 4.0,
+            "from_storage": "spritis",
+            "from_container": "",
+            "to_storage": "meta",
+            "to_container": "tejplåda"
+        });
+        app.expect(
+            Some(ADMIN),
+            send(Method::PATCH, "/api/item/move", item_move),
+            OK,
+        )
+        .await;
+
+        let log = app.expect(Some(ADMIN), get("/api/log?name=tejp"), OK).await;
+        assert!(!log.as_array().unwrap().is_empty());
+
+        let unsupply = "/api/supply?name=tejp&supplier=Systembolaget";
+        app.expect(Some(NOBODY), delete(unsupply), FORBIDDEN).await;
+        app.expect(Some(META), delete(unsupply), OK).await;
+
+        let remove = "/api/item?name=tejp&storage=meta&container=tejpl%C3%A5da";
+        app.expect(Some(SPRITIS), delete(remove), FORBIDDEN).await;
+        app.expect(Some(META), delete(remove), OK).await;
+    }
+
+    #[sqlx::test]
+    async fn barcode_routes(db: Pool<Postgres>) {
+        let app = harness::init(db).await;
+        setup(&app).await;
+
+        let barcode = json!({ "name": "tejp", "barcode": "036000291452" });
+        app.expect(
+            Some(SPRITIS),
+            send(Method::POST, "/api/item/barcode", barcode.clone()),
+            FORBIDDEN,
+        )
+        .await;
+        app.expect(
+            Some(META),
+            send(Method::POST, "/api/item/barcode", barcode.clone()),
+            OK,
+        )
+        .await;
+        app.expect(
+            Some(META),
+            send(Method::POST, "/api/item/barcode", barcode),
+            CONFLICT,
+        )
+        .await;
+        let body = app
+            .expect(
+                Some(META),
+                send(
+                    Method::POST,
+                    "/api/item/barcode",
+                    json!({ "name": "tejp", "barcode": "036000291453" }),
+                ),
+                BAD_REQUEST,
+            )
+            .await;
+        assert_eq!(body["fields"][0]["field"], "barcode");
+
+        let rom = json!({
+            "storage": "spritis",
+            "container": "",
+            "name": "rom",
+            "amount": 2.0,
+            "barcodes": ["7310865004703"]
+        });
+        app.expect(Some(ADMIN), send(Method::POST, "/api/item", rom), OK)
+            .await;
+
+        // The UPC-A code is found as the EAN-13 it equals
+        let item = app
+            .expect(
+                Some(NOBODY),
+                get("/api/item/scan?barcode=0036000291452"),
+                OK,
+            )
+            .await;
+        assert_eq!(item["name"], "tejp");
+        assert_eq!(item["barcodes"], json!(["0036000291452"]));
+        assert_eq!(item["storage"][0]["container"], "tejplåda");
+        app.expect(
+            Some(NOBODY),
+            get("/api/item/scan?barcode=7310865004703"),
+            NOT_FOUND,
+        )
+        .await;
+        let item = app
+            .expect(
+                Some(SPRITIS),
+                get("/api/item/scan?barcode=7310865004703"),
+                OK,
+            )
+            .await;
+        assert_eq!(item["name"], "rom");
+        app.expect(
+            Some(NOBODY),
+            get("/api/item/scan?barcode=4006381333931"),
+            NOT_FOUND,
+        )
+        .await;
+
+        let count = json!({
+            "items": [{ "barcode": "036000291452", "storage": "meta", "container": "tejplåda", "amount": 7.0 }]
+        });
+        app.expect(Some(META), send(Method::POST, "/api/inventory", count), OK)
+            .await;
+        let item_move = json!({
+            "barcode": "036000291452",
+            "amount": 7.0,
+            "from_storage": "meta",
+            "from_container": "tejplåda",
+            "to_storage": "meta",
+            "to_container": ""
+        });
+        app.expect(
+            Some(META),
+            send(Method::PATCH, "/api/item/move", item_move),
+            OK,
+        )
+        .await;
+        let item = app.expect(Some(META), get("/api/item?name=tejp"), OK).await;
+        assert_eq!(item["storage"][0]["container"], "");
+        assert_eq!(item["storage"][0]["amount"], 7.0);
+
+        let both = json!({
+            "items": [{ "name": "tejp", "barcode": "036000291452", "storage": "meta", "container": "", "amount": 1.0 }]
+        });
+        let body = app
+            .expect(
+                Some(META),
+                send(Method::POST, "/api/inventory", both),
+                BAD_REQUEST,
+            )
+            .await;
+        assert_eq!(body["fields"][0]["field"], "items[0].barcode");
+
+        app.expect(
+            Some(SPRITIS),
+            delete("/api/item/barcode?barcode=036000291452"),
+            FORBIDDEN,
+        )
+        .await;
+        app.expect(
+            Some(META),
+            delete("/api/item/barcode?barcode=036000291452"),
+            OK,
+        )
+        .await;
+        app.expect(
+            Some(NOBODY),
+            get("/api/item/scan?barcode=036000291452"),
+            NOT_FOUND,
+        )
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn ids(db: Pool<Postgres>) {
+        let app = harness::init(db).await;
+        setup(&app).await;
+
+        let hylla = json!({ "name": "hylla", "storage": "meta" });
+        let container = app
+            .expect(Some(META), send(Method::POST, "/api/container", hylla), OK)
+            .await;
+        let storages = app.expect(Some(NOBODY), get("/api/storages"), OK).await;
+        let storage = storages[0]["id"].as_str().unwrap().to_string();
+        let container = container.as_str().unwrap().to_string();
+
+        let silvertejp = json!({
+            "storage": storage,
+            "container": container,
+            "name": "silvertejp",
+            "amount": 4.0
+        });
+        let item = app
+            .expect(Some(META), send(Method::POST, "/api/item", silvertejp), OK)
+            .await;
+        let item = item.as_str().unwrap().to_string();
+
+        let rename = json!({ "name": item, "new_name": "gaffatejp", "unit": "rullar" });
+        app.expect(Some(META), send(Method::PATCH, "/api/item", rename), OK)
+            .await;
+        let rename = json!({ "name": storage, "new_name": "Meta", "protected": false });
+        app.expect(Some(ADMIN), send(Method::PATCH, "/api/storage", rename), OK)
+            .await;
+
+        // The ids stay the same after the renames and the history follows the item
+        let found = app
+            .expect(Some(ADMIN), get(&format!("/api/item?name={item}")), OK)
+            .await;
+        assert_eq!(found["id"], item.as_str());
+        assert_eq!(found["name"], "gaffatejp");
+        assert_eq!(found["storage"][0]["storage"], "Meta");
+        assert_eq!(found["storage"][0]["container"], "hylla");
+        let log = app
+            .expect(Some(ADMIN), get("/api/log?name=gaffatejp"), OK)
+            .await;
+        assert!(!log.as_array().unwrap().is_empty());
+
+        let tree = app
+            .expect(
+                Some(ADMIN),
+                get(&format!("/api/storages/containers/items?name={storage}")),
+                OK,
+            )
+            .await;
+        assert!(tree.to_string().contains(&container), "{tree}");
+
+        let remove = format!("/api/item?name={item}&storage={storage}&container={container}");
+        app.expect(Some(ADMIN), delete(&remove), OK).await;
+        let remove = format!("/api/container?name={container}&storage={storage}");
+        app.expect(Some(ADMIN), delete(&remove), OK).await;
+    }
+
+    #[sqlx::test]
+    async fn categories(db: Pool<Postgres>) {
+        let app = harness::init(db).await;
+        setup(&app).await;
+
+        let dryck = json!({ "name": "dryck" });
+        app.expect(
+            Some(META),
+            send(Method::POST, "/api/category", dryck.clone()),
+            FORBIDDEN,
+        )
+        .await;
+        app.expect(Some(ADMIN), send(Method::POST, "/api/category", dryck), OK)
+            .await;
+        for category in [
+            json!({ "name": "sprit", "parent": "dryck" }),
+            json!({ "name": "kontor" }),
+        ] {
+            app.expect(
+                Some(ADMIN),
+                send(Method::POST, "/api/category", category),
+                OK,
+            )
+            .await;
+        }
+
+        let gin =
+            json!({ "name": "gin", "unit": "flaskor", "category": "sprit", "tags": ["stark"] });
+        app.expect(Some(ADMIN), send(Method::PATCH, "/api/item", gin), OK)
+            .await;
+        let tejp =
+            json!({ "name": "tejp", "unit": "st", "category": "kontor", "tags": ["forbrukning"] });
+        app.expect(Some(META), send(Method::PATCH, "/api/item", tejp), OK)
+            .await;
+        let unknown = json!({ "name": "tejp", "unit": "st", "category": "verktyg" });
+        app.expect(
+            Some(META),
+            send(Method::PATCH, "/api/item", unknown),
+            NOT_FOUND,
+        )
+        .await;
+
+        let items = app
+            .expect(Some(SPRITIS), get("/api/items?category=dryck"), OK)
+            .await;
+        assert_eq!(names(&items), ["gin"]);
+        let items = app
+            .expect(Some(SPRITIS), get("/api/items?tag=forbrukning"), OK)
+            .await;
+        assert_eq!(names(&items), ["tejp"]);
+        let item = app
+            .expect(Some(SPRITIS), get("/api/item?name=gin"), OK)
+            .await;
+        assert_eq!(item["category"], "sprit");
+        assert_eq!(item["tags"], json!(["stark"]));
+
+        // gin is the only item short, and it is in a subcategory of drinks
+        let shortage = app
+            .expect(Some(SPRITIS), get("/api/shortage?category=dryck"), OK)
+            .await;
+        assert_eq!(names(&shortage), ["gin"]);
+        assert_eq!(shortage[0]["category"], "sprit");
+        let shortage = app
+            .expect(Some(SPRITIS), get("/api/shortage?category=kontor"), OK)
+            .await;
+        assert_eq!(names(&shortage).len(), 0);
+
+        let categories = app.expect(Some(NOBODY), get("/api/categories"), OK).await;
+        assert_eq!(names(&categories), ["dryck", "kontor", "sprit"]);
+        let circle = json!({ "name": "dryck", "parent": "sprit" });
+        app.expect(
+            Some(ADMIN),
+            send(Method::PATCH, "/api/category", circle),
+            BAD_REQUEST,
+        )
+        .await;
+        app.expect(Some(ADMIN), delete("/api/category?name=sprit"), CONFLICT)
+            .await;
+
+        // Renaming leaves out the category and tags, they are kept
+        let rename = json!({
+            "name": "gin",
+            "new_name": "Gin",
+            "unit": "flaskor",
+            "inventory_interval": null
+        });
+        app.expect(Some(ADMIN), send(Method::PATCH, "/api/item", rename), OK)
+            .await;
+        let item = app
+            .expect(Some(SPRITIS), get("/api/item?name=Gin"), OK)
+            .await;
+        assert_eq!(item["category"], "sprit");
+        assert_eq!(item["tags"], json!(["stark"]));
+
+        let clear = json!({ "name": "Gin", "unit": "flaskor", "category": null, "tags": [] });
+        app.expect(Some(ADMIN), send(Method::PATCH, "/api/item", clear), OK)
+            .await;
+        let item = app
+            .expect(Some(SPRITIS), get("/api/item?name=Gin"), OK)
+            .await;
+        assert_eq!(item["category"], Value::Null);
+        assert_eq!(item["tags"], json!([]));
+        app.expect(Some(ADMIN), delete("/api/category?name=sprit"), OK)
+            .await;
+
+        // Renaming leaves out the parent, the category stays where it is
+        let nested = json!({ "name": "kontor", "parent": "dryck" });
+        app.expect(
+            Some(ADMIN),
+            send(Method::PATCH, "/api/category", nested),
+            OK,
+        )
+        .await;
+        let rename = json!({ "name": "kontor", "new_name": "kontorsmaterial" });
+        app.expect(
+            Some(ADMIN),
+            send(Method::PATCH, "/api/category", rename),
+            OK,
+        )
+        .await;
+        let categories = app.expect(Some(NOBODY), get("/api/categories"), OK).await;
+        assert_eq!(names(&categories), ["dryck", "kontorsmaterial"]);
+        assert_eq!(categories[1]["parent"], "dryck");
+        let top = json!({ "name": "kontorsmaterial", "parent": null });
+        app.expect(Some(ADMIN), send(Method::PATCH, "/api/category", top), OK)
+            .await;
+        let categories = app.expect(Some(NOBODY), get("/api/categories"), OK).await;
+        assert_eq!(categories[1]["parent"], Value::Null);
+    }
+
+    #[sqlx::test]
+    async fn lots(db: Pool<Postgres>) {
+        let app = harness::init(db).await;
+        setup(&app).await;
+
+        let lot = json!({
+            "item": "tejp",
+            "storage": "meta",
+            "container": "tejplåda",
+            "amount": 2.0,
+            "best_before": Utc::now().date_naive().to_string()
+        });
+        app.expect(
+            Some(NOBODY),
+            send(Method::POST, "/api/lot", lot.clone()),
+            FORBIDDEN,
+        )
+        .await;
+        let id = app
+            .expect(Some(META), send(Method::POST, "/api/lot", lot), OK)
+            .await;
+
+        let expiring = app
+            .expect(Some(NOBODY), get("/api/lots/expiring"), OK)
+            .await;
+        assert_eq!(expiring.as_array().unwrap().len(), 1);
+        assert_eq!(expiring[0]["id"], id);
+        assert_eq!(expiring[0]["amount"], 2.0);
+        app.expect(Some(NOBODY), get("/api/lots/expiring?days=-1"), BAD_REQUEST)
+            .await;
+        let lots = app
+            .expect(Some(NOBODY), get("/api/lots?name=tejp"), OK)
+            .await;
+        assert_eq!(lots.as_array().unwrap().len(), 1);
+
+        let too_much = json!({ "id": id, "amount": 5.0, "reason": "expired" });
+        app.expect(
+            Some(META),
+            send(Method::POST, "/api/lot/write-off", too_much),
+            BAD_REQUEST,
+        )
+        .await;
+        let write_off = json!({ "id": id, "reason": "expired" });
+        app.expect(
+            Some(NOBODY),
+            send(Method::POST, "/api/lot/write-off", write_off.clone()),
+            FORBIDDEN,
+        )
+        .await;
+        app.expect(
+            Some(META),
+            send(Method::POST, "/api/lot/write-off", write_off),
+            OK,
+        )
+        .await;
+
+        let item = app.expect(Some(META), get("/api/item?name=tejp"), OK).await;
+        assert_eq!(item["storage"][0]["amount"], 3.0);
+        let expiring = app
+            .expect(Some(NOBODY), get("/api/lots/expiring"), OK)
+            .await;
+        assert!(expiring.as_array().unwrap().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn units(db: Pool<Postgres>) {
+        let app = harness::init(db).await;
+        setup(&app).await;
+
+        let cola = json!({
+            "storage": "meta",
+            "container": "",
+            "name": "cola",
+            "amount": 2.0,
+            "unit": "burk",
+            "amount_unit": "24-pack",
+            "units": [{ "name": "24-pack", "factor": 24.0 }]
+        });
+        app.expect(Some(META), send(Method::POST, "/api/item", cola), OK)
+            .await;
+        let cola = json!({
+            "storage": "meta",
+            "container": "tejplåda",
+            "name": "cola",
+            "amount": 1.0,
+            "amount_unit": "24-pack"
+        });
+        app.expect(Some(META), send(Method::POST, "/api/item", cola), OK)
+            .await;
+        let item = app.expect(Some(META), get("/api/item?name=cola"), OK).await;
+        assert_eq!(item["unit"], "burk");
+        assert_eq!(
+            item["units"],
+            json!([{ "name": "24-pack", "factor": 24.0 }])
+        );
+        assert_eq!(item["storage"][0]["amount"], 48.0);
+        assert_eq!(item["storage"][1]["amount"], 24.0);
+
+        let item_move = json!({
+            "name": "cola",
+            "amount": 0.5,
+            "unit": "24-pack",
+            "from_storage": "meta",
+            "from_container": "",
+            "to_storage": "meta",
+            "to_container": "tejplåda"
+        });
+        app.expect(
+            Some(META),
+            send(Method::PATCH, "/api/item/move", item_move),
+            OK,
+        )
+        .await;
+        let item = app.expect(Some(META), get("/api/item?name=cola"), OK).await;
+        assert_eq!(item["storage"][0]["amount"], 36.0);
+        assert_eq!(item["storage"][1]["amount"], 36.0);
+
+        let unknown = json!({ "items": [
+            { "name": "cola", "storage": "meta", "container": "", "amount": 1.0, "unit": "flak" }
+        ]});
+        let body = app
+            .expect(
+                Some(META),
+                send(Method::POST, "/api/inventory", unknown),
+                BAD_REQUEST,
+            )
+            .await;
+        assert_eq!(body["fields"][0]["field"], "items[0].unit");
+        let stock = json!({ "items": [
+            { "name": "cola", "storage": "meta", "container": "", "amount": 1.0, "unit": "24-pack" }
+        ]});
+        app.expect(Some(META), send(Method::POST, "/api/inventory", stock), OK)
+            .await;
+        let item = app.expect(Some(META), get("/api/item?name=cola"), OK).await;
+        assert_eq!(item["storage"][0]["amount"], 24.0);
+
+        let unit = json!({ "name": "cola", "unit": "6-pack", "factor": 6.0 });
+        app.expect(
+            Some(NOBODY),
+            send(Method::POST, "/api/item/unit", unit.clone()),
+            FORBIDDEN,
+        )
+        .await;
+        app.expect(
+            Some(META),
+            send(Method::POST, "/api/item/unit", unit.clone()),
+            OK,
+        )
+        .await;
+        app.expect(
+            Some(META),
+            send(Method::POST, "/api/item/unit", unit),
+            CONFLICT,
+        )
+        .await;
+
+        // A unit can't have the name of the items own unit
+        let own = json!({ "name": "cola", "unit": "burk", "factor": 2.0 });
+        let body = app
+            .expect(
+                Some(META),
+                send(Method::POST, "/api/item/unit", own),
+                BAD_REQUEST,
+            )
+            .await;
+        assert_eq!(body["fields"][0]["field"], "unit");
+        let own = json!({ "name": "cola", "unit": "6-pack" });
+        let body = app
+            .expect(
+                Some(META),
+                send(Method::PATCH, "/api/item", own),
+                BAD_REQUEST,
+            )
+            .await;
+        assert_eq!(body["fields"][0]["field"], "unit");
+        let fanta = json!({
+            "storage": "meta",
+            "container": "",
+            "name": "fanta",
+            "amount": 1.0,
+            "unit": "burk",
+            "units": [{ "name": "burk", "factor": 2.0 }]
+        });
+        let body = app
+            .expect(
+                Some(META),
+                send(Method::POST, "/api/item", fanta),
+                BAD_REQUEST,
+            )
+            .await;
+        assert_eq!(body["fields"][0]["field"], "units[0].name");
+        app.expect(Some(META), get("/api/item?name=fanta"), NOT_FOUND)
+            .await;
+
+        let shipment = json!({
+            "arrival_time": Utc::now(),
+            "items": [{
+                "item": "cola",
+                "amount": 2.0,
+                "unit": "6-pack",
+                "storage": "meta",
+                "container": ""
+            }]
+        });
+        app.expect(
+            Some(META),
+            send(Method::POST, "/api/shipment", shipment),
+            OK,
+        )
+        .await;
+        let shipments = app.expect(Some(META), get("/api/shipment"), OK).await;
+        assert_eq!(shipments[0]["items"][0]["amount"], 12.0);
+        assert_eq!(shipments[0]["items"][0]["unit"], "burk");
+
+        app.expect(
+            Some(META),
+            delete("/api/item/unit?name=cola&unit=6-pack"),
+            OK,
+        )
+        .await;
+        let item = app.expect(Some(META), get("/api/item?name=cola"), OK).await;
+        assert_eq!(item["units"].as_array().unwrap().len(), 1);
+    }
+
+    #[sqlx::test]
+    async fn storage_routes(db: Pool<Postgres>) {
+        let app = harness::init(db).await;
+        setup(&app).await;
+
+        let storages = app.expect(Some(NOBODY), get("/api/storages"), OK).await;
+        assert_eq!(names(&storages), ["meta"]);
+        let storages = app.expect(Some(SPRITIS), get("/api/storages"), OK).await;
+        assert_eq!(names(&storages).len(), 2);
+
+        let tree = app
+            .expect(Some(NOBODY), get("/api/storages/containers"), OK)
+            .await;
+        assert!(tree.is_array() || tree.is_object(), "{tree}");
+
+        let spritis = "/api/storages/containers/items?name=spritis";
+        app.expect(Some(NOBODY), get(spritis), FORBIDDEN).await;
+        let tree = app.expect(Some(SPRITIS), get(spritis), OK).await;
+        assert!(tree.to_string().contains("gin"), "{tree}");
+
+        let storage = json!({ "name": "meta", "protected": false, "inventory_interval": null });
+        app.expect(
+            Some(META),
+            send(Method::PATCH, "/api/storage", storage.clone()),
+            FORBIDDEN,
+        )
+        .await;
+        app.expect(
+            Some(ADMIN),
+            send(Method::PATCH, "/api/storage", storage),
+            OK,
+        )
+        .await;
+
+        let container = json!({ "name": "hylla", "storage": "meta" });
+        app.expect(
+            Some(META),
+            send(Method::POST, "/api/container", container),
+            OK,
+        )
+        .await;
+
+        let rename = json!({ "name": "hylla", "new_name": "hyllan", "storage": "meta" });
+        app.expect(
+            Some(NOBODY),
+            send(Method::PATCH, "/api/container", rename.clone()),
+            FORBIDDEN,
+        )
+        .await;
+        app.expect(
+            Some(META),
+            send(Method::PATCH, "/api/container", rename),
+            OK,
+        )
+        .await;
+
+        let container_move = json!({
+            "name": "hyllan",
+            "from_storage": "meta",
+            "to_storage": "spritis",
+            "merge": false
+        });
+        app.expect(
+            Some(META),
+            send(Method::PATCH, "/api/container/move", container_move.clone()),
+            FORBIDDEN,
+        )
+        .await;
+        app.expect(
+            Some(ADMIN),
+            send(Method::PATCH, "/api/container/move", container_move),
+            OK,
+        )
+        .await;
+
+        let remove = "/api/container?name=hyllan&storage=spritis";
+        app.expect(Some(META), delete(remove), FORBIDDEN).await;
+        app.expect(Some(ADMIN), delete(remove), OK).await;
+
+        let empty = json!({ "name": "tom", "protected": false });
+        app.expect(Some(ADMIN), send(Method::POST, "/api/storage", empty), OK)
+            .await;
+        app.expect(Some(META), delete("/api/storage?name=tom"), FORBIDDEN)
+            .await;
+        app.expect(Some(ADMIN), delete("/api/storage?name=tom"), OK)
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn supplier_routes(db: Pool<Postgres>) {
+        let app = harness::init(db).await;
+        setup(&app).await;
+
+        let suppliers = app.expect(Some(META), get("/api/supplier"), OK).await;
+        assert_eq!(names(&suppliers), ["Systembolaget"]);
+        assert_eq!(suppliers[0]["group"], GROUP);
+        let suppliers = app.expect(Some(NOBODY), get("/api/supplier"), OK).await;
+        assert!(names(&suppliers).is_empty());
+
+        let update =
+            json!({ "name": "Systembolaget", "link": "https://systembolaget.se", "group": GROUP });
+        app.expect(
+            Some(SPRITIS),
+            send(Method::PATCH, "/api/supplier", update.clone()),
+            FORBIDDEN,
+        )
+        .await;
+        app.expect(Some(META), send(Method::PATCH, "/api/supplier", update), OK)
+            .await;
+
+        app.expect(
+            Some(SPRITIS),
+            delete("/api/supplier?name=Systembolaget"),
+            FORBIDDEN,
+        )
+        .await;
+        app.expect(Some(META), delete("/api/supplier?name=Systembolaget"), OK)
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn shipment_routes(db: Pool<Postgres>) {
+        let app = harness::init(db).await;
+        setup(&app).await;
+
+        let shipment = json!({
+            "arrival_time": Utc::now(),
+            "supplier": "Systembolaget",
+            "items": [{ "item": "tejp", "amount": 5.0, "storage": "meta", "container": "tejplåda" }]
+        });
+        for user in [NOBODY, SPRITIS] {
+            app.expect(
+                Some(user),
+                send(Method::POST, "/api/shipment", shipment.clone()),
+                FORBIDDEN,
+            )
+            .await;
+        }
+        app.expect(
+            Some(META),
+            send(Method::POST, "/api/shipment", shipment),
+            OK,
+        )
+        .await;
+
+        let shipments = app.expect(Some(META), get("/api/shipment"), OK).await;
+        assert_eq!(shipments.as_array().unwrap().len(), 1);
+        assert_eq!(shipments[0]["state"], "ordered");
+        assert_eq!(shipments[0]["supplier"], "Systembolaget");
+        let id = shipments[0]["id"].as_str().unwrap();
+
+        let state = json!({ "id": id, "state": "shipped" });
+        app.expect(
+            Some(NOBODY),
+            send(Method::PATCH, "/api/shipment/state", state.clone()),
+            FORBIDDEN,
+        )
+        .await;
+        app.expect(
+            Some(META),
+            send(Method::PATCH, "/api/shipment/state", state),
+            OK,
+        )
+        .await;
+
+        let receive = json!({ "id": id, "items": [{ "item": "tejp", "amount": 5.0 }] });
+        app.expect(
+            Some(NOBODY),
+            send(Method::POST, "/api/shipment/receive", receive.clone()),
+            FORBIDDEN,
+        )
+        .await;
+        let received = app
+            .expect(
+                Some(META),
+                send(Method::POST, "/api/shipment/receive", receive),
+                OK,
+            )
+            .await;
+        assert_eq!(received["state"], "received");
+        assert_eq!(received["items"][0]["received"], 5.0);
+
+        let orders = app
+            .expect(Some(SPRITIS), get("/api/shortage/order"), OK)
+            .await;
+        assert!(orders.to_string().contains("gin"), "{orders}");
+
+        // gin has no supplier and is only readable by those who may read spritis
+        let order = json!({ "supplier": null, "arrival_time": Utc::now() });
+        app.expect(
+            Some(META),
+            send(Method::POST, "/api/shortage/order", order.clone()),
+            NOT_FOUND,
+        )
+        .await;
+        app.expect(
+            Some(ADMIN),
+            send(Method::POST, "/api/shortage/order", order),
+            OK,
+        )
+        .await;
+
+        let shipments = app
+            .expect(Some(ADMIN), get("/api/shipment?state=ordered"), OK)
+            .await;
+        assert_eq!(shipments[0]["items"][0]["item"], "gin");
+        let remove = format!("/api/shipment?id={}", shipments[0]["id"].as_str().unwrap());
+        app.expect(Some(NOBODY), delete(&remove), FORBIDDEN).await;
+        app.expect(Some(ADMIN), delete(&remove), OK).await;
+    }
+
+    #[sqlx::test]
+    async fn inventory_routes(db: Pool<Postgres>) {
+        let app = harness::init(db).await;
+        setup(&app).await;
+
+        let shortage = app.expect(Some(NOBODY), get("/api/shortage"), OK).await;
+        assert!(shortage.as_array().unwrap().is_empty(), "{shortage}");
+        let shortage = app.expect(Some(SPRITIS), get("/api/shortage"), OK).await;
+        assert_eq!(names(&shortage), ["gin"]);
+
+        let forecast = app.expect(Some(META), get("/api/forecast"), OK).await;
+        assert!(forecast.is_array(), "{forecast}");
+        let due = app.expect(Some(META), get("/api/inventory"), OK).await;
+        assert!(due.is_array(), "{due}");
+
+        let stats = app.expect(Some(ADMIN), get("/api/stats"), OK).await;
+        assert_eq!(stats["items"], 2);
+        assert_eq!(stats["suppliers"], 1);
+        assert_eq!(stats["shortages"], 1);
+
+        let count = json!({
+            "items": [{ "name": "tejp", "storage": "meta", "container": "tejplåda", "amount": 4.0 }]
+        });
+        let refused = app
+            .expect(
+                Some(SPRITIS),
+                send(Method::POST, "/api/inventory", count.clone()),
+                FORBIDDEN,
+            )
+            .await;
+        assert_eq!(
+            refused,
+            json!({
+                "error": "forbidden",
+                "message": "Not allowed",
+                "locations": [{ "storage": "meta", "container": "tejplåda" }]
+            })
+        );
+        app.expect(Some(META), send(Method::POST, "/api/inventory", count), OK)
+            .await;
+
+        let open = json!({ "storage": "meta" });
+        app.expect(
+            Some(NOBODY),
+            send(Method::POST, "/api/stocktake", open.clone()),
+            FORBIDDEN,
+        )
+        .await;
+        let id = app
+            .expect(
+                Some(META),
+                send(Method::POST, "/api/stocktake", open.clone()),
+                OK,
+            )
+            .await;
+        let id = id.as_str().unwrap();
+
+        let stocktakes = app.expect(Some(META), get("/api/stocktake"), OK).await;
+        assert_eq!(stocktakes[0]["id"], id);
+        assert_eq!(stocktakes[0]["user"], META);
+
+        let counts = json!({ "id": id, "items": [{ "item": "tejp", "container": "tejplåda", "amount": 6.0 }] });
+        app.expect(
+            Some(NOBODY),
+            send(Method::POST, "/api/stocktake/count", counts.clone()),
+            FORBIDDEN,
+        )
+        .await;
+        app.expect(
+            Some(META),
+            send(Method::POST, "/api/stocktake/count", counts),
+            OK,
+        )
+        .await;
+
+        let report = format!("/api/stocktake/report?id={id}");
+        app.expect(Some(NOBODY), get(&report), FORBIDDEN).await;
+        let report = app.expect(Some(META), get(&report), OK).await;
+        assert_eq!(report["stocktake"]["id"], id);
+        assert!(report["items"].is_array(), "{report}");
+
+        let finalize = json!({ "id": id });
+        app.expect(
+            Some(NOBODY),
+            send(Method::POST, "/api/stocktake/finalize", finalize.clone()),
+            FORBIDDEN,
+        )
+        .await;
+        app.expect(
+            Some(META),
+            send(Method::POST, "/api/stocktake/finalize", finalize),
+            OK,
+        )
+        .await;
+
+        let id = app
+            .expect(Some(META), send(Method::POST, "/api/stocktake", open), OK)
+            .await;
+        let abandon = format!("/api/stocktake?id={}", id.as_str().unwrap());
+        app.expect(Some(NOBODY), delete(&abandon), FORBIDDEN).await;
+        app.expect(Some(META), delete(&abandon), OK).await;
+    }
+
+    /// tejp is stored loose in meta and spritis as well, 5 of it in both
+    async fn spread_tejp<S, B>(app: &TestApp<S>)
+    where
+        S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+        B: MessageBody,
+    {
+        for storage in ["meta", "spritis"] {
+            let tejp = json!({
+                "storage": storage,
+                "container": "",
+                "name": "tejp",
+                "amount": 5.0,
+                "unit": "st"
+            });
+            app.expect(Some(ADMIN), send(Method::POST, "/api/item", tejp), OK)
+                .await;
+        }
+    }
+
+    async fn tejp_in<S, B>(app: &TestApp<S>, storage: &str, container: &str) -> Value
+    where
+        S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+        B: MessageBody,
+    {
+        let item = app
+            .expect(Some(ADMIN), get("/api/item?name=tejp"), OK)
+            .await;
+        item["storage"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|stored| stored["storage"] == storage && stored["container"] == container)
+            .map(|stored| stored["amount"].clone())
+            .unwrap_or_else(|| panic!("tejp is not in {storage}/{container}: {item}"))
+    }
+
+    fn stock(updates: &[(&str, &str, f32)]) -> Value {
+        let items: Vec<Value> = updates
+            .iter()
+            .map(|(storage, container, amount)| {
+                json!({ "name": "tejp", "storage": storage, "container": container, "amount": amount })
+            })
+            .collect();
+        json!({ "items": items })
+    }
+
+    #[sqlx::test]
+    async fn stock_updates(db: Pool<Postgres>) {
+        let app = harness::init(db).await;
+        setup(&app).await;
+        spread_tejp(&app).await;
+
+        let both = stock(&[("meta", "", 3.0), ("meta", "tejplåda", 2.0)]);
+        app.expect(Some(META), send(Method::POST, "/api/inventory", both), OK)
+            .await;
+        assert_eq!(tejp_in(&app, "meta", "").await, 3.0);
+        assert_eq!(tejp_in(&app, "meta", "tejplåda").await, 2.0);
+
+        // One refused location rejects the whole batch, and is only listed once
+        let batch = stock(&[
+            ("meta", "", 1.0),
+            ("spritis", "", 0.0),
+            ("spritis", "", 1.0),
+        ]);
+        let refused = app
+            .expect(
+                Some(META),
+                send(Method::POST, "/api/inventory", batch),
+                FORBIDDEN,
+            )
+            .await;
+        assert_eq!(
+            refused,
+            json!({
+                "error": "forbidden",
+                "message": "Not allowed",
+                "locations": [{ "storage": "spritis", "container": "" }]
+            })
+        );
+        assert_eq!(tejp_in(&app, "meta", "").await, 3.0);
+        assert_eq!(tejp_in(&app, "spritis", "").await, 5.0);
+
+        // A container scope covers the container, not the rest of the storage
+        let outside = stock(&[("meta", "tejplåda", 1.0), ("meta", "", 1.0)]);
+        let refused = app
+            .expect(
+                Some(TEJPLADA),
+                send(Method::POST, "/api/inventory", outside),
+                FORBIDDEN,
+            )
+            .await;
+        assert_eq!(
+            refused["locations"],
+            json!([{ "storage": "meta", "container": "" }])
+        );
+        let inside = stock(&[("meta", "tejplåda", 1.0)]);
+        app.expect(
+            Some(TEJPLADA),
+            send(Method::POST, "/api/inventory", inside),
+            OK,
+        )
+        .await;
+        assert_eq!(tejp_in(&app, "meta", "tejplåda").await, 1.0);
+
+        let read = stock(&[("meta", "", 0.0)]);
+        app.expect(
+            Some(META_READER),
+            send(Method::POST, "/api/inventory", read),
+            FORBIDDEN,
+        )
+        .await;
+        assert_eq!(tejp_in(&app, "meta", "").await, 3.0);
+
+        let protected = stock(&[("spritis", "", 0.0)]);
+        app.expect(
+            Some(ADMIN),
+            send(Method::POST, "/api/inventory", protected),
+            OK,
+        )
+        .await;
+        assert_eq!(tejp_in(&app, "spritis", "").await, 0.0);
+    }
+
+    #[sqlx::test]
+    async fn auth_routes(db: Pool<Postgres>) {
+        let app = harness::init(db).await;
+
+        let info = app.expect(Some(META), get("/api/userinfo"), OK).await;
+        assert_eq!(info["username"], META);
+        assert_eq!(info["permissions"][0]["scope"], "meta");
+        assert_eq!(info["groups"], json!([GROUP]));
+        assert_eq!(info["token"], Value::Null);
+
+        let info = app.expect(Some(TOKEN), get("/api/userinfo"), OK).await;
+        assert_eq!(info["username"], "token:1");
+        assert_eq!(info["token"]["name"], "test token");
+
+        // Only known users and tokens get in
+        app.expect(
+            None,
+            get("/auth/oidc/callback?code=ghost&state=dev"),
+            UNAUTHORIZED,
+        )
+        .await;
+        let unknown = get("/api/userinfo").insert_header((header::AUTHORIZATION, "Bearer fel"));
+        app.expect(None, unknown, UNAUTHORIZED).await;
+
+        app.expect(Some(META), delete("/api/permissions/cache"), FORBIDDEN)
+            .await;
+        app.expect(
+            Some(ADMIN),
+            delete("/api/permissions/cache?subject=meta"),
+            OK,
+        )
+        .await;
+
+        app.expect(Some(META), delete("/api/sessions?user=meta"), FORBIDDEN)
+            .await;
+        let revoked = app
+            .expect(Some(ADMIN), delete("/api/sessions?user=meta"), OK)
+            .await;
+        assert_eq!(revoked, 1);
+        app.expect(Some(META), get("/api/userinfo"), UNAUTHORIZED)
+            .await;
+    }
+
+    /// Adds history in spritis to what setup creates: tejp stored there and used, gin on its way
+    /// and with a barcode, and a stocktake of the storage
+    async fn spritis_history<S, B>(app: &TestApp<S>, db: &Pool<Postgres>)
+    where
+        S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+        B: MessageBody,
+    {
+        spread_tejp(app).await;
+        sqlx::query!(
+            r#"
+                INSERT INTO log (item_id, container_id, amount, user_, time)
+                VALUES (
+                    item_ref('tejp'),
+                    container_ref('spritis', ''),
+                    20,
+                    'test',
+                    CURRENT_TIMESTAMP - INTERVAL '10 days'
+                )
+            "#
+        )
+        .execute(db)
+        .await
+        .unwrap();
+
+        let barcode = json!({ "name": "gin", "barcode": "7310865004703" });
+        app.expect(
+            Some(ADMIN),
+            send(Method::POST, "/api/item/barcode", barcode),
+            OK,
+        )
+        .await;
+        let shipment = json!({
+            "arrival_time": Utc::now(),
+            "items": [{ "item": "gin", "amount": 2.0, "storage": "spritis", "container": "" }]
+        });
+        app.expect(
+            Some(ADMIN),
+            send(Method::POST, "/api/shipment", shipment),
+            OK,
+        )
+        .await;
+        let stocktake = json!({ "storage": "spritis" });
+        app.expect(
+            Some(ADMIN),
+            send(Method::POST, "/api/stocktake", stocktake),
+            OK,
+        )
+        .await;
+    }
+
+    /// Returns if anything from spritis was visible in a response
+    type Visible = fn(&Value) -> bool;
+
+    fn mentions_spritis(body: &Value) -> bool {
+        body.to_string().contains("spritis")
+    }
+
+    fn mentions_gin(body: &Value) -> bool {
+        body.to_string().contains("gin")
+    }
+
+    #[sqlx::test]
+    async fn read_permission_matrix(db: Pool<Postgres>) {
+        let app = harness::init(db.clone()).await;
+        setup(&app).await;
+        spritis_history(&app, &db).await;
+
+        let users = [
+            (NOBODY, false),
+            (META_READER, false),
+            (META, false),
+            (TEJPLADA, false),
+            (SPRITIS, true),
+            (SPRITIS_WRITER, true),
+            (ADMIN, true),
+            (TOKEN, true),
+        ];
+
+        let lists: [(&str, Visible); 11] = [
+            ("/api/items", mentions_spritis),
+            ("/api/items?name=gin", mentions_gin),
+            ("/api/item?name=tejp", mentions_spritis),
+            ("/api/log?name=tejp", mentions_spritis),
+            ("/api/storages", mentions_spritis),
+            ("/api/storages/containers", mentions_spritis),
+            ("/api/shipment", mentions_gin),
+            ("/api/shortage", mentions_spritis),
+            ("/api/shortage/order", mentions_gin),
+            ("/api/forecast", mentions_spritis),
+            ("/api/stocktake", mentions_spritis),
+        ];
+        // Answered with the status instead when spritis can't be read, hidden items are answered
+        // the same as items that don't exist
+        let guarded = [
+            ("/api/item?name=gin", NOT_FOUND),
+            ("/api/item/scan?barcode=7310865004703", NOT_FOUND),
+            ("/api/storages/containers/items?name=spritis", FORBIDDEN),
+        ];
+
+        for (user, allowed) in users {
+            for (uri, visible) in lists {
+                let body = app.expect(Some(user), get(uri), OK).await;
+                assert_eq!(visible(&body), allowed, "{user} reading {uri}: {body}");
+            }
+
+            for (uri, hidden) in guarded {
+                let status = if allowed { OK } else { hidden };
+                app.expect(Some(user), get(uri), status).await;
+            }
+
+            let stats = app.expect(Some(user), get("/api/stats"), OK).await;
+            let counted = if allowed { (2, 1) } else { (1, 0) };
+            assert_eq!(
+                (stats["items"].as_i64(), stats["shortages"].as_i64()),
+                (Some(counted.0), Some(counted.1)),
+                "{user} reading /api/stats: {stats}"
+            );
+        }
+    }
+
+    #[sqlx::test]
+    async fn error_bodies(db: Pool<Postgres>) {
+        let app = harness::init(db).await;
+        setup(&app).await;
+
+        let body = app
+            .expect(Some(NOBODY), get("/api/item?name=rom"), NOT_FOUND)
+            .await;
+        assert_eq!(
+            body,
+            json!({ "error": "not_found", "message": "Not found" })
+        );
+
+        let body = app
+            .expect(
+                Some(ADMIN),
+                send(Method::POST, "/api/item", json!([])),
+                BAD_REQUEST,
+            )
+            .await;
+        assert_eq!(body["error"], "bad_request");
+
+        let storage = json!({ "name": "meta", "protected": false });
+        let body = app
+            .expect(
+                Some(ADMIN),
+                send(Method::POST, "/api/storage", storage),
+                CONFLICT,
+            )
+            .await;
+        assert_eq!(body["error"], "conflict");
+        assert_eq!(body["message"], "the storage already exists");
+
+        // The container does not exist
+        let misplaced = json!({
+            "storage": "meta",
+            "container": "hylla",
+            "name": "rom",
+            "amount": 1.0
+        });
+        app.expect(
+            Some(META),
+            send(Method::POST, "/api/item", misplaced),
+            NOT_FOUND,
+        )
+        .await;
+
+        let body = app
+            .expect(Some(ADMIN), delete("/api/storage?name=meta"), CONFLICT)
+            .await;
+        assert_eq!(body["message"], "the container is still in use");
+
+        let negative = json!({
+            "storage": "meta",
+            "container": "tejplåda",
+            "name": "tejp",
+            "amount": -1.0
+        });
+        let body = app
+            .expect(
+                Some(META),
+                send(Method::PUT, "/api/item", negative),
+                BAD_REQUEST,
+            )
+            .await;
+        assert_eq!(body["error"], "validation");
+        assert_eq!(body["fields"][0]["field"], "amount");
+
+        let open = json!({ "storage": "meta" });
+        app.expect(
+            Some(META),
+            send(Method::POST, "/api/stocktake", open.clone()),
+            OK,
+        )
+        .await;
+        let body = app
+            .expect(
+                Some(META),
+                send(Method::POST, "/api/stocktake", open),
+                CONFLICT,
+            )
+            .await;
+        assert_eq!(body["message"], "the storage already has an open stocktake");
+    }
+
+    #[sqlx::test]
+    async fn invalid_fields_together(db: Pool<Postgres>) {
+        let app = harness::init(db).await;
+        setup(&app).await;
+
+        let item = json!({
+            "storage": " meta",
+            "container": "tejplåda",
+            "name": "  ",
+            "min": 4.0,
+            "amount": -2.0,
+            "inventory_interval": "PT1H"
+        });
+        let body = app
+            .expect(
+                Some(META),
+                send(Method::POST, "/api/item", item),
+                BAD_REQUEST,
+            )
+            .await;
+        assert_eq!(body["error"], "validation");
+        let fields: Vec<&str> = body["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field| field["field"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            fields,
+            ["storage", "name", "max", "amount", "inventory_interval"]
+        );
+
+        let shipment = json!({
+            "arrival_time": "2026-10-20T12:00:00Z",
+            "items": [
+                { "item": "tejp", "amount": 5.0, "storage": "meta", "container": "tejplåda" },
+                { "item": "gin", "amount": 0.0, "storage": "spritis" }
+            ]
+        });
+        let body = app
+            .expect(
+                Some(ADMIN),
+                send(Method::POST, "/api/shipment", shipment),
+                BAD_REQUEST,
+            )
+            .await;
+        assert_eq!(
+            body["fields"],
+            json!([
+                { "field": "items[1].amount", "message": "must be positive" },
+                { "field": "items[1].container", "message": "must be set together with storage" }
+            ])
+        );
+
+        // Nothing was created
+        let items = app.expect(Some(ADMIN), get("/api/items"), OK).await;
+        assert_eq!(names(&items), ["gin", "tejp"]);
+    }
+}
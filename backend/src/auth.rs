@@ -1,36 +1,37 @@
 use actix_web::{
     body::{EitherBody, MessageBody},
+    delete,
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
     get,
-    http::header::Header,
-    web, HttpMessage, HttpResponse,
+    http::header::{Header, HeaderValue, WWW_AUTHENTICATE},
+    web, HttpMessage, HttpRequest, HttpResponse, ResponseError,
 };
-use actix_web_httpauth::headers::authorization::{Authorization, Bearer};
-use jsonwebtoken::get_current_timestamp;
-use openidconnect::{
-    core::{
-        CoreGenderClaim, CoreJsonWebKey, CoreJweContentEncryptionAlgorithm, CoreJwsSigningAlgorithm,
-    },
-    AccessTokenHash, AuthorizationCode, CsrfToken, EmptyAdditionalClaims, IdToken, IdTokenClaims,
-    IdTokenVerifier, OAuth2TokenResponse, TokenResponse,
+use actix_web_httpauth::headers::{
+    authorization::{Authorization, Bearer},
+    www_authenticate::bearer::Error as BearerError,
 };
+use jsonwebtoken::get_current_timestamp;
 use serde::Deserialize;
-use sqlx::{Pool, Postgres};
+use sqlx::{types::Uuid, Pool, Postgres};
 use std::{
     future::{ready, Ready},
     rc::Rc,
 };
-use types::{
-    AuthMiddleware, AuthTokenResponse, InnerAuthMiddleware, LocalBoxFuture, OIDCData, Token,
-};
+use types::{AuthMiddleware, InnerAuthMiddleware, LocalBoxFuture, LoginState, Token};
+use utoipa::IntoParams;
 use utoipa_actix_web::{scope, service_config::ServiceConfig};
 
 use crate::{
-    auth::types::{Group, HivePermission, UserInfo},
+    auth::{
+        provider::{IdentityProvider, PermissionProvider},
+        types::{Group, HivePermission, HiveToken, UserInfo},
+    },
     db,
-    error::Error,
+    error::{Error, ErrorResponse, Refusal},
 };
 
+pub mod hive;
+pub mod provider;
 pub mod types;
 
 #[derive(Deserialize)]
@@ -39,9 +40,13 @@ struct CallbackQuery {
     state: String,
 }
 
+/// Where users that are not logged in are sent to start a login
+pub const LOGIN_PATH: &str = "/auth/oidc/login";
+
 pub(crate) fn config() -> impl FnOnce(&mut ServiceConfig) {
     |cfg: &mut ServiceConfig| {
-        cfg.service(scope("/oidc").service(callback));
+        cfg.service(scope("/oidc").service(login).service(callback))
+            .service(logout);
     }
 }
 
@@ -73,6 +78,9 @@ pub enum CheckType<'a> {
         mandates: &'a [Group],
         name: &'a str,
     },
+    Read {
+        storage: &'a str,
+    },
 }
 
 /// Checks if user is allowed to perform an action based on provided information
@@ -83,84 +91,128 @@ pub enum CheckType<'a> {
 ///     Allowed if the user has write access to any location that item is stored
 /// none:
 ///     Allowed if the user has write access to any location
+/// read:
+///     Allowed if the storage is unprotected or the user has read or write access to it
+///
+/// A refusal is Forbidden and says what was missing
 pub(crate) async fn check_auth(
     check_type: CheckType<'_>,
     db: &Pool<Postgres>,
     permissions: &[HivePermission],
 ) -> Result<(), Error> {
-    // TODO: check protected
     log::debug!("Checking permission {:?}", check_type);
 
     if permissions.iter().any(|perm| perm.id == "admin") {
         return Ok(());
     }
 
-    if match check_type {
-        CheckType::Any => permissions.iter().any(|perm| perm.id == "write"),
+    // If the user may write to any of the scopes
+    let writes = |scopes: &[&str]| {
+        permissions.iter().any(|perm| {
+            perm.id == "write"
+                && scopes
+                    .iter()
+                    .any(|scope| perm.scope == Some(scope.to_lowercase()))
+        })
+    };
+
+    let (allowed, refusal) = match check_type {
+        CheckType::Any => (
+            permissions.iter().any(|perm| perm.id == "write"),
+            Refusal::new("any", "write", &[]),
+        ),
         CheckType::Storage {
             storage,
             container: Some(container),
-        } => permissions.iter().any(|perm| {
-            perm.id == "write"
-                && (perm.scope == Some(storage.to_lowercase())
-                    || perm.scope == Some(container.to_lowercase()))
-        }),
+        } => (
+            writes(&[storage, container]),
+            Refusal::new("storage", "write", &[storage, container]),
+        ),
         CheckType::Storage {
             storage,
             container: None,
-        } => permissions
-            .iter()
-            .any(|perm| perm.id == "write" && perm.scope == Some(storage.to_lowercase())),
-        CheckType::Item(item) => db::item::get_location(db, &item).await?.iter().any(|item| {
-            permissions.iter().any(|perm| {
-                perm.id == "write"
-                    && (perm.scope == Some(item.storage.to_lowercase())
-                        || perm.scope == Some(item.container.to_lowercase()))
-            })
-        }),
+        } => (
+            writes(&[storage]),
+            Refusal::new("storage", "write", &[storage]),
+        ),
+        CheckType::Item(item) => {
+            let locations = db::item::get_location(db, item).await?;
+            let scopes = locations
+                .iter()
+                .flat_map(|location| [location.storage.as_str(), location.container.as_str()])
+                .collect::<Vec<_>>();
+
+            (writes(&scopes), Refusal::new("item", "write", &scopes))
+        }
         CheckType::MoveItem {
             from_storage,
             from_container,
             to_storage,
             to_container,
         } => {
-            permissions.iter().any(|perm| {
-                perm.id == "write"
-                    && (perm.scope == Some(from_storage.to_lowercase())
-                        || perm.scope == Some(from_container.to_lowercase()))
-            }) && permissions.iter().any(|perm| {
-                perm.id == "write"
-                    && (perm.scope == Some(to_storage.to_lowercase())
-                        || perm.scope == Some(to_container.to_lowercase()))
-            })
+            let from = writes(&[from_storage, from_container]);
+            let to = writes(&[to_storage, to_container]);
+
+            let missing = if from {
+                [to_storage, to_container]
+            } else {
+                [from_storage, from_container]
+            };
+
+            (from && to, Refusal::new("move_item", "write", &missing))
         }
         CheckType::MoveContainer {
             container,
             from_storage,
             to_storage,
         } => {
-            permissions
-                .iter()
-                .any(|perm| perm.id == "write" && perm.scope == Some(container.to_lowercase()))
-                || permissions.iter().any(|perm| {
-                    perm.id == "write" && (perm.scope == Some(from_storage.to_lowercase()))
-                }) && permissions.iter().any(|perm| {
-                    perm.id == "write" && (perm.scope == Some(to_storage.to_lowercase()))
-                })
+            let from = writes(&[from_storage]);
+            let to = writes(&[to_storage]);
+
+            // Write access to the container itself is always enough
+            let missing = match (from, to) {
+                (true, false) => vec![container, to_storage],
+                (false, true) => vec![container, from_storage],
+                _ => vec![container],
+            };
+
+            (
+                writes(&[container]) || from && to,
+                Refusal::new("move_container", "write", &missing),
+            )
         }
         CheckType::SupplierCreate { mandates, mandate } => {
-            permissions.iter().any(|perm| perm.id == "write")
-                && mandates.iter().any(|man| man.0 == mandate)
+            if permissions.iter().any(|perm| perm.id == "write") {
+                (
+                    mandates.iter().any(|man| man.0 == mandate),
+                    Refusal::new("supplier_create", "group", &[mandate]),
+                )
+            } else {
+                (false, Refusal::new("supplier_create", "write", &[]))
+            }
         }
         CheckType::Supplier { mandates, name } => {
             let supplier = db::supplier::get_by_name(db, name).await?;
-            mandates.iter().any(|mandate| mandate.0 == supplier.mandate)
+            (
+                mandates.iter().any(|mandate| mandate.0 == supplier.mandate),
+                Refusal::new("supplier", "group", &[&supplier.mandate]),
+            )
         }
-        CheckType::Admin => permissions.iter().any(|perm| perm.id == "admin"),
-    } {
+        CheckType::Admin => (false, Refusal::new("admin", "admin", &[])),
+        CheckType::Read { storage } => (
+            !db::storage::get_by_name(db, storage).await?.protected
+                || permissions.iter().any(|perm| {
+                    (perm.id == "read" || perm.id == "write")
+                        && perm.scope == Some(storage.to_lowercase())
+                }),
+            Refusal::new("read", "read", &[storage]),
+        ),
+    };
+
+    if allowed {
         Ok(())
     } else {
-        Err(Error::Unauthorized)
+        Err(Error::Forbidden(refusal))
     }
 }
 
@@ -189,6 +241,33 @@ pub async fn get_permitted_storages(
     }
 }
 
+/// Gets the protected storages the user is allowed to read, unprotected storages are readable
+/// by everyone so this is meant to be used together with `storage.protected`
+pub async fn get_readable_storages(
+    db: &Pool<Postgres>,
+    permissions: &[HivePermission],
+) -> Result<Vec<String>, Error> {
+    if check_auth(CheckType::Admin, db, permissions).await.is_ok() {
+        Ok(db::storage::get_all_unprotected(db).await.map(|storages| {
+            storages
+                .iter()
+                .map(|storage| storage.name.clone().to_lowercase())
+                .collect::<Vec<String>>()
+        })?)
+    } else {
+        Ok(permissions
+            .iter()
+            .filter_map(|perm| {
+                if perm.id == "read" || perm.id == "write" {
+                    perm.scope.clone()
+                } else {
+                    None
+                }
+            })
+            .collect())
+    }
+}
+
 #[utoipa::path(
     tag = "auth",
     responses(
@@ -200,6 +279,7 @@ async fn user_info(
     id: web::ReqData<String>,
     permissions: web::ReqData<Vec<HivePermission>>,
     groups: web::ReqData<Vec<Group>>,
+    token: Option<web::ReqData<HiveToken>>,
 ) -> HttpResponse {
     let info = UserInfo {
         username: id.to_string(),
@@ -207,10 +287,155 @@ async fn user_info(
         groups: groups.to_vec(),
         // TODO: use rfinger to get accutal picture
         image: String::new(),
+        token: token.map(|token| token.into_inner()),
     };
     HttpResponse::Ok().json(info)
 }
 
+#[derive(Deserialize, IntoParams)]
+struct SessionRevokeQuery {
+    /// The user whose sessions are ended
+    user: String,
+}
+
+/// Ends every session of a user, they have to log in again
+#[utoipa::path(
+    tag = "auth",
+    params(SessionRevokeQuery),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = u64,
+            description = "The number of sessions that were ended"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[delete("/sessions")]
+async fn revoke_sessions(
+    query: web::Query<SessionRevokeQuery>,
+    db: web::Data<Pool<Postgres>>,
+    id: web::ReqData<String>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    check_auth(CheckType::Admin, &db, &permissions).await?;
+
+    let revoked = db::session::revoke_all(&db, &query.user, &id).await?;
+
+    log::info!(
+        "{} revoked {revoked} sessions of {}",
+        id.as_str(),
+        query.user
+    );
+
+    Ok(HttpResponse::Ok().json(revoked))
+}
+
+#[derive(Deserialize, IntoParams)]
+struct CacheInvalidateQuery {
+    /// The user, or the id or fingerprint of the API token, to forget. Everything is forgotten if
+    /// left out
+    subject: Option<String>,
+}
+
+/// Forgets cached permissions and groups so the next lookup asks Hive
+#[utoipa::path(
+    tag = "auth",
+    params(CacheInvalidateQuery),
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "Success"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[delete("/permissions/cache")]
+async fn invalidate_cache(
+    query: web::Query<CacheInvalidateQuery>,
+    db: web::Data<Pool<Postgres>>,
+    provider: web::Data<dyn PermissionProvider>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    check_auth(CheckType::Admin, &db, &permissions).await?;
+
+    match &query.subject {
+        Some(subject) => provider.invalidate(subject),
+        None => provider.invalidate_all(),
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[utoipa::path(
+    tag = "auth",
+    responses(
+        (status = 307, description = "Redirect to the OIDC provider to log in")
+    )
+)]
+#[get("/login")]
+async fn login(
+    req: HttpRequest,
+    identity: web::Data<dyn IdentityProvider>,
+) -> Result<HttpResponse, Error> {
+    identity.login(&req).await
+}
+
+/// Ends the session and clears the cookie, the user may also be logged out at the provider
+#[utoipa::path(
+    tag = "auth",
+    responses(
+        (status = 307, description = "Redirect to the provider or the front page")
+    )
+)]
+#[get("/logout")]
+async fn logout(
+    req: HttpRequest,
+    db: web::Data<Pool<Postgres>>,
+    identity: web::Data<dyn IdentityProvider>,
+) -> Result<HttpResponse, Error> {
+    if let Some(token) = Token::extract_token(req.cookie("token")) {
+        if let Ok(sid) = Uuid::parse_str(&token.sid) {
+            db::session::revoke(&db, sid, &token.sub).await?;
+        }
+    }
+
+    let location = identity.logout_url()?;
+
+    Ok(HttpResponse::TemporaryRedirect()
+        .insert_header(("location", location))
+        .cookie(Token::removal_cookie())
+        .finish())
+}
+
 #[utoipa::path(
     tag = "auth",
     responses(
@@ -219,104 +444,39 @@ async fn user_info(
 )]
 #[get("/callback")]
 async fn callback(
+    req: HttpRequest,
     query: web::Query<CallbackQuery>,
-    oidc: web::Data<OIDCData>,
+    identity: web::Data<dyn IdentityProvider>,
+    provider: web::Data<dyn PermissionProvider>,
+    db: web::Data<Pool<Postgres>>,
 ) -> Result<HttpResponse, Error> {
     let CallbackQuery { code, state } = query.0;
-    let OIDCData {
-        client,
-        http_client,
-        nonce,
-        csrf_token,
-    } = oidc.get_ref();
-
-    check_csrf_token(csrf_token, state)?;
-
-    let token_respones = client
-        .exchange_code(AuthorizationCode::new(code))?
-        .request_async(http_client)
-        .await?;
-
-    let id_token = token_respones
-        .id_token()
-        .ok_or(Error::InternalServerError(String::from(
-            "oidc server returned no id",
-        )))?;
-
-    let id_token_verifier = client.id_token_verifier();
 
-    let claims = id_token.claims(&id_token_verifier, nonce)?;
+    let subject = identity.callback(&req, code, state).await?;
 
-    check_token_hash(claims, &token_respones, id_token, id_token_verifier)?;
-
-    let permissions = HivePermission::get(claims.subject().to_string()).await?;
+    let permissions = provider.permissions(&subject).await?;
 
     log::debug!("permissions: {permissions:?}");
 
-    let groups = Group::get(
-        &claims.subject().to_string(),
-        permissions.contains(&HivePermission {
-            id: String::from("admin"),
-            scope: Some(String::from("")),
-        }),
-    )
-    .await?;
+    let groups = provider
+        .groups(&subject, is_hive_admin(&permissions))
+        .await?;
 
     log::debug!("groups: {:?}", groups);
 
-    let token = Token::new(claims.subject().to_string(), permissions, groups).unwrap();
+    let sid = db::session::create(&db, &subject).await?;
+
+    let token = Token::new(sid, subject, permissions, groups).unwrap();
 
     let cookie = token.cookie()?;
 
     Ok(HttpResponse::TemporaryRedirect()
         .insert_header(("location", "/"))
         .cookie(cookie)
+        .cookie(LoginState::removal_cookie())
         .finish())
 }
 
-fn check_csrf_token(token: &CsrfToken, state: String) -> Result<(), Error> {
-    if *token.secret() != state {
-        log::error!("Invalid CSRF token on oidc callback");
-        return Err(Error::BadRequest);
-    }
-
-    Ok(())
-}
-
-fn check_token_hash(
-    claims: &IdTokenClaims<EmptyAdditionalClaims, CoreGenderClaim>,
-    token_respones: &AuthTokenResponse,
-    id_token: &IdToken<
-        EmptyAdditionalClaims,
-        CoreGenderClaim,
-        CoreJweContentEncryptionAlgorithm,
-        CoreJwsSigningAlgorithm,
-    >,
-    id_token_verifier: IdTokenVerifier<'_, CoreJsonWebKey>,
-) -> Result<(), Error> {
-    let expected_access_token_hash =
-        claims
-            .access_token_hash()
-            .ok_or(Error::InternalServerError(String::from(
-                "Missing access token hash",
-            )))?;
-
-    let actual_access_token_hash = AccessTokenHash::from_token(
-        token_respones.access_token(),
-        id_token.signing_alg()?,
-        id_token.signing_key(&id_token_verifier)?,
-    )?;
-
-    if actual_access_token_hash != *expected_access_token_hash {
-        return Err(Error::InternalServerError(format!(
-            "Hashes did not mach for subject {}",
-            **claims.subject()
-        )));
-    }
-
-    Ok(())
-}
-
 impl<S, B> Transform<S, ServiceRequest> for AuthMiddleware
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
@@ -333,6 +493,7 @@ where
         ready(Ok(InnerAuthMiddleware {
             service: Rc::new(service),
             auth_url: self.auth_url.clone(),
+            session: self.session,
         }))
     }
 }
@@ -351,27 +512,69 @@ where
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
         let srv = self.service.clone();
+        let session = self.session;
+
+        let token = Token::extract_token(req.cookie("token")).filter(|token| {
+            let expired = token.expired(&session);
+            if expired {
+                log::info!("session of {} has reached its lifetime", token.sub);
+            }
+            !expired
+        });
 
-        let mut token = if let Some(token) = Token::extract_token(req.cookie("token")) {
+        let mut token = if let Some(token) = token {
             token
         } else {
             if let Ok(auth) = Authorization::<Bearer>::parse(&req) {
                 return Box::pin(async move {
-                    let permissions =
-                        HivePermission::get_from_api_token(auth.into_scheme().token().to_string())
-                            .await;
+                    let Some(provider) =
+                        req.app_data::<web::Data<dyn PermissionProvider>>().cloned()
+                    else {
+                        let error =
+                            Error::InternalServerError(String::from("no permission provider"));
+                        return Ok(req.error_response(error).map_into_right_body());
+                    };
 
-                    if let Err(error) = &permissions {
-                        log::error!("{error}");
-                    }
+                    let secret = auth.as_ref().token();
+                    let permissions = provider.token_permissions(secret).await;
+
+                    let permissions = match permissions {
+                        Ok(permissions) => permissions,
+                        Err(Error::Unauthorized) => {
+                            return Ok(unauthorized(req, Some(BearerError::InvalidToken))
+                                .map_into_right_body());
+                        }
+                        Err(error) => {
+                            log::error!("{error}");
+                            return Ok(req.error_response(error).map_into_right_body());
+                        }
+                    };
 
-                    let permissions = permissions.unwrap_or(Vec::new());
+                    let identity = match provider.token(secret).await {
+                        Ok(identity) => identity,
+                        Err(error) => {
+                            log::error!("{error}");
+                            return Ok(req.error_response(error).map_into_right_body());
+                        }
+                    };
 
-                    let token = Token::new(String::from("token"), permissions, Vec::new()).unwrap();
+                    if let Some(db) = req.app_data::<web::Data<Pool<Postgres>>>() {
+                        if let Err(error) = db::api_token::used(
+                            db,
+                            &identity.id,
+                            identity.name.as_deref(),
+                            identity.owner.as_deref(),
+                        )
+                        .await
+                        {
+                            log::error!("failed to record api token use: {error}");
+                        }
+                    }
 
-                    req.extensions_mut().insert(String::from("api key"));
-                    req.extensions_mut().insert(token.permissions.clone());
-                    req.extensions_mut().insert(token.groups.clone());
+                    req.extensions_mut().insert(identity.user_id());
+                    req.extensions_mut().insert(identity);
+                    req.extensions_mut().insert(permissions);
+                    req.extensions_mut().insert(Vec::<Group>::new());
 
                     let response = srv.call(req);
 
@@ -381,25 +584,44 @@ where
                 });
             }
 
-            let response = HttpResponse::TemporaryRedirect()
-                .insert_header(("location", self.auth_url.clone()))
-                .finish()
-                .map_into_right_body();
-
-            let (request, _pl) = req.into_parts();
-            return Box::pin(async move { Ok(ServiceResponse::new(request, response)) });
+            let response = login_required(req, &self.auth_url).map_into_right_body();
+            return Box::pin(async move { Ok(response) });
         };
 
-        req.extensions_mut().insert(token.sub.clone());
-        req.extensions_mut().insert(token.permissions.clone());
-        req.extensions_mut().insert(token.groups.clone());
-
-        let res = srv.call(req);
+        let auth_url = self.auth_url.clone();
 
         Box::pin(async move {
-            let mut res = res.await.unwrap();
+            match is_active(&req, &token).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    log::info!("session of {} has been revoked", token.sub);
+                    return Ok(login_required(req, &auth_url).map_into_right_body());
+                }
+                Err(error) => {
+                    log::error!("{error}");
+                    return Ok(req.error_response(error).map_into_right_body());
+                }
+            }
 
-            token.exp = get_current_timestamp() + 7200;
+            if token.needs_revalidation(&session) {
+                match revalidate(&req, &mut token).await {
+                    Ok(()) => {}
+                    Err(Error::Unauthorized) => {
+                        log::info!("hive no longer knows {}, ending the session", token.sub);
+                        return Ok(login_required(req, &auth_url).map_into_right_body());
+                    }
+                    // Keep the permissions and try again on the next request
+                    Err(error) => log::warn!("could not revalidate {}: {error}", token.sub),
+                }
+            }
+
+            req.extensions_mut().insert(token.sub.clone());
+            req.extensions_mut().insert(token.permissions.clone());
+            req.extensions_mut().insert(token.groups.clone());
+
+            let mut res = srv.call(req).await.unwrap();
+
+            token.exp = get_current_timestamp() + Token::IDLE;
             let cookie = token.cookie().unwrap();
             res.response_mut().add_cookie(&cookie).unwrap();
 
@@ -407,3 +629,578 @@ where
         })
     }
 }
+
+/// If the session has not been logged out or revoked
+async fn is_active(req: &ServiceRequest, token: &Token) -> Result<bool, Error> {
+    let db = req
+        .app_data::<web::Data<Pool<Postgres>>>()
+        .ok_or(Error::InternalServerError(String::from("no database")))?;
+
+    let Ok(sid) = Uuid::parse_str(&token.sid) else {
+        return Ok(false);
+    };
+
+    Ok(db::session::is_active(db, sid).await?)
+}
+
+/// Fetches the permissions and groups of the session again
+async fn revalidate(req: &ServiceRequest, token: &mut Token) -> Result<(), Error> {
+    let provider =
+        req.app_data::<web::Data<dyn PermissionProvider>>()
+            .ok_or(Error::InternalServerError(String::from(
+                "no permission provider",
+            )))?;
+
+    let permissions = provider.permissions(&token.sub).await?;
+    let groups = provider
+        .groups(&token.sub, is_hive_admin(&permissions))
+        .await?;
+
+    token.permissions = permissions;
+    token.groups = groups;
+    token.checked = get_current_timestamp();
+
+    Ok(())
+}
+
+fn is_hive_admin(permissions: &[HivePermission]) -> bool {
+    permissions.contains(&HivePermission {
+        id: String::from("admin"),
+        scope: Some(String::from("")),
+    })
+}
+
+/// Sends browsers navigating to a page to log in and answers everything else with 401
+fn login_required(req: ServiceRequest, auth_url: &str) -> ServiceResponse {
+    if !is_navigation(&req) {
+        return unauthorized(req, None);
+    }
+
+    let response = HttpResponse::TemporaryRedirect()
+        .insert_header(("location", auth_url))
+        .finish();
+
+    req.into_response(response)
+}
+
+/// If the request is a browser navigating to a page, these can be sent to log in while scripts
+/// and fetch calls should get a status they can act on
+fn is_navigation(req: &ServiceRequest) -> bool {
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+    };
+
+    if !header("X-Requested-With").is_empty() {
+        return false;
+    }
+
+    header("Sec-Fetch-Mode") == "navigate" || header("Accept").contains("text/html")
+}
+
+/// Answers a request that could not be authenticated, the body tells the caller where to log in
+fn unauthorized(req: ServiceRequest, error: Option<BearerError>) -> ServiceResponse {
+    // Built by hand since the challenge in actix_web_httpauth leaves out the comma between
+    // parameters
+    let challenge = match error {
+        Some(error) => format!(r#"Bearer realm="zaiko", error="{error}""#),
+        None => String::from(r#"Bearer realm="zaiko""#),
+    };
+
+    let mut response = Error::Unauthorized.error_response();
+    response.headers_mut().insert(
+        WWW_AUTHENTICATE,
+        HeaderValue::from_str(&challenge).expect("the challenge is a valid header"),
+    );
+
+    req.into_response(response)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, time::Duration};
+
+    use actix_web::{
+        cookie::Cookie,
+        http::{header, StatusCode},
+        test::{self, TestRequest},
+        web::{self, Data},
+        App, HttpResponse, HttpServer,
+    };
+    use jsonwebtoken::get_current_timestamp;
+    use serde_json::Value;
+    use sqlx::{Pool, Postgres};
+
+    use crate::{
+        auth::{
+            check_auth,
+            hive::Hive,
+            provider::{DevProvider, IdentityProvider, PermissionProvider},
+            types::{AuthMiddleware, HivePermission, HiveToken, LoginState, SessionConfig, Token},
+            user_info, CheckType, LOGIN_PATH,
+        },
+        db,
+        error::{Error, Refusal},
+    };
+
+    fn permission(id: &str, scope: Option<&str>) -> HivePermission {
+        HivePermission {
+            id: String::from(id),
+            scope: scope.map(String::from),
+        }
+    }
+
+    /// meta is open to everyone while spritis is protected, tejp is stored in both
+    async fn setup(db: &Pool<Postgres>) {
+        db::storage::create(db, "meta", false, None).await.unwrap();
+        db::storage::create(db, "spritis", true, None)
+            .await
+            .unwrap();
+
+        for storage in ["meta", "spritis"] {
+            db::item::create(
+                db,
+                "test",
+                storage,
+                "",
+                "tejp",
+                None,
+                None,
+                5.0,
+                Some("st"),
+                None,
+            )
+            .await
+            .unwrap();
+        }
+    }
+
+    #[sqlx::test]
+    async fn refusals_say_what_is_missing(db: Pool<Postgres>) {
+        setup(&db).await;
+
+        let refusal = |check_type| {
+            let db = db.clone();
+            async move {
+                match check_auth(check_type, &db, &[permission("read", Some("spritis"))]).await {
+                    Err(Error::Forbidden(refusal)) => refusal,
+                    other => panic!("expected a refusal, got {other:?}"),
+                }
+            }
+        };
+
+        assert_eq!(
+            refusal(CheckType::Storage {
+                storage: "Meta",
+                container: Some("tejplåda"),
+            })
+            .await,
+            Refusal::new("storage", "write", &["meta", "tejplåda"])
+        );
+
+        let item = refusal(CheckType::Item("tejp")).await;
+        assert_eq!(item.check, "item");
+        assert_eq!(item.scope.len(), 2);
+
+        assert_eq!(
+            refusal(CheckType::SupplierCreate {
+                mandates: &[],
+                mandate: "metadorerna@datasektionen.se",
+            })
+            .await,
+            Refusal::new("supplier_create", "write", &[])
+        );
+        assert_eq!(
+            refusal(CheckType::Admin).await,
+            Refusal::new("admin", "admin", &[])
+        );
+        assert!(check_auth(
+            CheckType::Read { storage: "spritis" },
+            &db,
+            &[permission("read", Some("spritis"))]
+        )
+        .await
+        .is_ok());
+    }
+
+    #[test]
+    fn login_state() {
+        std::env::set_var("APP_SECRET", "1234567890abcdefabcdef");
+
+        let login = LoginState {
+            state: String::from("state"),
+            nonce: String::from("nonce"),
+            pkce_verifier: String::from("verifier"),
+            exp: get_current_timestamp() + LoginState::LIFETIME,
+        };
+        let cookie = login.cookie().unwrap();
+
+        assert_eq!(
+            LoginState::verify(Some(cookie.clone()), "state").unwrap(),
+            login
+        );
+        assert!(matches!(
+            LoginState::verify(Some(cookie.clone()), "other state"),
+            Err(Error::BadRequest)
+        ));
+        assert!(matches!(
+            LoginState::verify(None, "state"),
+            Err(Error::BadRequest)
+        ));
+
+        let tampered = Cookie::new(LoginState::COOKIE, format!("{}x", cookie.value()));
+        assert!(matches!(
+            LoginState::verify(Some(tampered), "state"),
+            Err(Error::BadRequest)
+        ));
+
+        let expired = LoginState {
+            exp: get_current_timestamp() - 2 * LoginState::LIFETIME,
+            ..login.clone()
+        };
+        assert!(matches!(
+            LoginState::verify(Some(expired.cookie().unwrap()), "state"),
+            Err(Error::BadRequest)
+        ));
+    }
+
+    fn hive(url: &str) -> Data<dyn PermissionProvider> {
+        Data::from(Arc::new(Hive::new(
+            String::from(url),
+            String::from("1234567890abcdefabcdef"),
+            Duration::from_secs(60),
+            Duration::ZERO,
+        )) as Arc<dyn PermissionProvider>)
+    }
+
+    async fn call_api(
+        hive: &Data<dyn PermissionProvider>,
+        req: TestRequest,
+    ) -> (StatusCode, Option<String>, Option<String>, Value) {
+        call_api_with(
+            hive,
+            None,
+            SessionConfig::default(),
+            req.uri("/api/userinfo"),
+        )
+        .await
+    }
+
+    async fn call_api_with(
+        hive: &Data<dyn PermissionProvider>,
+        db: Option<&Pool<Postgres>>,
+        session: SessionConfig,
+        req: TestRequest,
+    ) -> (StatusCode, Option<String>, Option<String>, Value) {
+        let identity: Arc<dyn IdentityProvider> = Arc::new(DevProvider::default());
+        let mut app = App::new()
+            .app_data(hive.clone())
+            .app_data(Data::from(identity));
+        if let Some(db) = db {
+            app = app.app_data(Data::new(db.clone()));
+        }
+
+        let app = test::init_service(
+            app.service(super::logout).service(
+                web::scope("/api")
+                    .wrap(AuthMiddleware::new(String::from(LOGIN_PATH), session))
+                    .service(user_info)
+                    .service(super::revoke_sessions),
+            ),
+        )
+        .await;
+
+        let response = test::call_service(&app, req.to_request()).await;
+
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .map(|value| value.to_str().unwrap().to_string())
+        };
+        let location = header(header::LOCATION);
+        let challenge = header(header::WWW_AUTHENTICATE);
+
+        let status = response.status();
+        let body = test::read_body(response).await;
+
+        (
+            status,
+            location,
+            challenge,
+            serde_json::from_slice(&body).unwrap_or(Value::Null),
+        )
+    }
+
+    #[actix_web::test]
+    async fn unauthenticated_navigation_redirects() {
+        let (status, location, _, _) = call_api(
+            &hive(""),
+            TestRequest::get()
+                .insert_header((header::ACCEPT, "text/html,application/xhtml+xml,*/*;q=0.8"))
+                .insert_header(("Sec-Fetch-Mode", "navigate")),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(location.as_deref(), Some(LOGIN_PATH));
+    }
+
+    #[actix_web::test]
+    async fn unauthenticated_api_call() {
+        for request in [
+            TestRequest::get(),
+            TestRequest::get().insert_header((header::ACCEPT, "application/json")),
+            TestRequest::get()
+                .insert_header((header::ACCEPT, "text/html"))
+                .insert_header(("X-Requested-With", "XMLHttpRequest")),
+        ] {
+            let (status, location, challenge, body) = call_api(&hive(""), request).await;
+
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert_eq!(location, None);
+            assert_eq!(challenge.as_deref(), Some(r#"Bearer realm="zaiko""#));
+            assert_eq!(body["error"], "unauthorized");
+            assert_eq!(body["message"], "Not logged in");
+            assert_eq!(body["login"], LOGIN_PATH);
+        }
+    }
+
+    #[actix_web::test]
+    async fn bearer_token() {
+        let fake_hive = HttpServer::new(|| {
+            App::new()
+                .route(
+                    "/token/{token}/permissions",
+                    web::get().to(|token: web::Path<String>| async move {
+                        if token.as_str() == "bad" {
+                            return HttpResponse::NotFound().finish();
+                        }
+                        HttpResponse::Ok().json(vec![HivePermission {
+                            id: String::from("read"),
+                            scope: None,
+                        }])
+                    }),
+                )
+                .route(
+                    "/token/good",
+                    web::get().to(|| async {
+                        HttpResponse::Ok().json(serde_json::json!({
+                            "id": "42",
+                            "description": "inköpslistan",
+                            "owner": "mister",
+                        }))
+                    }),
+                )
+                .default_service(web::to(|| async { HttpResponse::NotFound().finish() }))
+        })
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let address = fake_hive.addrs()[0];
+        actix_web::rt::spawn(fake_hive.run());
+
+        let hive = hive(&format!("http://{address}"));
+
+        let (status, _, _, body) = call_api(
+            &hive,
+            TestRequest::get().insert_header((header::AUTHORIZATION, "Bearer good")),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["username"], "token:42");
+        assert_eq!(body["token"]["name"], "inköpslistan");
+        assert_eq!(body["token"]["owner"], "mister");
+
+        // Hive doesn't know who the token is, it is still told apart from other tokens
+        let (status, _, _, body) = call_api(
+            &hive,
+            TestRequest::get().insert_header((header::AUTHORIZATION, "Bearer anonymous")),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        let fingerprint = HiveToken::fingerprint("anonymous");
+        assert_eq!(body["username"], fingerprint.user_id());
+        assert_eq!(body["token"]["owner"], Value::Null);
+        assert_ne!(fingerprint.id, "anonymous");
+
+        let (status, location, challenge, body) = call_api(
+            &hive,
+            TestRequest::get()
+                .insert_header((header::ACCEPT, "text/html"))
+                .insert_header((header::AUTHORIZATION, "Bearer bad")),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(location, None);
+        assert_eq!(
+            challenge.as_deref(),
+            Some(r#"Bearer realm="zaiko", error="invalid_token""#)
+        );
+        assert_eq!(body["error"], "unauthorized");
+    }
+
+    #[sqlx::test]
+    async fn session_revalidation(db: Pool<Postgres>) {
+        std::env::set_var("APP_SECRET", "1234567890abcdefabcdef");
+
+        let fake_hive = HttpServer::new(|| {
+            App::new()
+                .route(
+                    "/user/mister/permissions",
+                    web::get().to(|| async {
+                        HttpResponse::Ok().json(vec![HivePermission {
+                            id: String::from("read"),
+                            scope: None,
+                        }])
+                    }),
+                )
+                .route(
+                    "/tagged/supply-manager/memberships/mister",
+                    web::get().to(|| async {
+                        HttpResponse::Ok().json(serde_json::json!([{
+                            "group_name": "Metadorerna",
+                            "group_id": "metadorerna",
+                            "group_domain": "example.com",
+                            "tag_content": null,
+                        }]))
+                    }),
+                )
+                .default_service(web::to(|| async { HttpResponse::NotFound().finish() }))
+        })
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let address = fake_hive.addrs()[0];
+        // Run on its own thread since sqlx tests are not run on an actix runtime
+        let fake_hive = fake_hive.run();
+        std::thread::spawn(move || actix_web::rt::System::new().block_on(fake_hive));
+
+        let hive = hive(&format!("http://{address}"));
+        let session = SessionConfig {
+            revalidate: 300,
+            lifetime: 3600,
+        };
+
+        let call = |token: Token| {
+            let (hive, db) = (hive.clone(), db.clone());
+            async move {
+                let cookie = token.cookie().unwrap().into_owned();
+                let request = TestRequest::get().uri("/api/userinfo").cookie(cookie);
+                let (status, _, _, body) = call_api_with(&hive, Some(&db), session, request).await;
+                (status, body)
+            }
+        };
+
+        let session_of = |sub: &'static str, age: u64, checked: u64| {
+            let db = db.clone();
+            async move {
+                let sid = db::session::create(&db, sub).await.unwrap();
+                let mut token = Token::new(
+                    sid,
+                    String::from(sub),
+                    vec![permission("write", None)],
+                    Vec::new(),
+                )
+                .unwrap();
+                token.iat -= age;
+                token.checked -= checked;
+                token
+            }
+        };
+
+        // Recently checked, the permissions in the session are used
+        let (status, body) = call(session_of("mister", 1000, 10).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["permissions"][0]["id"], "write");
+
+        // Checked too long ago, Hive has taken away write
+        let (status, body) = call(session_of("mister", 1000, 1000).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["permissions"][0]["id"], "read");
+        assert_eq!(body["groups"][0], "metadorerna@example.com");
+
+        // Hive no longer knows the user
+        let (status, _) = call(session_of("ghost", 1000, 1000).await).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // Active, but logged in longer ago than the lifetime
+        let (status, _) = call(session_of("mister", 4000, 10).await).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[sqlx::test]
+    async fn session_revocation(db: Pool<Postgres>) {
+        std::env::set_var("APP_SECRET", "1234567890abcdefabcdef");
+
+        let hive = hive("");
+
+        let login = |sub: &'static str, permissions: Vec<HivePermission>| {
+            let db = db.clone();
+            async move {
+                let sid = db::session::create(&db, sub).await.unwrap();
+                let token = Token::new(sid, String::from(sub), permissions, Vec::new()).unwrap();
+                token.cookie().unwrap().into_owned()
+            }
+        };
+
+        let call = |request: TestRequest| {
+            let (hive, db) = (hive.clone(), db.clone());
+            async move {
+                let (status, location, _, body) =
+                    call_api_with(&hive, Some(&db), SessionConfig::default(), request).await;
+                (status, location, body)
+            }
+        };
+
+        let mister = login("mister", Vec::new()).await;
+        let (status, _, _) = call(
+            TestRequest::get()
+                .uri("/api/userinfo")
+                .cookie(mister.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, location, _) =
+            call(TestRequest::get().uri("/logout").cookie(mister.clone())).await;
+        assert_eq!(status, StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(location.as_deref(), Some("/"));
+
+        // The cookie is still valid, but the session has ended
+        let (status, _, _) = call(TestRequest::get().uri("/api/userinfo").cookie(mister)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let first = login("turetek", Vec::new()).await;
+        let second = login("turetek", Vec::new()).await;
+        let admin = login("mister", vec![permission("admin", Some(""))]).await;
+
+        let (status, _, _) = call(
+            TestRequest::delete()
+                .uri("/api/sessions?user=turetek")
+                .cookie(first.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, _, body) = call(
+            TestRequest::delete()
+                .uri("/api/sessions?user=turetek")
+                .cookie(admin.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, 2);
+
+        for cookie in [first, second] {
+            let (status, _, _) = call(TestRequest::get().uri("/api/userinfo").cookie(cookie)).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        }
+
+        let (status, _, _) = call(TestRequest::get().uri("/api/userinfo").cookie(admin)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
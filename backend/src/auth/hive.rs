@@ -0,0 +1,508 @@
+use std::{
+    collections::HashMap,
+    env,
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use openidconnect::reqwest::{self, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    auth::{
+        provider::{PermissionProvider, ProviderFuture},
+        types::{Group, HivePermission, HiveToken},
+    },
+    error::Error,
+};
+
+/// Seconds a lookup is used before Hive is asked again
+const DEFAULT_TTL: u64 = 60;
+/// Seconds an expired lookup may still be used while Hive can't be reached
+const DEFAULT_STALE: u64 = 900;
+
+#[derive(Deserialize)]
+struct HiveGroup {
+    group_id: String,
+    group_domain: String,
+}
+
+/// Lookups of one kind, keyed by subject or the fingerprint of a token
+struct Cache<T> {
+    entries: Mutex<HashMap<String, (Instant, T)>>,
+}
+
+impl<T: Clone> Cache<T> {
+    fn new() -> Self {
+        Cache {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the cached value if it is fresh, otherwise fetches it. If fetching fails the
+    /// old value is returned as long as it is not older than ttl + stale. A rejection
+    /// (Unauthorized) is never covered up by an old value.
+    async fn get(
+        &self,
+        key: &str,
+        ttl: Duration,
+        stale: Duration,
+        fetch: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        let cached = self.entries.lock().unwrap().get(key).cloned();
+
+        if let Some((fetched, value)) = &cached {
+            if fetched.elapsed() < ttl {
+                return Ok(value.clone());
+            }
+        }
+
+        match fetch.await {
+            Ok(value) => {
+                self.entries
+                    .lock()
+                    .unwrap()
+                    .insert(key.to_string(), (Instant::now(), value.clone()));
+                Ok(value)
+            }
+            Err(Error::Unauthorized) => {
+                self.remove(key);
+                Err(Error::Unauthorized)
+            }
+            Err(error) => match cached {
+                Some((fetched, value)) if fetched.elapsed() < ttl + stale => {
+                    log::warn!("hive unavailable, using a lookup from {fetched:?}: {error}");
+                    Ok(value)
+                }
+                _ => Err(error),
+            },
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+
+    /// The keys of the cached values that match
+    fn keys_where(&self, matches: impl Fn(&T) -> bool) -> Vec<String> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, (_, value))| matches(value))
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+/// Client for Hive that shares one connection pool and caches what Hive answers
+pub struct Hive {
+    client: reqwest::Client,
+    url: String,
+    secret: String,
+    ttl: Duration,
+    stale: Duration,
+    permissions: Cache<Vec<HivePermission>>,
+    /// Keyed by the fingerprint of the token, so secrets are never kept
+    token_permissions: Cache<Vec<HivePermission>>,
+    /// Keyed by the fingerprint of the token, so secrets are never kept
+    tokens: Cache<HiveToken>,
+    groups: Cache<Vec<Group>>,
+}
+
+impl Hive {
+    /// Key used for the groups of admins, as they get every group
+    const ALL_GROUPS: &'static str = "*";
+
+    pub fn new(url: String, secret: String, ttl: Duration, stale: Duration) -> Self {
+        Hive {
+            client: reqwest::Client::new(),
+            url,
+            secret,
+            ttl,
+            stale,
+            permissions: Cache::new(),
+            token_permissions: Cache::new(),
+            tokens: Cache::new(),
+            groups: Cache::new(),
+        }
+    }
+
+    /// Configured by HIVE_URL and HIVE_SECRET, the cache by HIVE_CACHE_TTL and
+    /// HIVE_CACHE_STALE in seconds
+    pub fn from_env() -> Self {
+        let seconds = |var, default| {
+            env::var(var)
+                .map(|value| value.parse().expect("to be a number of seconds"))
+                .unwrap_or(default)
+        };
+
+        Hive::new(
+            env::var("HIVE_URL").expect("HIVE_URL in .env"),
+            env::var("HIVE_SECRET").expect("HIVE_SECRET in .env"),
+            Duration::from_secs(seconds("HIVE_CACHE_TTL", DEFAULT_TTL)),
+            Duration::from_secs(seconds("HIVE_CACHE_STALE", DEFAULT_STALE)),
+        )
+    }
+
+    async fn fetch<T: DeserializeOwned>(&self, path: &str) -> Result<T, Error> {
+        let res = self
+            .client
+            .get(format!("{}{}", self.url, path))
+            .bearer_auth(&self.secret)
+            .send()
+            .await?;
+
+        // Only these say who is asking is not known, anything else (ex. being rate limited) is
+        // treated as Hive being unavailable so the cached lookups are used
+        if matches!(
+            res.status(),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::NOT_FOUND
+        ) {
+            log::info!("hive answered {} on {path}", res.status());
+            return Err(Error::Unauthorized);
+        }
+
+        let res = res.error_for_status()?.text().await?;
+
+        Ok(serde_json::from_str::<T>(&res)?)
+    }
+
+    pub async fn permissions(&self, subject: &str) -> Result<Vec<HivePermission>, Error> {
+        log::debug!("getting hive permissions for {subject}");
+        self.permissions
+            .get(
+                subject,
+                self.ttl,
+                self.stale,
+                self.fetch(&format!("/user/{subject}/permissions")),
+            )
+            .await
+    }
+
+    /// Fails with Unauthorized if Hive rejects the token
+    pub async fn token_permissions(&self, token: &str) -> Result<Vec<HivePermission>, Error> {
+        self.token_permissions
+            .get(
+                &HiveToken::fingerprint(token).id,
+                self.ttl,
+                self.stale,
+                self.fetch(&format!("/token/{token}/permissions")),
+            )
+            .await
+    }
+
+    /// Who the token is, if Hive can't tell it is identified by a fingerprint of the secret
+    pub async fn token(&self, token: &str) -> HiveToken {
+        self.tokens
+            .get(
+                &HiveToken::fingerprint(token).id,
+                self.ttl,
+                self.stale,
+                self.fetch(&format!("/token/{token}")),
+            )
+            .await
+            .unwrap_or_else(|error| {
+                log::warn!("could not get the identity of an api token: {error}");
+                HiveToken::fingerprint(token)
+            })
+    }
+
+    pub async fn groups(&self, subject: &str, admin: bool) -> Result<Vec<Group>, Error> {
+        log::debug!("getting groups for {subject}");
+
+        let (key, path) = if admin {
+            (
+                Self::ALL_GROUPS,
+                String::from("/tagged/supply-manager/groups"),
+            )
+        } else {
+            (
+                subject,
+                format!("/tagged/supply-manager/memberships/{subject}"),
+            )
+        };
+
+        let fetch = async {
+            let mandates: Vec<HiveGroup> = self.fetch(&path).await?;
+            Ok(mandates
+                .iter()
+                .map(|mandate| Group(format!("{}@{}", mandate.group_id, mandate.group_domain)))
+                .collect())
+        };
+
+        self.groups.get(key, self.ttl, self.stale, fetch).await
+    }
+
+    /// Forgets everything known about a user or token, the next lookup asks Hive. Tokens are
+    /// given by their id in Hive or the fingerprint of their secret.
+    pub fn invalidate(&self, subject: &str) {
+        self.permissions.remove(subject);
+        self.groups.remove(subject);
+        // Admins see every group, the list is shared between them
+        self.groups.remove(Self::ALL_GROUPS);
+
+        let mut fingerprints = self.tokens.keys_where(|token| token.id == subject);
+        fingerprints.push(subject.to_string());
+        for fingerprint in fingerprints {
+            self.token_permissions.remove(&fingerprint);
+            self.tokens.remove(&fingerprint);
+        }
+    }
+
+    pub fn invalidate_all(&self) {
+        self.permissions.clear();
+        self.groups.clear();
+        self.token_permissions.clear();
+        self.tokens.clear();
+    }
+}
+
+impl PermissionProvider for Hive {
+    fn permissions<'a>(&'a self, subject: &'a str) -> ProviderFuture<'a, Vec<HivePermission>> {
+        Box::pin(Hive::permissions(self, subject))
+    }
+
+    fn groups<'a>(&'a self, subject: &'a str, admin: bool) -> ProviderFuture<'a, Vec<Group>> {
+        Box::pin(Hive::groups(self, subject, admin))
+    }
+
+    fn token_permissions<'a>(&'a self, token: &'a str) -> ProviderFuture<'a, Vec<HivePermission>> {
+        Box::pin(Hive::token_permissions(self, token))
+    }
+
+    fn token<'a>(&'a self, token: &'a str) -> ProviderFuture<'a, HiveToken> {
+        Box::pin(async move { Ok(Hive::token(self, token).await) })
+    }
+
+    fn invalidate(&self, subject: &str) {
+        Hive::invalidate(self, subject)
+    }
+
+    fn invalidate_all(&self) {
+        Hive::invalidate_all(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{
+            atomic::{AtomicU16, AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use actix_web::{http::StatusCode, web, App, HttpRequest, HttpResponse, HttpServer};
+
+    use super::Hive;
+    use crate::{
+        auth::types::{HivePermission, HiveToken},
+        error::Error,
+    };
+
+    struct FakeHive {
+        url: String,
+        calls: Arc<AtomicUsize>,
+        /// What the fake answers with, 200 answers with a permission
+        status: Arc<AtomicU16>,
+    }
+
+    impl FakeHive {
+        fn answer(&self, status: StatusCode) {
+            self.status.store(status.as_u16(), Ordering::SeqCst);
+        }
+    }
+
+    fn fake_hive() -> FakeHive {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let status = Arc::new(AtomicU16::new(200));
+
+        let (server_calls, server_status) = (calls.clone(), status.clone());
+        let server = HttpServer::new(move || {
+            let (calls, status) = (server_calls.clone(), server_status.clone());
+            App::new().default_service(web::to(move |req: HttpRequest| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                let status = StatusCode::from_u16(status.load(Ordering::SeqCst)).unwrap();
+                async move {
+                    if status != StatusCode::OK {
+                        return HttpResponse::build(status).finish();
+                    }
+                    // Who a token is, /token/{token}/permissions are its permissions
+                    if req.path().starts_with("/token/") && req.path().matches('/').count() == 2 {
+                        return HttpResponse::Ok().json(HiveToken {
+                            id: String::from("42"),
+                            name: None,
+                            owner: None,
+                        });
+                    }
+                    if req.path().starts_with("/tagged/supply-manager/") {
+                        return HttpResponse::Ok().json(serde_json::json!([
+                            { "group_id": "dsek", "group_domain": "datasektionen.se" }
+                        ]));
+                    }
+                    HttpResponse::Ok().json(vec![HivePermission {
+                        id: String::from("read"),
+                        scope: None,
+                    }])
+                }
+            }))
+        })
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+
+        FakeHive { url, calls, status }
+    }
+
+    #[actix_web::test]
+    async fn cached_until_invalidated() {
+        let fake = fake_hive();
+        let hive = Hive::new(
+            fake.url.clone(),
+            String::new(),
+            Duration::from_secs(60),
+            Duration::ZERO,
+        );
+
+        hive.permissions("mister").await.unwrap();
+        hive.permissions("mister").await.unwrap();
+        assert_eq!(fake.calls.load(Ordering::SeqCst), 1);
+
+        hive.permissions("turetek").await.unwrap();
+        assert_eq!(fake.calls.load(Ordering::SeqCst), 2);
+
+        hive.invalidate("mister");
+        hive.permissions("mister").await.unwrap();
+        assert_eq!(fake.calls.load(Ordering::SeqCst), 3);
+
+        hive.invalidate_all();
+        hive.permissions("turetek").await.unwrap();
+        assert_eq!(fake.calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[actix_web::test]
+    async fn admin_groups_forgotten() {
+        let fake = fake_hive();
+        let hive = Hive::new(
+            fake.url.clone(),
+            String::new(),
+            Duration::from_secs(60),
+            Duration::ZERO,
+        );
+
+        hive.groups("mister", true).await.unwrap();
+        hive.groups("turetek", true).await.unwrap();
+        assert_eq!(fake.calls.load(Ordering::SeqCst), 1);
+
+        hive.invalidate("mister");
+        hive.groups("turetek", true).await.unwrap();
+        assert_eq!(fake.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[actix_web::test]
+    async fn tokens_forgotten_without_the_secret() {
+        let fake = fake_hive();
+        let hive = Hive::new(
+            fake.url.clone(),
+            String::new(),
+            Duration::from_secs(60),
+            Duration::ZERO,
+        );
+        let secret = "hemlig-nyckel";
+        let lookup = || async {
+            hive.token(secret).await;
+            hive.token_permissions(secret).await.unwrap();
+        };
+
+        lookup().await;
+        assert_eq!(hive.token(secret).await.id, "42");
+        assert_eq!(fake.calls.load(Ordering::SeqCst), 2);
+
+        // By the id Hive knows the token by
+        hive.invalidate("42");
+        lookup().await;
+        assert_eq!(fake.calls.load(Ordering::SeqCst), 4);
+
+        // By the fingerprint it is logged as
+        hive.invalidate(&HiveToken::fingerprint(secret).id);
+        lookup().await;
+        assert_eq!(fake.calls.load(Ordering::SeqCst), 6);
+
+        hive.invalidate(secret);
+        lookup().await;
+        assert_eq!(fake.calls.load(Ordering::SeqCst), 6);
+    }
+
+    #[actix_web::test]
+    async fn stale_while_hive_is_down() {
+        let fake = fake_hive();
+        let hive = Hive::new(
+            fake.url.clone(),
+            String::new(),
+            Duration::ZERO,
+            Duration::from_secs(60),
+        );
+
+        hive.permissions("mister").await.unwrap();
+        fake.answer(StatusCode::SERVICE_UNAVAILABLE);
+
+        // Expired, but Hive can't answer so the last known permissions are used
+        assert_eq!(hive.permissions("mister").await.unwrap().len(), 1);
+        assert_eq!(fake.calls.load(Ordering::SeqCst), 2);
+
+        // Nothing is known about this user
+        assert!(matches!(
+            hive.permissions("turetek").await,
+            Err(Error::InternalServerError(_))
+        ));
+
+        let hive = Hive::new(
+            fake.url.clone(),
+            String::new(),
+            Duration::ZERO,
+            Duration::ZERO,
+        );
+        fake.answer(StatusCode::OK);
+        hive.permissions("mister").await.unwrap();
+        fake.answer(StatusCode::SERVICE_UNAVAILABLE);
+
+        // Too old to be used
+        assert!(hive.permissions("mister").await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn stale_while_rate_limited() {
+        let fake = fake_hive();
+        let hive = Hive::new(
+            fake.url.clone(),
+            String::new(),
+            Duration::ZERO,
+            Duration::from_secs(60),
+        );
+
+        hive.permissions("mister").await.unwrap();
+        fake.answer(StatusCode::TOO_MANY_REQUESTS);
+
+        // Being rate limited says nothing about the user, the last known permissions are used
+        assert_eq!(hive.permissions("mister").await.unwrap().len(), 1);
+        assert!(matches!(
+            hive.permissions("turetek").await,
+            Err(Error::InternalServerError(_))
+        ));
+
+        // Hive not knowing the user is never covered up
+        fake.answer(StatusCode::NOT_FOUND);
+        assert!(matches!(
+            hive.permissions("mister").await,
+            Err(Error::Unauthorized)
+        ));
+    }
+}
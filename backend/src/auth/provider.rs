@@ -0,0 +1,346 @@
+use std::{collections::HashMap, env, fs, future::Future, pin::Pin, sync::Arc};
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use openidconnect::{
+    core::{
+        CoreGenderClaim, CoreJsonWebKey, CoreJweContentEncryptionAlgorithm, CoreJwsSigningAlgorithm,
+    },
+    url::{form_urlencoded, Url},
+    AccessTokenHash, AuthorizationCode, EmptyAdditionalClaims, IdToken, IdTokenClaims,
+    IdTokenVerifier, OAuth2TokenResponse, TokenResponse,
+};
+use serde::Deserialize;
+
+use crate::{
+    auth::{
+        hive::Hive,
+        types::{AuthTokenResponse, Group, HivePermission, HiveToken, LoginState, OIDCData},
+    },
+    error::Error,
+};
+
+/// Where the dev provider sends users to finish logging in
+const CALLBACK_PATH: &str = "/auth/oidc/callback";
+
+pub type ProviderFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, Error>> + 'a>>;
+
+/// Who users are and how they log in
+pub trait IdentityProvider: Send + Sync {
+    /// Starts a login, the response sends the user to where they log in
+    fn login<'a>(&'a self, req: &'a HttpRequest) -> ProviderFuture<'a, HttpResponse>;
+
+    /// Finishes a login when the user is sent back to the callback, returns who logged in
+    fn callback<'a>(
+        &'a self,
+        req: &'a HttpRequest,
+        code: String,
+        state: String,
+    ) -> ProviderFuture<'a, String>;
+
+    /// Where to send the user after their session has been ended
+    fn logout_url(&self) -> Result<String, Error> {
+        Ok(String::from("/"))
+    }
+}
+
+/// What users and API tokens are allowed to do
+pub trait PermissionProvider: Send + Sync {
+    fn permissions<'a>(&'a self, subject: &'a str) -> ProviderFuture<'a, Vec<HivePermission>>;
+
+    /// Admins get every group
+    fn groups<'a>(&'a self, subject: &'a str, admin: bool) -> ProviderFuture<'a, Vec<Group>>;
+
+    /// Fails with Unauthorized if the token is not known
+    fn token_permissions<'a>(&'a self, token: &'a str) -> ProviderFuture<'a, Vec<HivePermission>>;
+
+    /// Who the token is
+    fn token<'a>(&'a self, token: &'a str) -> ProviderFuture<'a, HiveToken>;
+
+    /// Forgets anything remembered about a user or token
+    fn invalidate(&self, _subject: &str) {}
+
+    fn invalidate_all(&self) {}
+}
+
+/// Picks the providers with AUTH_PROVIDER, either the default hive (OIDC and Hive) or dev. Dev
+/// lets anyone log in without a password so it is refused unless APP_ENV is development.
+pub fn from_env() -> (Arc<dyn IdentityProvider>, Arc<dyn PermissionProvider>) {
+    match env::var("AUTH_PROVIDER").as_deref() {
+        Ok("dev") => {
+            if env::var("APP_ENV").as_deref() != Ok("development") {
+                panic!("AUTH_PROVIDER dev is only allowed when APP_ENV is development");
+            }
+            log::warn!("using the dev auth provider, anyone can log in as any of its users");
+            let dev = Arc::new(DevProvider::from_env());
+            (dev.clone(), dev)
+        }
+        Ok("hive") | Err(_) => (Arc::new(OIDCData::from_env()), Arc::new(Hive::from_env())),
+        Ok(other) => panic!("unknown AUTH_PROVIDER {other}, expected hive or dev"),
+    }
+}
+
+impl IdentityProvider for OIDCData {
+    fn login<'a>(&'a self, _req: &'a HttpRequest) -> ProviderFuture<'a, HttpResponse> {
+        Box::pin(async move {
+            let (auth_url, login_state) = self.authorize().await?;
+
+            Ok(HttpResponse::TemporaryRedirect()
+                .insert_header(("location", auth_url))
+                .cookie(login_state.cookie()?)
+                .finish())
+        })
+    }
+
+    fn callback<'a>(
+        &'a self,
+        req: &'a HttpRequest,
+        code: String,
+        state: String,
+    ) -> ProviderFuture<'a, String> {
+        Box::pin(async move {
+            let client = self.client().await?;
+
+            let login_state = LoginState::verify(req.cookie(LoginState::COOKIE), &state)?;
+
+            let token_respones = client
+                .exchange_code(AuthorizationCode::new(code))?
+                .set_pkce_verifier(login_state.pkce_verifier())
+                .request_async(&self.http_client)
+                .await?;
+
+            let id_token =
+                token_respones
+                    .id_token()
+                    .ok_or(Error::InternalServerError(String::from(
+                        "oidc server returned no id",
+                    )))?;
+
+            let id_token_verifier = client.id_token_verifier();
+
+            let claims = id_token.claims(&id_token_verifier, &login_state.nonce())?;
+
+            check_token_hash(claims, &token_respones, id_token, id_token_verifier)?;
+
+            Ok(claims.subject().to_string())
+        })
+    }
+
+    /// If OIDC_LOGOUT_URL is set the user is also logged out at the provider, which sends them
+    /// back to POST_LOGOUT_REDIRECT_URL
+    fn logout_url(&self) -> Result<String, Error> {
+        let Ok(logout_url) = env::var("OIDC_LOGOUT_URL") else {
+            return Ok(String::from("/"));
+        };
+
+        let mut url = Url::parse(&logout_url).map_err(|error| {
+            Error::InternalServerError(format!("OIDC_LOGOUT_URL is not a url: {error}"))
+        })?;
+        url.query_pairs_mut()
+            .append_pair("client_id", &env::var("OIDC_ID")?);
+        if let Ok(redirect) = env::var("POST_LOGOUT_REDIRECT_URL") {
+            url.query_pairs_mut()
+                .append_pair("post_logout_redirect_uri", &redirect);
+        }
+
+        Ok(url.to_string())
+    }
+}
+
+fn check_token_hash(
+    claims: &IdTokenClaims<EmptyAdditionalClaims, CoreGenderClaim>,
+    token_respones: &AuthTokenResponse,
+    id_token: &IdToken<
+        EmptyAdditionalClaims,
+        CoreGenderClaim,
+        CoreJweContentEncryptionAlgorithm,
+        CoreJwsSigningAlgorithm,
+    >,
+    id_token_verifier: IdTokenVerifier<'_, CoreJsonWebKey>,
+) -> Result<(), Error> {
+    let expected_access_token_hash =
+        claims
+            .access_token_hash()
+            .ok_or(Error::InternalServerError(String::from(
+                "Missing access token hash",
+            )))?;
+
+    let actual_access_token_hash = AccessTokenHash::from_token(
+        token_respones.access_token(),
+        id_token.signing_alg()?,
+        id_token.signing_key(&id_token_verifier)?,
+    )?;
+
+    if actual_access_token_hash != *expected_access_token_hash {
+        return Err(Error::InternalServerError(format!(
+            "Hashes did not mach for subject {}",
+            **claims.subject()
+        )));
+    }
+
+    Ok(())
+}
+
+/// A user of the dev provider
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DevUser {
+    #[serde(default)]
+    pub permissions: Vec<HivePermission>,
+    #[serde(default)]
+    pub groups: Vec<Group>,
+}
+
+/// An API token of the dev provider
+#[derive(Debug, Clone, Deserialize)]
+pub struct DevToken {
+    #[serde(flatten)]
+    pub identity: HiveToken,
+    #[serde(default)]
+    pub permissions: Vec<HivePermission>,
+}
+
+/// Fixed users and tokens for local development and tests, logging in needs no password and
+/// nothing is fetched over the network
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DevProvider {
+    /// Users by subject
+    #[serde(default)]
+    pub users: HashMap<String, DevUser>,
+    /// API tokens by their secret
+    #[serde(default)]
+    pub tokens: HashMap<String, DevToken>,
+}
+
+#[derive(Deserialize)]
+struct DevLoginQuery {
+    user: Option<String>,
+}
+
+impl DevProvider {
+    /// Read from the json file DEV_AUTH_FILE or the json in DEV_AUTH, without either there is
+    /// a single admin called dev
+    pub fn from_env() -> Self {
+        let json = match (env::var("DEV_AUTH_FILE"), env::var("DEV_AUTH")) {
+            (Ok(path), _) => fs::read_to_string(path).expect("DEV_AUTH_FILE to be readable"),
+            (_, Ok(json)) => json,
+            _ => {
+                return DevProvider::default().user(
+                    "dev",
+                    DevUser {
+                        permissions: vec![HivePermission {
+                            id: String::from("admin"),
+                            scope: Some(String::new()),
+                        }],
+                        groups: Vec::new(),
+                    },
+                )
+            }
+        };
+
+        serde_json::from_str(&json).expect("dev auth to be valid json")
+    }
+
+    pub fn user(mut self, subject: &str, user: DevUser) -> Self {
+        self.users.insert(subject.to_string(), user);
+        self
+    }
+
+    fn get_user(&self, subject: &str) -> Result<&DevUser, Error> {
+        self.users.get(subject).ok_or(Error::Unauthorized)
+    }
+
+    fn get_token(&self, secret: &str) -> Result<&DevToken, Error> {
+        self.tokens.get(secret).ok_or(Error::Unauthorized)
+    }
+}
+
+impl IdentityProvider for DevProvider {
+    /// Logs in as the user in the query, or the first user
+    fn login<'a>(&'a self, req: &'a HttpRequest) -> ProviderFuture<'a, HttpResponse> {
+        Box::pin(async move {
+            let query = web::Query::<DevLoginQuery>::from_query(req.query_string())
+                .map_err(|_| Error::BadRequest)?;
+
+            let user = match &query.user {
+                Some(user) => user.clone(),
+                None => self
+                    .users
+                    .keys()
+                    .min()
+                    .cloned()
+                    .ok_or(Error::Unauthorized)?,
+            };
+
+            let query = form_urlencoded::Serializer::new(String::new())
+                .append_pair("code", &user)
+                .append_pair("state", "dev")
+                .finish();
+
+            Ok(HttpResponse::TemporaryRedirect()
+                .insert_header(("location", format!("{CALLBACK_PATH}?{query}")))
+                .finish())
+        })
+    }
+
+    fn callback<'a>(
+        &'a self,
+        _req: &'a HttpRequest,
+        code: String,
+        _state: String,
+    ) -> ProviderFuture<'a, String> {
+        Box::pin(async move {
+            self.get_user(&code)?;
+            Ok(code)
+        })
+    }
+}
+
+impl PermissionProvider for DevProvider {
+    fn permissions<'a>(&'a self, subject: &'a str) -> ProviderFuture<'a, Vec<HivePermission>> {
+        Box::pin(async move { Ok(self.get_user(subject)?.permissions.clone()) })
+    }
+
+    fn groups<'a>(&'a self, subject: &'a str, admin: bool) -> ProviderFuture<'a, Vec<Group>> {
+        Box::pin(async move {
+            if !admin {
+                return Ok(self.get_user(subject)?.groups.clone());
+            }
+
+            let mut groups = self
+                .users
+                .values()
+                .flat_map(|user| user.groups.clone())
+                .collect::<Vec<_>>();
+            groups.sort_by(|a, b| a.0.cmp(&b.0));
+            groups.dedup();
+
+            Ok(groups)
+        })
+    }
+
+    fn token_permissions<'a>(&'a self, token: &'a str) -> ProviderFuture<'a, Vec<HivePermission>> {
+        Box::pin(async move { Ok(self.get_token(token)?.permissions.clone()) })
+    }
+
+    fn token<'a>(&'a self, token: &'a str) -> ProviderFuture<'a, HiveToken> {
+        Box::pin(async move { Ok(self.get_token(token)?.identity.clone()) })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DevProvider, PermissionProvider};
+
+    #[actix_web::test]
+    async fn dev_example() {
+        let dev: DevProvider =
+            serde_json::from_str(include_str!("../../dev_auth.example.json")).unwrap();
+
+        let groups = dev.groups("admin", true).await.unwrap();
+        assert_eq!(groups.len(), 1);
+        assert!(dev.groups("admin", false).await.unwrap().is_empty());
+
+        let token = dev.token("dev-token").await.unwrap();
+        assert_eq!(token.user_id(), "token:1");
+        assert!(dev.permissions("ghost").await.is_err());
+    }
+}
@@ -1,7 +1,7 @@
-use std::{env, future::Future, pin::Pin, rc::Rc};
+use std::{env, future::Future, pin::Pin, rc::Rc, sync::Mutex};
 
 use actix_web::{
-    cookie::{Cookie, SameSite},
+    cookie::{time::Duration, Cookie, SameSite},
     FromRequest, HttpRequest,
 };
 use jsonwebtoken::{
@@ -15,9 +15,11 @@ use openidconnect::{
     },
     reqwest, Client, ClientId, ClientSecret, CsrfToken, EmptyAdditionalClaims,
     EmptyExtraTokenFields, EndpointMaybeSet, EndpointNotSet, EndpointSet, IdTokenFields, IssuerUrl,
-    Nonce, RedirectUrl, RevocationErrorResponseType, StandardErrorResponse,
-    StandardTokenIntrospectionResponse, StandardTokenResponse,
+    Nonce, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, RevocationErrorResponseType,
+    StandardErrorResponse, StandardTokenIntrospectionResponse, StandardTokenResponse,
 };
+use sha2::{Digest, Sha256};
+use sqlx::types::Uuid;
 use utoipa::ToSchema;
 
 use crate::error::Error;
@@ -56,173 +58,248 @@ pub type AuthTokenResponse = StandardTokenResponse<
 
 pub type LocalBoxFuture<T> = Pin<Box<dyn Future<Output = T> + 'static>>;
 
-#[derive(Clone)]
 pub(crate) struct OIDCData {
-    pub(crate) client: OIDCClient,
     pub(crate) http_client: reqwest::Client,
-    // pkce_verifier: PkceCodeVerifier,
-    pub(crate) nonce: Nonce,
-    pub(crate) csrf_token: CsrfToken,
+    issuer: IssuerUrl,
+    client_id: ClientId,
+    client_secret: ClientSecret,
+    redirect_url: RedirectUrl,
+    /// Discovered on first use, so the server starts while the provider can't be reached
+    client: Mutex<Option<OIDCClient>>,
 }
 
 impl OIDCData {
-    pub async fn get_oidc() -> (OIDCData, String) {
+    pub fn from_env() -> OIDCData {
         let http_client = reqwest::ClientBuilder::new()
             .redirect(reqwest::redirect::Policy::none())
             .build()
             .expect("Client should build");
 
-        let provider_metadata = CoreProviderMetadata::discover_async(
-            IssuerUrl::new(env::var("OIDC_PROVIDER").expect("OIDC_PROVIDER in .env"))
+        OIDCData {
+            http_client,
+            issuer: IssuerUrl::new(env::var("OIDC_PROVIDER").expect("OIDC_PROVIDER in .env"))
                 .expect("OIDC_PROVIDER to be correctly formated"),
-            &http_client,
-        )
-        .await
-        .expect("to get metadata from oidc provider");
+            client_id: ClientId::new(env::var("OIDC_ID").expect("OIDC_ID in .env")),
+            client_secret: ClientSecret::new(env::var("OIDC_SECRET").expect("OIDC_SECRET in .env")),
+            redirect_url: RedirectUrl::new(env::var("REDIRECT_URL").expect("REDIRECT_URL in .env"))
+                .expect("REDIRECT_URL to be correctly formated"),
+            client: Mutex::new(None),
+        }
+    }
+
+    pub async fn client(&self) -> Result<OIDCClient, Error> {
+        if let Some(client) = self.client.lock().unwrap().clone() {
+            return Ok(client);
+        }
+
+        let provider_metadata =
+            CoreProviderMetadata::discover_async(self.issuer.clone(), &self.http_client)
+                .await
+                .map_err(|error| {
+                    log::error!("oidc discovery: {error}");
+                    Error::InternalServerError(format!(
+                        "could not reach the oidc provider: {error}"
+                    ))
+                })?;
 
         let client = Client::from_provider_metadata(
             provider_metadata,
-            ClientId::new(env::var("OIDC_ID").expect("OIDC_ID in .env")),
-            Some(ClientSecret::new(
-                env::var("OIDC_SECRET").expect("OIDC_SECRET in .env"),
-            )),
+            self.client_id.clone(),
+            Some(self.client_secret.clone()),
         )
-        .set_redirect_uri(
-            RedirectUrl::new(env::var("REDIRECT_URL").expect("REDIRECT_URL in .env"))
-                .expect("REDIRECT_URL to be correctly formated"),
-        );
+        .set_redirect_uri(self.redirect_url.clone());
+
+        *self.client.lock().unwrap() = Some(client.clone());
 
-        // let (pkce_challange, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+        Ok(client)
+    }
 
-        let (auth_url, csrf_token, nonce) = client
+    /// Starts a new login with its own state, nonce and pkce verifier, returns the url to send
+    /// the user to and what the callback has to check
+    pub async fn authorize(&self) -> Result<(String, LoginState), Error> {
+        let (pkce_challange, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+
+        let (auth_url, csrf_token, nonce) = self
+            .client()
+            .await?
             .authorize_url(
                 CoreAuthenticationFlow::AuthorizationCode,
                 CsrfToken::new_random,
                 Nonce::new_random,
             )
             // .add_scope(Scope::new(String::from("pls_zaiko")))
-            // .set_pkce_challenge(pkce_challange)
+            .set_pkce_challenge(pkce_challange)
             .url();
 
-        let oidc = OIDCData {
-            client,
-            http_client,
-            nonce,
-            // pkce_verifier,
-            csrf_token,
+        let login = LoginState {
+            state: csrf_token.secret().clone(),
+            nonce: nonce.secret().clone(),
+            pkce_verifier: pkce_verifier.secret().clone(),
+            exp: get_current_timestamp() + LoginState::LIFETIME,
         };
 
-        (oidc, auth_url.to_string())
+        Ok((auth_url.to_string(), login))
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
-pub struct HivePermission {
-    pub id: String,
-    pub scope: Option<String>,
+/// What is needed to finish a login, kept in a signed cookie between the redirect to the
+/// provider and the callback
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoginState {
+    pub state: String,
+    pub nonce: String,
+    pub pkce_verifier: String,
+    pub exp: u64,
 }
 
-impl HivePermission {
-    pub async fn get(subject: String) -> Result<Vec<HivePermission>, Error> {
-        log::debug!("getting hive permissions for {subject}");
-        let client = reqwest::Client::new();
-        let res = client
-            .get(format!(
-                "{}/user/{}/permissions",
-                env::var("HIVE_URL")?,
-                subject.as_str()
-            ))
-            .bearer_auth(env::var("HIVE_SECRET")?)
-            .send()
-            .await?
-            .text()
-            .await?;
+impl LoginState {
+    pub const COOKIE: &'static str = "login";
+    /// Seconds the user has to finish logging in at the provider
+    pub const LIFETIME: u64 = 600;
 
-        Ok(serde_json::from_str::<Vec<HivePermission>>(&res)?)
-    }
+    pub fn cookie(&'_ self) -> Result<Cookie<'_>, Error> {
+        let secret = EncodingKey::from_secret(env::var("APP_SECRET")?.as_bytes());
 
-    pub async fn get_from_api_token(token: String) -> Result<Vec<HivePermission>, Error> {
-        log::debug!("getting hive permissions token {token}");
-        let client = reqwest::Client::new();
-        let res = client
-            .get(format!(
-                "{}/token/{}/permissions",
-                env::var("HIVE_URL")?,
-                token.as_str()
-            ))
-            .bearer_auth(env::var("HIVE_SECRET")?)
-            .send()
-            .await?
-            .text()
-            .await?;
+        let login = encode(&Header::default(), &self, &secret).unwrap();
 
-        Ok(serde_json::from_str::<Vec<HivePermission>>(&res)?)
+        Ok(Cookie::build(Self::COOKIE, login)
+            .same_site(SameSite::Lax)
+            .secure(true)
+            .http_only(true)
+            .path("/auth")
+            .max_age(Duration::seconds(Self::LIFETIME as i64))
+            .finish())
     }
-}
-
-#[derive(Clone, Serialize, Deserialize)]
-pub struct HiveGroup {
-    group_name: String,
-    group_id: String,
-    group_domain: String,
-    tag_content: Option<String>,
-}
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema, sqlx::Type)]
-#[sqlx(transparent)]
-pub struct Group(pub String);
+    /// A cookie that removes the login state once it has been used
+    pub fn removal_cookie() -> Cookie<'static> {
+        let mut cookie = Cookie::build(Self::COOKIE, "").path("/auth").finish();
+        cookie.make_removal();
+        cookie
+    }
 
-impl Group {
-    pub async fn get(subject: &str, admin: bool) -> Result<Vec<Group>, Error> {
-        log::debug!("getting groups for {subject}");
-        let client = reqwest::Client::new();
-
-        let url = if admin {
-            format!("{}/tagged/supply-manager/groups", env::var("HIVE_URL")?,)
-        } else {
-            format!(
-                "{}/tagged/supply-manager/memberships/{}",
-                env::var("HIVE_URL")?,
-                subject
-            )
+    /// Gets the login state from its cookie and checks that it belongs to the returned state
+    pub fn verify(cookie: Option<Cookie>, state: &str) -> Result<LoginState, Error> {
+        let Some(cookie) = cookie else {
+            log::error!("Missing login state on oidc callback");
+            return Err(Error::BadRequest);
         };
 
-        log::debug!("group url: {url}");
+        let secret = DecodingKey::from_secret(env::var("APP_SECRET")?.as_bytes());
 
-        let res = client
-            .get(url)
-            .bearer_auth(env::var("HIVE_SECRET")?)
-            .send()
-            .await?
-            .text()
-            .await?;
+        let login =
+            decode::<LoginState>(cookie.value(), &secret, &Validation::new(Algorithm::HS256))
+                .map_err(|error| {
+                    log::error!("Invalid login state on oidc callback: {error}");
+                    Error::BadRequest
+                })?
+                .claims;
+
+        if login.state != state {
+            log::error!("Invalid CSRF token on oidc callback");
+            return Err(Error::BadRequest);
+        }
+
+        Ok(login)
+    }
 
-        let mandates = serde_json::from_str::<Vec<HiveGroup>>(&res)?;
-        Ok(mandates
+    pub fn nonce(&self) -> Nonce {
+        Nonce::new(self.nonce.clone())
+    }
+
+    pub fn pkce_verifier(&self) -> PkceCodeVerifier {
+        PkceCodeVerifier::new(self.pkce_verifier.clone())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct HivePermission {
+    pub id: String,
+    pub scope: Option<String>,
+}
+
+/// The identity of an API token in Hive
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct HiveToken {
+    pub id: String,
+    #[serde(alias = "description")]
+    pub name: Option<String>,
+    pub owner: Option<String>,
+}
+
+impl HiveToken {
+    /// An id derived from the secret, used when Hive can't tell which token it is. The secret
+    /// itself is never stored
+    pub fn fingerprint(token: &str) -> HiveToken {
+        let id = Sha256::digest(token.as_bytes())
             .iter()
-            .map(|mandate| Group(format!("{}@{}", mandate.group_id, mandate.group_domain)))
-            .collect())
+            .take(8)
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+
+        HiveToken {
+            id,
+            name: None,
+            owner: None,
+        }
+    }
+
+    /// The user that changes made with the token are logged as
+    pub fn user_id(&self) -> String {
+        format!("token:{}", self.id)
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema, sqlx::Type)]
+#[sqlx(transparent)]
+pub struct Group(pub String);
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Token {
+    /// The session, checked against the session table on every request
+    pub sid: String,
     pub sub: String,
     pub exp: u64,
+    /// When the user logged in
+    pub iat: u64,
+    /// When the permissions and groups were last fetched from Hive
+    pub checked: u64,
     pub permissions: Vec<HivePermission>,
     pub groups: Vec<Group>,
 }
 
 impl Token {
-    pub fn new(sub: String, permissions: Vec<HivePermission>, groups: Vec<Group>) -> Option<Self> {
+    /// Seconds without requests before the session ends
+    pub const IDLE: u64 = 7200;
+
+    pub fn new(
+        sid: Uuid,
+        sub: String,
+        permissions: Vec<HivePermission>,
+        groups: Vec<Group>,
+    ) -> Option<Self> {
+        let now = get_current_timestamp();
         Some(Token {
+            sid: sid.to_string(),
             sub,
-            exp: get_current_timestamp() + 7200,
+            exp: now + Token::IDLE,
+            iat: now,
+            checked: now,
             permissions,
             groups,
         })
     }
 
+    /// If the session has outlived the absolute lifetime
+    pub fn expired(&self, session: &SessionConfig) -> bool {
+        get_current_timestamp() >= self.iat + session.lifetime
+    }
+
+    /// If the permissions are old enough to be fetched again
+    pub fn needs_revalidation(&self, session: &SessionConfig) -> bool {
+        get_current_timestamp() >= self.checked + session.revalidate
+    }
+
     pub fn cookie(&'_ self) -> Result<Cookie<'_>, Error> {
         let secret = EncodingKey::from_secret(env::var("APP_SECRET")?.as_bytes());
 
@@ -236,6 +313,12 @@ impl Token {
             .finish())
     }
 
+    pub fn removal_cookie() -> Cookie<'static> {
+        let mut cookie = Cookie::build("token", "").path("/").finish();
+        cookie.make_removal();
+        cookie
+    }
+
     pub fn extract_token(cookie: Option<Cookie>) -> Option<Token> {
         let token = cookie?;
 
@@ -260,18 +343,55 @@ impl FromRequest for Token {
     }
 }
 
+/// How long logged in sessions are trusted, in seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SessionConfig {
+    /// How often the permissions and groups of a session are fetched again from Hive
+    pub revalidate: u64,
+    /// How long after logging in a session ends, no matter how active the user is
+    pub lifetime: u64,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            revalidate: 300,
+            lifetime: 86400,
+        }
+    }
+}
+
+impl SessionConfig {
+    /// Configured by SESSION_REVALIDATE and SESSION_LIFETIME
+    pub fn from_env() -> Self {
+        let default = SessionConfig::default();
+        let seconds = |var, default| {
+            env::var(var)
+                .map(|value| value.parse().expect("to be a number of seconds"))
+                .unwrap_or(default)
+        };
+
+        SessionConfig {
+            revalidate: seconds("SESSION_REVALIDATE", default.revalidate),
+            lifetime: seconds("SESSION_LIFETIME", default.lifetime),
+        }
+    }
+}
+
 pub struct AuthMiddleware {
     pub auth_url: String,
+    pub session: SessionConfig,
 }
 
 impl AuthMiddleware {
-    pub fn new(auth_url: String) -> Self {
-        AuthMiddleware { auth_url }
+    pub fn new(auth_url: String, session: SessionConfig) -> Self {
+        AuthMiddleware { auth_url, session }
     }
 }
 
 pub struct InnerAuthMiddleware<S> {
     pub auth_url: String,
+    pub session: SessionConfig,
     pub service: Rc<S>,
 }
 
@@ -281,4 +401,6 @@ pub struct UserInfo {
     pub permissions: Vec<HivePermission>,
     pub groups: Vec<Group>,
     pub image: String,
+    /// The API token used for the request, if any
+    pub token: Option<HiveToken>,
 }
@@ -0,0 +1,258 @@
+use actix_web::{delete, get, patch, post, web, HttpResponse};
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+use utoipa::{IntoParams, ToSchema};
+use utoipa_actix_web::service_config::ServiceConfig;
+
+use crate::{
+    auth::{
+        check_auth,
+        types::{HivePermission},
+        CheckType,
+    },
+    db::{self, category::Category},
+    error::{Error, ErrorResponse},
+    validate::{self, Validate, Validator},
+};
+
+/// Info used to create a category
+#[derive(Debug, Deserialize, ToSchema)]
+struct CategoryCreateRequest {
+    /// The categorys name
+    name: String,
+    /// The category it is a subcategory of, none to put it at the top
+    parent: Option<String>,
+}
+
+impl Validate for CategoryCreateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.name("name", &self.name)
+            .optional_name("parent", self.parent.as_deref());
+    }
+}
+
+/// Info used to rename or move a category
+#[derive(Debug, Deserialize, ToSchema)]
+struct CategoryUpdateRequest {
+    /// The categorys name
+    name: String,
+    /// The new name of the category if it is changed
+    new_name: Option<String>,
+    /// The category it should be a subcategory of, null to put it at the top and left out to
+    /// keep it where it is
+    #[serde(default, deserialize_with = "validate::present")]
+    parent: Option<Option<String>>,
+}
+
+impl Validate for CategoryUpdateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.name("name", &self.name)
+            .optional_name("new_name", self.new_name.as_deref())
+            .optional_name("parent", self.parent.as_ref().and_then(Option::as_deref));
+    }
+}
+
+/// Info used to delete a category
+#[derive(Debug, Deserialize, IntoParams)]
+struct CategoryDeleteQuery {
+    /// The categorys name
+    name: String,
+}
+
+pub(crate) fn config() -> impl FnOnce(&mut ServiceConfig) {
+    |cfg: &mut ServiceConfig| {
+        cfg.service(get_categories)
+            .service(create_category)
+            .service(change_category)
+            .service(delete_category);
+    }
+}
+
+#[utoipa::path(
+    tag = "category",
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<Category>,
+            description = "Every category with its parent"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[get("/categories")]
+async fn get_categories(db: web::Data<Pool<Postgres>>) -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok().json(db::category::get_all(&db).await?))
+}
+
+#[utoipa::path(
+    tag = "category",
+    request_body = CategoryCreateRequest,
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "Success"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[post("/category")]
+async fn create_category(
+    body: String,
+    db: web::Data<Pool<Postgres>>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let category: CategoryCreateRequest = validate::parse(&body)?;
+
+    check_auth(CheckType::Admin, &db, &permissions).await?;
+
+    db::category::create(&db, &category.name, category.parent.as_deref()).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[utoipa::path(
+    tag = "category",
+    request_body = CategoryUpdateRequest,
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "Success"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[patch("/category")]
+async fn change_category(
+    body: String,
+    db: web::Data<Pool<Postgres>>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let category: CategoryUpdateRequest = validate::parse(&body)?;
+
+    check_auth(CheckType::Admin, &db, &permissions).await?;
+
+    db::category::change(
+        &db,
+        &category.name,
+        category.new_name.as_deref(),
+        category.parent.as_ref().map(Option::as_deref),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[utoipa::path(
+    tag = "category",
+    params(CategoryDeleteQuery),
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "Success"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[delete("/category")]
+async fn delete_category(
+    query: web::Query<CategoryDeleteQuery>,
+    db: web::Data<Pool<Postgres>>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    check_auth(CheckType::Admin, &db, &permissions).await?;
+    db::category::delete(&db, &query.name)
+        .await
+        .map_err(Error::removing)?;
+    Ok(HttpResponse::Ok().finish())
+}
@@ -4,13 +4,22 @@ use serde::Serialize;
 use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
 use utoipa::ToSchema;
 
+pub mod api_token;
+pub mod barcode;
+pub mod category;
 pub mod container;
+pub mod forecast;
+pub mod id;
 pub mod interval;
 pub mod item;
 pub mod log;
+pub mod lot;
+pub mod session;
 pub mod shipment;
+pub mod stocktake;
 pub mod storage;
 pub mod supplier;
+pub mod unit;
 
 #[derive(Debug, PartialEq, Serialize, sqlx::Type, ToSchema)]
 #[sqlx(rename_all = "lowercase")]
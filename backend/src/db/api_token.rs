@@ -0,0 +1,46 @@
+use sqlx::{postgres::PgQueryResult, Pool, Postgres};
+
+/// Records that the token was used, keeping its name and owner up to date
+pub async fn used(
+    db: &Pool<Postgres>,
+    id: &str,
+    name: Option<&str>,
+    owner: Option<&str>,
+) -> Result<PgQueryResult, sqlx::Error> {
+    sqlx::query!(
+        r#"
+            INSERT INTO api_token (id, name, owner)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (id) DO UPDATE SET
+                name = COALESCE(EXCLUDED.name, api_token.name),
+                owner = COALESCE(EXCLUDED.owner, api_token.owner),
+                last_used = CURRENT_TIMESTAMP
+        "#,
+        id,
+        name,
+        owner
+    )
+    .execute(db)
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::{Pool, Postgres};
+
+    #[sqlx::test]
+    async fn used(db: Pool<Postgres>) {
+        super::used(&db, "1234", Some("inköp"), Some("mister"))
+            .await
+            .unwrap();
+        super::used(&db, "1234", None, None).await.unwrap();
+
+        let token = sqlx::query!("SELECT name, owner FROM api_token WHERE id = '1234'")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+
+        assert_eq!(token.name.as_deref(), Some("inköp"));
+        assert_eq!(token.owner.as_deref(), Some("mister"));
+    }
+}
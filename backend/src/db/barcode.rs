@@ -0,0 +1,143 @@
+use sqlx::{postgres::PgQueryResult, Pool, Postgres, Transaction};
+
+use crate::db;
+
+/// Codes of only digits with one of these lengths are GTINs (EAN-8, UPC-A, EAN-13 and
+/// GTIN-14) and end with a check digit, anything else is a code of our own
+const GTIN_LENGTHS: [usize; 4] = [8, 12, 13, 14];
+
+fn is_gtin(code: &str) -> bool {
+    GTIN_LENGTHS.contains(&code.len()) && code.bytes().all(|digit| digit.is_ascii_digit())
+}
+
+/// If the check digit of a GTIN matches the rest of the code, other codes are always valid
+pub fn check_digit_valid(code: &str) -> bool {
+    if !is_gtin(code) {
+        return true;
+    }
+
+    let digits: Vec<u32> = code.bytes().map(|digit| u32::from(digit - b'0')).collect();
+    let (check, digits) = digits.split_last().unwrap();
+    // Weighted 3, 1, 3, ... from the digit next to the check digit
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, digit)| if i % 2 == 0 { digit * 3 } else { *digit })
+        .sum();
+
+    (10 - sum % 10) % 10 == *check
+}
+
+/// A UPC-A code is the EAN-13 with a leading zero, so a scan finds the item with either
+pub fn normalize(code: &str) -> String {
+    let code = code.trim();
+    if code.len() == 12 && is_gtin(code) {
+        format!("0{code}")
+    } else {
+        code.to_string()
+    }
+}
+
+/// The item a scanned code belongs to, RowNotFound if no item has it
+pub async fn get_item(db: &Pool<Postgres>, code: &str) -> Result<String, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT item.name FROM barcode JOIN item ON item.id = barcode.item_id WHERE code = $1",
+        normalize(code)
+    )
+    .fetch_one(db)
+    .await
+}
+
+/// The name of an item given either by name, id or by one of its barcodes
+pub async fn item_name(
+    db: &Pool<Postgres>,
+    name: Option<&str>,
+    code: Option<&str>,
+) -> Result<String, sqlx::Error> {
+    match (name, code) {
+        (Some(name), _) => {
+            let mut name = name.to_string();
+            db::id::resolve_item(db, &mut name).await?;
+            Ok(name)
+        }
+        (None, Some(code)) => get_item(db, code).await,
+        (None, None) => Err(sqlx::Error::RowNotFound),
+    }
+}
+
+pub async fn add(
+    db: &mut Transaction<'static, Postgres>,
+    item: &str,
+    code: &str,
+) -> Result<PgQueryResult, sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO barcode (code, item_id) VALUES ($1, item_ref($2))",
+        normalize(code),
+        item
+    )
+    .execute(&mut **db)
+    .await
+}
+
+pub async fn delete(db: &Pool<Postgres>, code: &str) -> Result<PgQueryResult, sqlx::Error> {
+    sqlx::query!("DELETE FROM barcode WHERE code = $1", normalize(code))
+        .execute(db)
+        .await
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::{Pool, Postgres};
+
+    use crate::db;
+
+    #[test]
+    fn check_digits() {
+        assert!(super::check_digit_valid("7310865004703"));
+        assert!(super::check_digit_valid("96385074"));
+        assert!(super::check_digit_valid("036000291452"));
+        assert!(!super::check_digit_valid("7310865004704"));
+        assert!(!super::check_digit_valid("036000291453"));
+        // Codes of our own have no check digit
+        assert!(super::check_digit_valid("META-0042"));
+        assert!(super::check_digit_valid("12345"));
+    }
+
+    #[sqlx::test]
+    async fn scan(db: Pool<Postgres>) {
+        db::storage::create(&db, "meta", false, None).await.unwrap();
+        db::item::create(
+            &db,
+            "test",
+            "meta",
+            "",
+            "tejp",
+            None,
+            None,
+            5.0,
+            Some("st"),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let mut tx = db.begin().await.unwrap();
+        super::add(&mut tx, "tejp", "036000291452").await.unwrap();
+        super::add(&mut tx, "tejp", "META-0042").await.unwrap();
+        tx.commit().await.unwrap();
+
+        // UPC-A and the EAN-13 it equals are the same code
+        assert_eq!(super::get_item(&db, "0036000291452").await.unwrap(), "tejp");
+        assert_eq!(super::get_item(&db, "036000291452").await.unwrap(), "tejp");
+        assert_eq!(super::get_item(&db, "META-0042").await.unwrap(), "tejp");
+        assert!(super::get_item(&db, "7310865004703").await.is_err());
+
+        let mut tx = db.begin().await.unwrap();
+        assert!(super::add(&mut tx, "tejp", "0036000291452").await.is_err());
+        tx.rollback().await.unwrap();
+
+        super::delete(&db, "META-0042").await.unwrap();
+        assert!(super::get_item(&db, "META-0042").await.is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use serde::Serialize;
+use sqlx::{postgres::PgQueryResult, Pool, Postgres};
+use utoipa::ToSchema;
+
+use crate::error::{Error, FieldError};
+
+/// A category items are sorted into
+#[derive(Debug, PartialEq, Serialize, ToSchema)]
+pub struct Category {
+    /// The categorys name
+    pub name: String,
+    /// The category this is a subcategory of, none at the top of the tree
+    pub parent: Option<String>,
+}
+
+pub async fn get_all(db: &Pool<Postgres>) -> Result<Vec<Category>, sqlx::Error> {
+    sqlx::query_as!(
+        Category,
+        r#"
+            SELECT name, parent
+            FROM category
+            ORDER BY name
+        "#
+    )
+    .fetch_all(db)
+    .await
+}
+
+pub async fn create(
+    db: &Pool<Postgres>,
+    name: &str,
+    parent: Option<&str>,
+) -> Result<PgQueryResult, sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO category (name, parent) VALUES ($1, $2)",
+        name,
+        parent
+    )
+    .execute(db)
+    .await
+}
+
+/// Renames and moves a category, it can't be moved into one of its own subcategories. It stays
+/// where it is if no parent is given, `Some(None)` moves it to the top.
+pub async fn change(
+    db: &Pool<Postgres>,
+    name: &str,
+    new_name: Option<&str>,
+    parent: Option<Option<&str>>,
+) -> Result<PgQueryResult, Error> {
+    if let Some(Some(parent)) = parent {
+        let inside =
+            sqlx::query_scalar!(r#"SELECT in_category($1, $2) AS "inside!""#, parent, name)
+                .fetch_one(db)
+                .await?;
+        if inside {
+            return Err(Error::Validation(vec![FieldError::new(
+                "parent",
+                "must not be the category or one of its subcategories",
+            )]));
+        }
+    }
+
+    let result = sqlx::query!(
+        r#"
+            UPDATE category
+            SET
+                name = $2,
+                parent = CASE WHEN $4 THEN $3 ELSE parent END
+            WHERE name = $1
+        "#,
+        name,
+        new_name.unwrap_or(name),
+        parent.flatten(),
+        parent.is_some()
+    )
+    .execute(db)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::NotFound);
+    }
+    Ok(result)
+}
+
+pub async fn delete(db: &Pool<Postgres>, name: &str) -> Result<PgQueryResult, sqlx::Error> {
+    sqlx::query!("DELETE FROM category WHERE name = $1", name)
+        .execute(db)
+        .await
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::{Pool, Postgres};
+
+    use crate::{db, error::Error};
+
+    #[sqlx::test]
+    async fn tree(db: Pool<Postgres>) {
+        super::create(&db, "dryck", None).await.unwrap();
+        super::create(&db, "läsk", Some("dryck")).await.unwrap();
+        super::create(&db, "cola", Some("läsk")).await.unwrap();
+
+        let inside = |c: &'static str, ancestor: &'static str| {
+            let db = db.clone();
+            async move {
+                sqlx::query_scalar!(r#"SELECT in_category($1, $2) AS "inside!""#, c, ancestor)
+                    .fetch_one(&db)
+                    .await
+                    .unwrap()
+            }
+        };
+        assert!(inside("cola", "dryck").await);
+        assert!(inside("läsk", "läsk").await);
+        assert!(!inside("dryck", "läsk").await);
+
+        // A category can't end up inside itself
+        assert!(matches!(
+            super::change(&db, "dryck", None, Some(Some("cola"))).await,
+            Err(Error::Validation(_))
+        ));
+
+        // Renaming keeps it where it is
+        super::change(&db, "läsk", Some("Läsk"), None)
+            .await
+            .unwrap();
+        assert!(inside("cola", "dryck").await);
+        assert!(inside("cola", "Läsk").await);
+
+        super::change(&db, "Läsk", None, Some(None)).await.unwrap();
+        assert!(!inside("cola", "dryck").await);
+        assert!(inside("cola", "Läsk").await);
+
+        // Categories in use can't be removed
+        db::storage::create(&db, "meta", false, None).await.unwrap();
+        db::item::create(
+            &db,
+            "test",
+            "meta",
+            "",
+            "coca-cola",
+            None,
+            None,
+            5.0,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        db::item::change(&db, "coca-cola", None, "st", None, Some(Some("cola")), None)
+            .await
+            .unwrap();
+        assert!(super::delete(&db, "cola").await.is_err());
+        assert!(super::delete(&db, "dryck").await.is_ok());
+    }
+}
@@ -20,6 +20,8 @@ pub struct Container {
 /// List of containers at a storage location
 #[derive(Debug, PartialEq, Serialize, ToSchema)]
 pub struct ContainerStorage {
+    /// The storages id
+    id: String,
     /// The storages name
     name: String,
     /// List of containers
@@ -29,6 +31,8 @@ pub struct ContainerStorage {
 /// List of containers with items
 #[derive(Debug, Serialize, PartialEq, ToSchema)]
 pub struct ContainerItem {
+    /// The containers id
+    id: String,
     /// The containers name
     name: String,
     /// List of items
@@ -42,11 +46,12 @@ pub async fn get_all_containers_grouped_by_storage(
     sqlx::query_as!(
         ContainerStorage,
         r#"
-            SELECT storage.name, ARRAY_AGG(container.name) AS "containers!"
+            SELECT storage.id::TEXT AS "id!", storage.name, ARRAY_AGG(container.name) AS "containers!"
             FROM storage
-            JOIN container ON storage.name = container.storage
+            JOIN container ON storage.id = container.storage_id
             WHERE protected <> true OR LOWER(storage.name) IN (SELECT UNNEST($1::TEXT[]))
-            GROUP BY storage.name
+            GROUP BY storage.name, storage.id
+            ORDER BY storage.name
         "#,
         protected
     )
@@ -55,6 +60,7 @@ pub async fn get_all_containers_grouped_by_storage(
 }
 
 struct Entry {
+    id: String,
     name: String,
 }
 
@@ -65,9 +71,10 @@ pub async fn get_all_containers_in_storage_with_items(
     let containers = sqlx::query_as!(
         Entry,
         r#"
-            SELECT name
-            FROM container
+            SELECT id::TEXT AS "id!", container AS "name!"
+            FROM location
             WHERE storage = $1
+            ORDER BY container
         "#,
         storage
     )
@@ -76,8 +83,13 @@ pub async fn get_all_containers_in_storage_with_items(
 
     let mut result = Vec::new();
 
-    for Entry { name: container } in containers {
+    for Entry {
+        id,
+        name: container,
+    } in containers
+    {
         result.push(ContainerItem {
+            id,
             name: container.clone(),
             items: db::item::get_all_in_storage_grouped_by_container_minimal(
                 db, storage, &container,
@@ -97,8 +109,8 @@ pub async fn create(
 ) -> Result<PgQueryResult, sqlx::Error> {
     sqlx::query!(
         r#"
-            INSERT INTO container (name, storage, inventory_interval)
-            VALUES ($1, $2, $3)
+            INSERT INTO container (name, storage_id, inventory_interval)
+            VALUES ($1, storage_ref($2), $3)
         "#,
         name,
         storage,
@@ -123,10 +135,8 @@ pub async fn change(
     sqlx::query!(
         r#"
             UPDATE container
-            SET
-                name = $2,
-                storage = $3
-            WHERE name = $1
+            SET name = $2
+            WHERE name = $1 AND storage_id = (SELECT id FROM storage WHERE name = $3)
         "#,
         name,
         new_name,
@@ -153,8 +163,8 @@ pub async fn move_container(
     let result = sqlx::query!(
         r#"
             UPDATE container
-            SET storage = $1
-            WHERE name = $2 AND storage = $3
+            SET storage_id = storage_ref($1)
+            WHERE name = $2 AND storage_id = (SELECT id FROM storage WHERE name = $3)
         "#,
         to_storage,
         name,
@@ -169,9 +179,11 @@ pub async fn move_container(
         let items = sqlx::query_as!(
             Item,
             r#"
-                SELECT item
+                SELECT item.name AS item
                 FROM stored_item
-                WHERE storage = $1 AND container = $2
+                JOIN item ON item.id = stored_item.item_id
+                JOIN location ON location.id = stored_item.container_id
+                WHERE location.storage = $1 AND location.container = $2
             "#,
             from_storage,
             name
@@ -196,7 +208,7 @@ pub async fn move_container(
         sqlx::query!(
             r#"
                 DELETE FROM container
-                WHERE storage = $1 AND name = $2
+                WHERE storage_id = (SELECT id FROM storage WHERE name = $1) AND name = $2
             "#,
             from_storage,
             name
@@ -221,7 +233,7 @@ pub async fn destroy(
     sqlx::query!(
         r#"
             DELETE FROM container
-            WHERE name = $1 AND storage = $2
+            WHERE name = $1 AND storage_id = (SELECT id FROM storage WHERE name = $2)
         "#,
         name,
         storage
@@ -255,6 +267,7 @@ mod test {
         assert_eq!(
             containers,
             vec![ContainerStorage {
+                id: db::id::storage_id(&db, "meta").await.unwrap(),
                 name: String::from("meta"),
                 containers: vec![String::new()]
             }]
@@ -276,6 +289,7 @@ mod test {
         assert_eq!(
             containers,
             vec![ContainerStorage {
+                id: db::id::storage_id(&db, "meta").await.unwrap(),
                 name: String::from("meta"),
                 containers: vec![String::new(), String::from("Märkeslåda")]
             }]
@@ -297,10 +311,12 @@ mod test {
             containers,
             vec![
                 ContainerStorage {
+                    id: db::id::storage_id(&db, "ESCapen").await.unwrap(),
                     name: String::from("ESCapen"),
                     containers: vec![String::new()]
                 },
                 ContainerStorage {
+                    id: db::id::storage_id(&db, "meta").await.unwrap(),
                     name: String::from("meta"),
                     containers: vec![String::new()]
                 },
@@ -322,6 +338,7 @@ mod test {
         assert_eq!(
             containers,
             vec![ContainerStorage {
+                id: db::id::storage_id(&db, "meta").await.unwrap(),
                 name: String::from("meta"),
                 containers: vec![String::new()]
             },]
@@ -336,7 +353,7 @@ mod test {
             .unwrap();
 
         let containers =
-            super::get_all_containers_grouped_by_storage(&db, &vec![String::from("spritis")])
+            super::get_all_containers_grouped_by_storage(&db, &[String::from("spritis")])
                 .await
                 .unwrap();
 
@@ -344,11 +361,13 @@ mod test {
             containers,
             vec![
                 ContainerStorage {
-                    name: String::from("spritis"),
+                    id: db::id::storage_id(&db, "meta").await.unwrap(),
+                    name: String::from("meta"),
                     containers: vec![String::new()]
                 },
                 ContainerStorage {
-                    name: String::from("meta"),
+                    id: db::id::storage_id(&db, "spritis").await.unwrap(),
+                    name: String::from("spritis"),
                     containers: vec![String::new()]
                 },
             ]
@@ -428,9 +447,11 @@ mod test {
             tree,
             vec![
                 ContainerItem {
+                    id: db::id::container_id(&db, "meta", "").await.unwrap(),
                     name: String::from(""),
                     items: vec![
                         MinimalItem {
+                            id: db::id::item_id(&db, "pantsäck").await.unwrap(),
                             name: String::from("pantsäck"),
                             amount: 3.0,
                             unit: String::from("rullar"),
@@ -438,6 +459,7 @@ mod test {
                             next_inventory: None
                         },
                         MinimalItem {
+                            id: db::id::item_id(&db, "ziptie").await.unwrap(),
                             name: String::from("ziptie"),
                             amount: 50.0,
                             unit: String::from("st"),
@@ -447,9 +469,11 @@ mod test {
                     ]
                 },
                 ContainerItem {
+                    id: db::id::container_id(&db, "meta", "tejplåda").await.unwrap(),
                     name: String::from("tejplåda"),
                     items: vec![
                         MinimalItem {
+                            id: db::id::item_id(&db, "eltejp").await.unwrap(),
                             name: String::from("eltejp"),
                             amount: 5.0,
                             unit: String::from("st"),
@@ -457,6 +481,7 @@ mod test {
                             next_inventory: None
                         },
                         MinimalItem {
+                            id: db::id::item_id(&db, "silvertejp").await.unwrap(),
                             name: String::from("silvertejp"),
                             amount: 5.0,
                             unit: String::from("st"),
@@ -480,9 +505,10 @@ mod test {
         let container = sqlx::query_as!(
             Container,
             r#"
-                SELECT name, storage, inventory_interval as "inventory_interval: Interval"
+                SELECT container.name, storage.name AS storage, container.inventory_interval as "inventory_interval: Interval"
                 FROM container
-                WHERE name = 'Märkeslåda'
+                JOIN storage ON storage.id = container.storage_id
+                WHERE container.name = 'Märkeslåda'
             "#,
         )
         .fetch_one(&db)
@@ -509,9 +535,10 @@ mod test {
         let containers = sqlx::query_as!(
             Container,
             r#"
-                SELECT name, storage, inventory_interval as "inventory_interval: Interval"
+                SELECT container.name, storage.name AS storage, container.inventory_interval as "inventory_interval: Interval"
                 FROM container
-                WHERE name <> ''
+                JOIN storage ON storage.id = container.storage_id
+                WHERE container.name <> ''
             "#,
         )
         .fetch_all(&db)
@@ -554,9 +581,10 @@ mod test {
         let container = sqlx::query_as!(
             Container,
             r#"
-                SELECT name, storage, inventory_interval as "inventory_interval: Interval"
+                SELECT container.name, storage.name AS storage, container.inventory_interval as "inventory_interval: Interval"
                 FROM container
-                WHERE name = 'Märkeslåda'
+                JOIN storage ON storage.id = container.storage_id
+                WHERE container.name = 'Märkeslåda'
             "#,
         )
         .fetch_one(&db)
@@ -575,7 +603,9 @@ mod test {
             None,
             None,
             None,
-            &vec![String::from("meta"), String::from("örådet")],
+            None,
+            None,
+            &[String::from("meta"), String::from("örådet")],
         )
         .await
         .unwrap();
@@ -583,9 +613,12 @@ mod test {
         assert_eq!(
             item,
             vec![BasicItem {
+                id: db::id::item_id(&db, "tejp").await.unwrap(),
                 name: String::from("tejp"),
                 amount: 5.0,
                 unit: String::from("st"),
+                category: None,
+                tags: vec![],
                 storage: vec![BasicItemStorage {
                     storage: String::from("örådet"),
                     container: String::from("Märkeslåda"),
@@ -689,7 +722,9 @@ mod test {
             None,
             None,
             None,
-            &vec![String::from("meta"), String::from("örådet")],
+            None,
+            None,
+            &[String::from("meta"), String::from("örådet")],
         )
         .await
         .unwrap();
@@ -697,9 +732,12 @@ mod test {
         assert_eq!(
             item,
             vec![BasicItem {
+                id: db::id::item_id(&db, "tejp").await.unwrap(),
                 name: String::from("tejp"),
                 amount: 10.0,
                 unit: String::from("st"),
+                category: None,
+                tags: vec![],
                 storage: vec![BasicItemStorage {
                     storage: String::from("örådet"),
                     container: String::from("Märkeslåda"),
@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    types::chrono::{DateTime, Utc},
+    Pool, Postgres,
+};
+use utoipa::ToSchema;
+
+/// What to sort the forecasts on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ForecastSort {
+    /// When the item is expected to run out
+    #[default]
+    Stockout,
+    /// When the item is expected to go below its order floor
+    BelowMin,
+}
+
+/// The expected future of an item at a storage location based on its consumption
+#[derive(Debug, PartialEq, Serialize, ToSchema)]
+pub struct Forecast {
+    /// The items name
+    pub name: String,
+    /// The storage where the item is located
+    pub storage: String,
+    /// The container where the item is stored
+    pub container: String,
+    /// The number of items currently in storage
+    pub amount: f32,
+    /// The unit that the amount is measured in
+    pub unit: String,
+    /// The order floor for this storage
+    pub min: Option<f32>,
+    /// The average number of items used per day
+    pub consumption: f32,
+    /// When the item is expected to run out
+    pub stockout: DateTime<Utc>,
+    /// When the amount is expected to go below the order floor
+    pub below_min: Option<DateTime<Utc>>,
+}
+
+/// Gets the forecasts of all items that are being consumed, soonest first
+///
+/// days:
+///     Only include items expected to run out (or go below min) within this many days
+pub async fn get_running_out(
+    db: &Pool<Postgres>,
+    sort: ForecastSort,
+    days: Option<i32>,
+    permitted_storages: &[String],
+) -> Result<Vec<Forecast>, sqlx::Error> {
+    let below_min = sort == ForecastSort::BelowMin;
+
+    sqlx::query_as!(
+        Forecast,
+        r#"
+            SELECT
+                item.name,
+                location.storage as "storage!",
+                location.container as "container!",
+                forecast.amount as "amount!",
+                item.unit,
+                forecast.min,
+                forecast.per_day as "consumption!",
+                forecast.stockout as "stockout!",
+                forecast.below_min
+            FROM forecast
+            JOIN item ON item.id = forecast.item_id
+            JOIN location ON location.id = forecast.container_id
+            WHERE
+                forecast.per_day > 0 AND
+                (
+                    location.protected <> true OR
+                    LOWER(location.storage) IN (SELECT UNNEST($3::TEXT[]))
+                ) AND
+                (
+                    $2::INT IS NULL OR
+                    CASE WHEN $1 THEN forecast.below_min ELSE forecast.stockout END
+                        <= CURRENT_TIMESTAMP + make_interval(days => $2)
+                )
+            ORDER BY
+                CASE WHEN $1 THEN forecast.below_min ELSE forecast.stockout END NULLS LAST,
+                item.name
+        "#,
+        below_min,
+        days,
+        permitted_storages
+    )
+    .fetch_all(db)
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::{types::chrono::Utc, Pool, Postgres};
+
+    use crate::db::{self, forecast::ForecastSort};
+
+    async fn setup(db: &Pool<Postgres>) {
+        db::storage::create(db, "meta", false, None).await.unwrap();
+        db::storage::create(db, "örådet", false, None)
+            .await
+            .unwrap();
+
+        db::item::create(
+            db,
+            "test",
+            "meta",
+            "",
+            "tejp",
+            Some(4.0),
+            Some(30.0),
+            10.0,
+            Some("st"),
+            None,
+        )
+        .await
+        .unwrap();
+
+        sqlx::query!(
+            r#"
+                INSERT INTO log (item_id, container_id, amount, user_, time)
+                VALUES
+                    (item_ref('tejp'), container_ref('meta', ''), 20, 'test', CURRENT_TIMESTAMP - INTERVAL '10 days'),
+                    (item_ref('tejp'), container_ref('meta', ''), 30, 'test', CURRENT_TIMESTAMP - INTERVAL '8 days')
+            "#
+        )
+        .execute(db)
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn running_out(db: Pool<Postgres>) {
+        setup(&db).await;
+
+        let forecasts = super::get_running_out(&db, ForecastSort::Stockout, None, &[])
+            .await
+            .unwrap();
+
+        assert_eq!(forecasts.len(), 1);
+
+        let forecast = &forecasts[0];
+        assert!((forecast.consumption - 2.0).abs() < 0.01);
+
+        let days_left = (forecast.stockout - Utc::now()).num_hours() as f32 / 24.0;
+        assert!((days_left - 5.0).abs() < 0.1);
+
+        let days_to_min = (forecast.below_min.unwrap() - Utc::now()).num_hours() as f32 / 24.0;
+        assert!((days_to_min - 3.0).abs() < 0.1);
+
+        assert!(
+            super::get_running_out(&db, ForecastSort::BelowMin, Some(2), &[])
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[sqlx::test]
+    async fn moves_are_not_consumption(db: Pool<Postgres>) {
+        setup(&db).await;
+
+        sqlx::query!(
+            r#"
+                INSERT INTO move_log (item_id, from_container_id, to_container_id, amount, user_, time)
+                VALUES (
+                    item_ref('tejp'),
+                    container_ref('meta', ''),
+                    container_ref('örådet', ''),
+                    10,
+                    'test',
+                    CURRENT_TIMESTAMP - INTERVAL '4 days'
+                )
+            "#
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+
+        let forecasts = super::get_running_out(&db, ForecastSort::Stockout, None, &[])
+            .await
+            .unwrap();
+
+        assert!((forecasts[0].consumption - 1.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,187 @@
+//! Items, storages, containers and suppliers have ids that stay the same when they are renamed.
+//! Wherever the API refers to one of them it takes either the name or the id, ids are swapped
+//! for the current name before a request is handled. Names can't look like ids, so anything
+//! that does is an id and has to exist.
+
+use sqlx::{types::Uuid, Pool, Postgres};
+
+/// Whether the text is an id rather than a name
+pub fn is_id(text: &str) -> bool {
+    Uuid::parse_str(text).is_ok()
+}
+
+/// Replaces the id of an item with its name, names are left as they are and
+/// unknown ids are not found
+pub async fn resolve_item(db: &Pool<Postgres>, item: &mut String) -> Result<(), sqlx::Error> {
+    let Ok(id) = Uuid::parse_str(item) else {
+        return Ok(());
+    };
+
+    *item = sqlx::query_scalar!("SELECT name FROM item WHERE id = $1", id)
+        .fetch_one(db)
+        .await?;
+    Ok(())
+}
+
+/// Replaces the id of a storage with its name, names are left as they are and
+/// unknown ids are not found
+pub async fn resolve_storage(db: &Pool<Postgres>, storage: &mut String) -> Result<(), sqlx::Error> {
+    let Ok(id) = Uuid::parse_str(storage) else {
+        return Ok(());
+    };
+
+    *storage = sqlx::query_scalar!("SELECT name FROM storage WHERE id = $1", id)
+        .fetch_one(db)
+        .await?;
+    Ok(())
+}
+
+/// Replaces the id of a container in the storage with its name, names are left as they are and
+/// unknown ids are not found
+pub async fn resolve_container(
+    db: &Pool<Postgres>,
+    storage: &str,
+    container: &mut String,
+) -> Result<(), sqlx::Error> {
+    let Ok(id) = Uuid::parse_str(container) else {
+        return Ok(());
+    };
+
+    *container = sqlx::query_scalar!(
+        r#"SELECT container AS "container!" FROM location WHERE id = $1 AND storage = $2"#,
+        id,
+        storage
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(())
+}
+
+/// Resolves a storage and then the container in it, as the container id is looked up in the storage
+pub async fn resolve_location(
+    db: &Pool<Postgres>,
+    storage: &mut String,
+    container: &mut String,
+) -> Result<(), sqlx::Error> {
+    resolve_storage(db, storage).await?;
+    resolve_container(db, storage, container).await
+}
+
+/// Replaces the id of a supplier with its name, names are left as they are and
+/// unknown ids are not found
+pub async fn resolve_supplier(
+    db: &Pool<Postgres>,
+    supplier: &mut String,
+) -> Result<(), sqlx::Error> {
+    let Ok(id) = Uuid::parse_str(supplier) else {
+        return Ok(());
+    };
+
+    *supplier = sqlx::query_scalar!("SELECT name FROM supplier WHERE id = $1", id)
+        .fetch_one(db)
+        .await?;
+    Ok(())
+}
+
+pub async fn item_id(db: &Pool<Postgres>, name: &str) -> Result<String, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT id::TEXT AS "id!" FROM item WHERE name = $1"#,
+        name
+    )
+    .fetch_one(db)
+    .await
+}
+
+pub async fn storage_id(db: &Pool<Postgres>, name: &str) -> Result<String, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT id::TEXT AS "id!" FROM storage WHERE name = $1"#,
+        name
+    )
+    .fetch_one(db)
+    .await
+}
+
+pub async fn container_id(
+    db: &Pool<Postgres>,
+    storage: &str,
+    name: &str,
+) -> Result<String, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT id::TEXT AS "id!" FROM location WHERE storage = $1 AND container = $2"#,
+        storage,
+        name
+    )
+    .fetch_one(db)
+    .await
+}
+
+pub async fn supplier_id(db: &Pool<Postgres>, name: &str) -> Result<String, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT id::TEXT AS "id!" FROM supplier WHERE name = $1"#,
+        name
+    )
+    .fetch_one(db)
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::{Pool, Postgres};
+
+    use crate::db;
+
+    #[sqlx::test]
+    async fn ids_survive_renames(db: Pool<Postgres>) {
+        db::storage::create(&db, "meta", false, None).await.unwrap();
+        db::container::create(&db, "tejplåda", "meta", None)
+            .await
+            .unwrap();
+        let storage = super::storage_id(&db, "meta").await.unwrap();
+        let container = super::container_id(&db, "meta", "tejplåda").await.unwrap();
+
+        db::storage::change(&db, "meta", Some("Meta"), false, None)
+            .await
+            .unwrap();
+        db::container::change(&db, "tejplåda", Some("Tejplåda"), "Meta")
+            .await
+            .unwrap();
+
+        let mut name = storage.clone();
+        super::resolve_storage(&db, &mut name).await.unwrap();
+        assert_eq!(name, "Meta");
+        assert_eq!(super::storage_id(&db, "Meta").await.unwrap(), storage);
+
+        let mut name = container.clone();
+        super::resolve_container(&db, "Meta", &mut name)
+            .await
+            .unwrap();
+        assert_eq!(name, "Tejplåda");
+
+        // Only containers in the storage are found
+        let mut name = container.clone();
+        assert!(matches!(
+            super::resolve_container(&db, "spritis", &mut name).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+
+        // Anything that looks like an id is one, even if there is nothing with it
+        let mut name = sqlx::types::Uuid::nil().to_string();
+        assert!(matches!(
+            super::resolve_storage(&db, &mut name).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+
+        let mut name = String::from("meta");
+        super::resolve_storage(&db, &mut name).await.unwrap();
+        assert_eq!(name, "meta");
+
+        // The database refuses names that look like ids as well
+        assert!(sqlx::query!(
+            "INSERT INTO storage (name) VALUES ($1)",
+            sqlx::types::Uuid::nil().to_string()
+        )
+        .execute(&db)
+        .await
+        .is_err());
+    }
+}
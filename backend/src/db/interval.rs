@@ -35,12 +35,12 @@ impl From<PgInterval> for Interval {
     }
 }
 
-impl Into<PgInterval> for Interval {
-    fn into(self) -> PgInterval {
+impl From<Interval> for PgInterval {
+    fn from(val: Interval) -> Self {
         PgInterval {
-            months: self.months,
-            days: self.days,
-            microseconds: self.microseconds,
+            months: val.months,
+            days: val.days,
+            microseconds: val.microseconds,
         }
     }
 }
@@ -7,7 +7,10 @@ use sqlx::{
 use utoipa::ToSchema;
 
 use crate::db::OrderState;
-use crate::{db::interval::Interval, error::Error};
+use crate::{
+    db::{self, interval::Interval, unit::ItemUnit},
+    error::{Error, FieldError},
+};
 
 pub struct Location {
     pub storage: String,
@@ -40,20 +43,65 @@ struct StoredItem {
 // }
 
 /// An item with an amount lower than its order floor
-#[derive(Serialize, ToSchema)]
+#[derive(Debug, PartialEq, Serialize, ToSchema)]
 pub struct ShortageItem {
     /// The items name
     name: String,
+    /// The category the item is in
+    category: Option<String>,
     /// The name of the storage where the item is located
     storage: String,
     /// The name of the container where the item is stored
     container: String,
     /// The number of item currently in storage
     amount: f32,
-    /// The number of items to buy to reach the order ceiling
+    /// The number of items to buy to reach the order ceiling, after what is incoming
     amount_to_buy: f32,
+    /// The number of items on their way in shipments that have not yet arrived
+    incoming: f32,
+    /// When the first of the incoming items are expected to arrive
+    expected_arrival: Option<DateTime<Utc>>,
+    /// The unit that the amount is measured in
+    unit: String,
+}
+
+/// The shortages that should be bought from one supplier
+#[derive(Debug, Serialize, ToSchema, PartialEq)]
+pub struct PurchaseOrder {
+    /// The suppliers name, none for items without a supplier
+    pub supplier: Option<String>,
+    /// Link to the suppliers website
+    pub supplier_link: Option<String>,
+    /// The items to buy
+    pub items: Vec<PurchaseOrderItem>,
+}
+
+/// An item to buy, summed over all storages where it is short
+#[derive(Debug, Serialize, ToSchema, PartialEq)]
+pub struct PurchaseOrderItem {
+    /// The items name
+    pub name: String,
     /// The unit that the amount is measured in
+    pub unit: String,
+    /// The number of items to buy to reach the order ceiling in every storage
+    pub amount_to_buy: f32,
+    /// Link to the item on the suppliers website
+    pub link: Option<String>,
+    /// The storage the items should go to if only one storage is short
+    pub storage: Option<String>,
+    /// The container the items should go to if only one container is short
+    pub container: Option<String>,
+}
+
+struct PurchaseOrderRow {
+    supplier: Option<String>,
+    supplier_link: Option<String>,
+    name: String,
     unit: String,
+    amount_to_buy: f32,
+    link: Option<String>,
+    storage: Option<String>,
+    container: Option<String>,
 }
 
 #[derive(Debug, Serialize, sqlx::FromRow, ToSchema, PartialEq)]
@@ -79,11 +127,15 @@ pub struct DueItem {
     unit: String,
     // The number of item currently in storage
     amount: f32,
+    /// The category the item is in
+    category: Option<String>,
 }
 
 /// An item ment to be viewed in the context of a tree representation of the db
 #[derive(Debug, Serialize, PartialEq, ToSchema)]
 pub struct MinimalItem {
+    /// The items id
+    pub id: String,
     /// The items name
     pub name: String,
     /// The current amount
@@ -99,12 +151,18 @@ pub struct MinimalItem {
 /// Basic item information
 #[derive(Debug, PartialEq, Serialize, ToSchema)]
 pub struct BasicItem {
+    /// The items id
+    pub id: String,
     /// The items name
     pub name: String,
     /// The current total amount of the item accros all storages
     pub amount: f32,
     /// The unit the amount is counted in
     pub unit: String,
+    /// The category the item is in
+    pub category: Option<String>,
+    /// Free-form tags on the item
+    pub tags: Vec<String>,
     /// List of storage locations containing the item with basic info
     pub storage: Vec<BasicItemStorage>,
 }
@@ -124,16 +182,26 @@ pub struct BasicItemStorage {
 /// Detailed item infarmation
 #[derive(Debug, PartialEq, Serialize, ToSchema)]
 pub struct DetailedItem {
+    /// The items id
+    pub id: String,
     /// The items name
     pub name: String,
     /// The maximum inventory interval of the item
     pub inventory_interval: Option<Interval>,
     /// The unit every amount is counted in
     pub unit: String,
+    /// Other units the item can be counted in, to show amounts in them as well
+    pub units: Vec<ItemUnit>,
+    /// The category the item is in
+    pub category: Option<String>,
+    /// Free-form tags on the item
+    pub tags: Vec<String>,
     /// The storages that this item type is stored in
     pub storage: Vec<StorageListing>,
     /// The suppliers that the item is bought from
     pub supplier: Vec<SupplierListing>,
+    /// The barcodes the item is identified by when scanned
+    pub barcodes: Vec<String>,
 }
 
 /// Detailed information about where an item is stored
@@ -153,6 +221,12 @@ pub struct StorageListing {
     state: OrderState,
     /// The next time the item is to be inventoried
     next_inventory: Option<DateTime<Utc>>,
+    /// The average number of items used per day
+    consumption: Option<f32>,
+    /// When the item is expected to run out at the current consumption
+    stockout: Option<DateTime<Utc>>,
+    /// When the amount is expected to go below the order floor at the current consumption
+    below_min: Option<DateTime<Utc>>,
 }
 
 /// Information about the supplier of an item
@@ -166,12 +240,21 @@ pub struct SupplierListing {
     prfered: bool,
 }
 
-pub async fn get_count(db: &Pool<Postgres>) -> Result<Option<i64>, sqlx::Error> {
+/// Counts the items stored in storages the user can read
+pub async fn get_count(
+    db: &Pool<Postgres>,
+    permitted_storages: &[String],
+) -> Result<Option<i64>, sqlx::Error> {
     Ok(sqlx::query!(
         r#"
-            SELECT count(*)
-            FROM item
-        "#
+            SELECT count(DISTINCT stored_item.item_id)
+            FROM stored_item
+            JOIN location ON location.id = stored_item.container_id
+            WHERE
+                location.protected <> true OR
+                LOWER(location.storage) IN (SELECT UNNEST($1::TEXT[]))
+        "#,
+        permitted_storages
     )
     .fetch_one(db)
     .await?
@@ -182,10 +265,11 @@ pub async fn get_location(db: &Pool<Postgres>, item: &str) -> Result<Vec<Locatio
     sqlx::query_as!(
         Location,
         r#"
-            SELECT storage, container
+            SELECT location.storage as "storage!", location.container as "container!"
             FROM stored_item
-            JOIN storage ON stored_item.storage = storage.name
-            WHERE item = $1
+            JOIN item ON item.id = stored_item.item_id
+            JOIN location ON location.id = stored_item.container_id
+            WHERE item.name = $1
         "#,
         item
     )
@@ -202,22 +286,20 @@ pub async fn get_all_in_storage_grouped_by_container_minimal(
         MinimalItem,
         r#"
             SELECT
+                item.id::TEXT AS "id!",
                 item.name,
                 stored_item.amount,
                 item.unit,
                 current_state.state as "state!: OrderState",
-                next_inventory(stored_item.item, stored_item.container, stored_item.storage)
+                next_inventory(stored_item.item_id, stored_item.container_id)
             FROM stored_item
-            JOIN item ON item.name = stored_item.item
-            JOIN current_state ON current_state.item = stored_item.item
-            WHERE stored_item.storage = $1 AND stored_item.container = $2
-            GROUP BY
-                item.name,
-                stored_item.item,
-                stored_item.container,
-                stored_item.storage,
-                stored_item.amount,
-                current_state.state
+            JOIN item ON item.id = stored_item.item_id
+            JOIN location ON location.id = stored_item.container_id
+            JOIN current_state ON
+                current_state.item_id = stored_item.item_id AND
+                current_state.container_id = stored_item.container_id
+            WHERE location.storage = $1 AND location.container = $2
+            ORDER BY item.name
         "#,
         storage,
         container
@@ -226,6 +308,7 @@ pub async fn get_all_in_storage_grouped_by_container_minimal(
     .await
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn get_all_filtered_basic(
     db: &Pool<Postgres>,
     name: Option<&str>,
@@ -234,6 +317,8 @@ pub async fn get_all_filtered_basic(
     supplier: Option<&str>,
     min: Option<f32>,
     max: Option<f32>,
+    category: Option<&str>,
+    tag: Option<&str>,
     permitted_storages: &[String],
 ) -> Result<Vec<BasicItem>, sqlx::Error> {
     sqlx::query_as!(
@@ -241,42 +326,45 @@ pub async fn get_all_filtered_basic(
         r#"
             WITH storages AS (
                 SELECT 
-                    stored_item.item,
+                    stored_item.item_id,
                     stored_item.amount,
-                    stored_item.storage,
-                    stored_item.container,
+                    location.storage,
+                    location.container,
                     (
-                        stored_item.storage,
-                        stored_item.container,
+                        location.storage,
+                        location.container,
                         current_state.state
                     )::storage_listing_basic AS "entry"
                 FROM stored_item
                 JOIN current_state ON
-                    current_state.item = stored_item.item AND
-                    current_state.storage = stored_item.storage AND
-                    current_state.container = stored_item.container
-                JOIN storage ON stored_item.storage = storage.name
-                WHERE storage.protected <> true OR
-                        LOWER(stored_item.storage) IN (SELECT UNNEST($7::TEXT[]))
-                GROUP BY
-                    stored_item.item,
-                    stored_item.storage,
-                    stored_item.container,
-                    current_state.state
-                ORDER BY stored_item.storage, stored_item.container
+                    current_state.item_id = stored_item.item_id AND
+                    current_state.container_id = stored_item.container_id
+                JOIN location ON location.id = stored_item.container_id
+                WHERE location.protected <> true OR
+                        LOWER(location.storage) IN (SELECT UNNEST($7::TEXT[]))
+                ORDER BY location.storage, location.container
             )
             SELECT
+                item.id::TEXT AS "id!",
                 item.name,
                 SUM(storages.amount) as "amount!",
                 item.unit,
+                item.category,
+                ARRAY (
+                    SELECT tag
+                    FROM item_tag
+                    WHERE item_tag.item_id = item.id
+                    ORDER BY tag
+                ) AS "tags!",
                 ARRAY (
                     SELECT entry
                     FROM storages
-                    WHERE storages.item = item.name
+                    WHERE storages.item_id = item.id
                 ) AS "storage!: Vec<BasicItemStorage>"
             FROM item
-            JOIN storages ON item.name = storages.item
-            LEFT JOIN supplier_item ON supplier_item.item = item.name
+            JOIN storages ON item.id = storages.item_id
+            LEFT JOIN supplier_item ON supplier_item.item_id = item.id
+            LEFT JOIN supplier ON supplier.id = supplier_item.supplier_id
             WHERE
                 ($1::TEXT IS NULL OR
                     (
@@ -286,10 +374,15 @@ pub async fn get_all_filtered_basic(
                 ) AND
                 ($2::TEXT IS NULL OR storages.storage = $2) AND
                 ($3::TEXT IS NULL OR storages.container = $3) AND
-                ($4::TEXT IS NULL OR supplier_item.supplier = $4) AND
+                ($4::TEXT IS NULL OR supplier.name = $4) AND
                 ($5::REAL IS NULL OR storages.amount >= $5) AND
-                ($6::REAL IS NULL OR storages.amount <= $6)
-            GROUP BY item.name
+                ($6::REAL IS NULL OR storages.amount <= $6) AND
+                ($8::TEXT IS NULL OR in_category(item.category, $8)) AND
+                ($9::TEXT IS NULL OR EXISTS (
+                    SELECT 1 FROM item_tag WHERE item_tag.item_id = item.id AND tag = $9
+                ))
+            GROUP BY item.name, item.id
+            ORDER BY item.name
         "#,
         name,
         storage,
@@ -297,7 +390,9 @@ pub async fn get_all_filtered_basic(
         supplier,
         min,
         max,
-        permitted_storages
+        permitted_storages,
+        category,
+        tag
     )
     .fetch_all(db)
     .await
@@ -314,56 +409,80 @@ pub async fn get_item_by_name_detailed(
         r#"
             WITH storages AS (
                 SELECT 
-                    stored_item.item,
-                    stored_item.storage,
-                    stored_item.container,
+                    stored_item.item_id,
                     (
-                        stored_item.storage,
-                        stored_item.container,
+                        location.storage,
+                        location.container,
                         stored_item.amount,
                         stored_item.min,
                         stored_item.max,
                         current_state.state,
-                        next_inventory(stored_item.item, stored_item.container, stored_item.storage)
+                        next_inventory(stored_item.item_id, stored_item.container_id),
+                        forecast.per_day,
+                        forecast.stockout,
+                        forecast.below_min
                     )::storage_listing AS "entry"
                 FROM stored_item
-                JOIN storage ON stored_item.storage = storage.name
+                JOIN location ON location.id = stored_item.container_id
                 JOIN current_state ON
-                    current_state.item = stored_item.item AND
-                    current_state.storage = stored_item.storage AND
-                    current_state.container = stored_item.container
+                    current_state.item_id = stored_item.item_id AND
+                    current_state.container_id = stored_item.container_id
+                JOIN forecast ON
+                    forecast.item_id = stored_item.item_id AND
+                    forecast.container_id = stored_item.container_id
                 WHERE
-                    storage.protected <> true OR
-                    LOWER(storage.name) In (SELECT UNNEST($2::TEXT[]))
-                ORDER BY stored_item.storage, stored_item.container
+                    location.protected <> true OR
+                    LOWER(location.storage) In (SELECT UNNEST($2::TEXT[]))
+                ORDER BY location.storage, location.container
             ),
             suppliers AS (
                 SELECT
-                    item,
+                    supplier_item.item_id,
                     (
-                        supplier,
-                        link,
-                        prefered
+                        supplier.name,
+                        supplier_item.link,
+                        supplier_item.prefered
                     )::supplier_listing AS "supplier"
                 FROM supplier_item
+                JOIN supplier ON supplier.id = supplier_item.supplier_id
             )
             SELECT
+                item.id::TEXT AS "id!",
                 item.name,
                 item.unit,
+                ARRAY(
+                    SELECT (name, factor)::unit_listing
+                    FROM item_unit
+                    WHERE item_unit.item_id = item.id
+                    ORDER BY name
+                ) AS "units!: Vec<ItemUnit>",
+                item.category,
+                ARRAY(
+                    SELECT tag
+                    FROM item_tag
+                    WHERE item_tag.item_id = item.id
+                    ORDER BY tag
+                ) AS "tags!",
                 item.inventory_interval as "inventory_interval: Interval",
                 ARRAY(
                     SELECT entry
                     FROM storages
-                    WHERE storages.item = item.name
+                    WHERE storages.item_id = item.id
                 ) AS "storage!: Vec<StorageListing>",
                 ARRAY(
                     SELECT supplier
                     FROM suppliers
-                    WHERE suppliers.item = item.name
-                ) AS "supplier!: Vec<SupplierListing>"
+                    WHERE suppliers.item_id = item.id
+                ) AS "supplier!: Vec<SupplierListing>",
+                ARRAY(
+                    SELECT code
+                    FROM barcode
+                    WHERE barcode.item_id = item.id
+                    ORDER BY code
+                ) AS "barcodes!"
             FROM item
             WHERE item.name = $1
-            GROUP BY item.name
+            GROUP BY item.name, item.id
         "#,
         name,
         permitted_storages
@@ -372,8 +491,11 @@ pub async fn get_item_by_name_detailed(
     .await
 }
 
+/// The items below their order floor, only those in the category or its subcategories if one is
+/// given. They are sorted on category so they can be shown grouped.
 pub async fn get_shortage(
     db: &Pool<Postgres>,
+    category: Option<&str>,
     permitted_storages: &[String],
 ) -> Result<Vec<ShortageItem>, sqlx::Error> {
     sqlx::query_as!(
@@ -381,42 +503,137 @@ pub async fn get_shortage(
         r#"
             SELECT
                 item.name,
-                storage,
-                container,
-                amount,
-                (max - amount) AS "amount_to_buy!",
+                item.category,
+                shortage.storage as "storage!",
+                shortage.container as "container!",
+                shortage.amount as "amount!",
+                shortage.amount_to_buy as "amount_to_buy!",
+                shortage.incoming as "incoming!",
+                shortage.expected_arrival,
                 unit
-            FROM stored_item
-            JOIN storage ON storage.name = stored_item.storage
-            JOIN item ON stored_item.item = item.name
+            FROM shortage
+            JOIN item ON shortage.item_id = item.id
             WHERE
-                amount <= min AND
+                shortage.amount_to_buy > 0 AND
                 (
                     protected <> true OR
-                    LOWER(storage.name) IN (SELECT UNNEST($1::TEXT[]))
-                )
+                    LOWER(shortage.storage) IN (SELECT UNNEST($1::TEXT[]))
+                ) AND
+                ($2::TEXT IS NULL OR in_category(item.category, $2))
+            ORDER BY item.category NULLS LAST, item.name, shortage.storage, shortage.container
         "#,
-        permitted_storages
+        permitted_storages,
+        category
     )
     .fetch_all(db)
     .await
 }
 
-pub async fn get_shortage_count(db: &Pool<Postgres>) -> Result<Option<i64>, sqlx::Error> {
+/// Groups the shortages by the supplier they should be bought from, prefering the prefered
+/// supplier of each item
+pub async fn get_purchase_orders(
+    db: &Pool<Postgres>,
+    permitted_storages: &[String],
+) -> Result<Vec<PurchaseOrder>, sqlx::Error> {
+    let rows = sqlx::query_as!(
+        PurchaseOrderRow,
+        r#"
+            WITH short AS (
+                SELECT
+                    item_id,
+                    SUM(amount_to_buy) AS "amount_to_buy",
+                    CASE WHEN COUNT(*) = 1 THEN MIN(storage) END AS "storage",
+                    CASE WHEN COUNT(*) = 1 THEN MIN(container) END AS "container"
+                FROM shortage
+                WHERE
+                    amount_to_buy > 0 AND
+                    (
+                        protected <> true OR
+                        LOWER(storage) IN (SELECT UNNEST($1::TEXT[]))
+                    )
+                GROUP BY item_id
+            ),
+            source AS (
+                SELECT DISTINCT ON (supplier_item.item_id)
+                    supplier_item.item_id,
+                    supplier.name AS supplier,
+                    supplier.link AS supplier_link,
+                    supplier_item.link
+                FROM supplier_item
+                JOIN supplier ON supplier.id = supplier_item.supplier_id
+                ORDER BY supplier_item.item_id, supplier_item.prefered DESC, supplier.name
+            )
+            SELECT
+                source.supplier as "supplier?",
+                source.supplier_link as "supplier_link?",
+                item.name,
+                item.unit,
+                short.amount_to_buy as "amount_to_buy!",
+                source.link as "link?",
+                short.storage,
+                short.container
+            FROM short
+            JOIN item ON item.id = short.item_id
+            LEFT JOIN source ON source.item_id = short.item_id
+            ORDER BY source.supplier NULLS LAST, item.name
+        "#,
+        permitted_storages
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut orders: Vec<PurchaseOrder> = Vec::new();
+
+    for row in rows {
+        let item = PurchaseOrderItem {
+            name: row.name,
+            unit: row.unit,
+            amount_to_buy: row.amount_to_buy,
+            link: row.link,
+            storage: row.storage,
+            container: row.container,
+        };
+
+        match orders.last_mut() {
+            Some(order) if order.supplier == row.supplier => order.items.push(item),
+            _ => orders.push(PurchaseOrder {
+                supplier: row.supplier,
+                supplier_link: row.supplier_link,
+                items: vec![item],
+            }),
+        }
+    }
+
+    Ok(orders)
+}
+
+pub async fn get_shortage_count(
+    db: &Pool<Postgres>,
+    permitted_storages: &[String],
+) -> Result<Option<i64>, sqlx::Error> {
     Ok(sqlx::query!(
         r#"
         SELECT count(*)
-        FROM stored_item
-        WHERE amount <= min
-    "#
+        FROM shortage
+        WHERE
+            amount_to_buy > 0 AND
+            (
+                protected <> true OR
+                LOWER(storage) IN (SELECT UNNEST($1::TEXT[]))
+            )
+    "#,
+        permitted_storages
     )
     .fetch_one(db)
     .await?
     .count)
 }
 
+/// The items due to be inventoried, only those in the category or its subcategories if one is
+/// given
 pub async fn items_due(
     db: &Pool<Postgres>,
+    category: Option<&str>,
     storages: &[String],
 ) -> Result<Vec<DueStorage>, sqlx::Error> {
     Ok(sqlx::query_as!(
@@ -424,29 +641,29 @@ pub async fn items_due(
         r#"
             WITH items AS (
                 SELECT 
-                    stored_item.item,
-                    stored_item.storage,
-                    stored_item.container,
+                    stored_item.container_id,
                     (
                         name,
                         unit,
-                        amount
+                        amount,
+                        category
                     )::shortage_item AS "entry"
                 FROM stored_item
-                JOIN item ON item.name = stored_item.item
-                WHERE next_inventory(stored_item.item, stored_item.container, stored_item.storage) < CURRENT_TIMESTAMP
+                JOIN item ON item.id = stored_item.item_id
+                WHERE
+                    next_inventory(stored_item.item_id, stored_item.container_id) < CURRENT_TIMESTAMP AND
+                    ($2::TEXT IS NULL OR in_category(item.category, $2))
+                ORDER BY category NULLS LAST, name
             ),
             containers AS (
                 SELECT
-                    container.storage,
+                    container.storage_id,
                     (
                         name,
                         ARRAY(
                             SELECT entry
                             FROM items
-                            WHERE
-                                items.container = container.name AND
-                                items.storage = container.storage
+                            WHERE items.container_id = container.id
                         )
                     )::shortage_listing AS "entry"
                 FROM container
@@ -456,12 +673,13 @@ pub async fn items_due(
                 ARRAY(
                     SELECT entry
                     FROM containers
-                    WHERE containers.storage = storage.name
+                    WHERE containers.storage_id = storage.id
                 ) AS "containers!: Vec<DueContainer>"
             FROM storage
             WHERE LOWER(storage.name) IN (SELECT UNNEST($1::TEXT[]))
         "#,
-        storages
+        storages,
+        category
     )
     .fetch_all(db)
     .await?
@@ -485,6 +703,9 @@ pub async fn items_due(
     .collect())
 }
 
+/// Creates an item in a transaction of its own
+#[cfg(test)]
+#[allow(clippy::too_many_arguments)]
 pub async fn create(
     db: &Pool<Postgres>,
     id: &str,
@@ -498,6 +719,35 @@ pub async fn create(
     inventory_interval: Option<Interval>,
 ) -> Result<(), sqlx::Error> {
     let mut db = db.begin().await?;
+    create_in_transaction(
+        &mut db,
+        id,
+        storage,
+        container,
+        item,
+        min,
+        max,
+        amount,
+        unit,
+        inventory_interval,
+    )
+    .await?;
+    db.commit().await
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn create_in_transaction(
+    db: &mut Transaction<'static, Postgres>,
+    id: &str,
+    storage: &str,
+    container: &str,
+    item: &str,
+    min: Option<f32>,
+    max: Option<f32>,
+    amount: f32,
+    unit: Option<&str>,
+    inventory_interval: Option<Interval>,
+) -> Result<(), sqlx::Error> {
     if let Some(unit) = unit {
         sqlx::query!(
             r#"
@@ -509,7 +759,7 @@ pub async fn create(
             unit,
             inventory_interval.map(Into::<PgInterval>::into)
         )
-        .execute(&mut *db)
+        .execute(&mut **db)
         .await?;
     } else {
         sqlx::query!(
@@ -521,21 +771,20 @@ pub async fn create(
             item,
             inventory_interval.map(Into::<PgInterval>::into)
         )
-        .execute(&mut *db)
+        .execute(&mut **db)
         .await?;
     }
 
     sqlx::query!(
         r#"
             INSERT INTO stored_item (
-                storage,
-                container,
-                item,
+                container_id,
+                item_id,
                 min,
                 max,
                 amount
             )
-            VALUES ($1, $2, $3, $4, $5, $6)
+            VALUES (container_ref($1, $2), item_ref($3), $4, $5, $6)
         "#,
         storage,
         container,
@@ -544,19 +793,18 @@ pub async fn create(
         max,
         amount,
     )
-    .execute(&mut *db)
+    .execute(&mut **db)
     .await?;
 
     sqlx::query!(
         r#"
             INSERT INTO log (
-                item,
-                storage,
-                container,
+                item_id,
+                container_id,
                 amount,
                 user_
             )
-            VALUES ($1, $2, $3, $4, $5)
+            VALUES (item_ref($1), container_ref($2, $3), $4, $5)
         "#,
         item,
         storage,
@@ -564,10 +812,10 @@ pub async fn create(
         amount,
         id
     )
-    .execute(&mut *db)
+    .execute(&mut **db)
     .await?;
 
-    db.commit().await
+    Ok(())
 }
 
 pub async fn add_supplier(
@@ -579,8 +827,8 @@ pub async fn add_supplier(
 ) -> Result<PgQueryResult, sqlx::Error> {
     sqlx::query!(
         r#"
-            INSERT INTO supplier_item (supplier, item, link, prefered)
-            VALUES ($1, $2, $3, $4)
+            INSERT INTO supplier_item (supplier_id, item_id, link, prefered)
+            VALUES (supplier_ref($1), item_ref($2), $3, $4)
         "#,
         supplier,
         item,
@@ -605,8 +853,8 @@ pub async fn update_supplier(
                 link = $1,
                 prefered = $2
             WHERE
-                item = $3 AND
-                supplier = $4
+                item_id = (SELECT id FROM item WHERE name = $3) AND
+                supplier_id = (SELECT id FROM supplier WHERE name = $4)
         "#,
         link,
         prefered,
@@ -617,20 +865,47 @@ pub async fn update_supplier(
     .await
 }
 
+/// Changes an item, its category and tags are only changed when given and the tags replace the
+/// ones it has
 pub async fn change(
     db: &Pool<Postgres>,
     name: &str,
     new_name: Option<&str>,
     unit: &str,
     inventory_interval: Option<Interval>,
-) -> Result<PgQueryResult, sqlx::Error> {
+    category: Option<Option<&str>>,
+    tags: Option<&[String]>,
+) -> Result<(), Error> {
     let new_name = if let Some(name) = new_name {
         name
     } else {
         name
     };
 
-    sqlx::query!(
+    let mut db = db.begin().await?;
+
+    let other = sqlx::query_scalar!(
+        r#"
+            SELECT EXISTS (
+                SELECT 1
+                FROM item_unit
+                JOIN item ON item.id = item_unit.item_id
+                WHERE item.name = $1 AND item_unit.name = $2
+            ) AS "other!"
+        "#,
+        name,
+        unit
+    )
+    .fetch_one(&mut *db)
+    .await?;
+    if other {
+        return Err(Error::Validation(vec![FieldError::new(
+            "unit",
+            "is already another unit of the item",
+        )]));
+    }
+
+    let result = sqlx::query!(
         r#"
             UPDATE item
             SET 
@@ -644,10 +919,74 @@ pub async fn change(
         unit,
         inventory_interval.map(Into::<PgInterval>::into)
     )
-    .execute(db)
+    .execute(&mut *db)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::NotFound);
+    }
+
+    if let Some(category) = category {
+        sqlx::query!(
+            "UPDATE item SET category = $2 WHERE name = $1",
+            new_name,
+            category
+        )
+        .execute(&mut *db)
+        .await?;
+    }
+
+    if let Some(tags) = tags {
+        sqlx::query!(
+            r#"
+                DELETE FROM item_tag
+                WHERE item_id = (SELECT id FROM item WHERE name = $1) AND tag <> ALL($2)
+            "#,
+            new_name,
+            tags
+        )
+        .execute(&mut *db)
+        .await?;
+        tag(&mut db, new_name, tags).await?;
+    }
+
+    Ok(db.commit().await?)
+}
+
+/// Adds tags to an item, tags it already has are left as they are
+pub async fn tag(
+    db: &mut Transaction<'static, Postgres>,
+    item: &str,
+    tags: &[String],
+) -> Result<PgQueryResult, sqlx::Error> {
+    sqlx::query!(
+        r#"
+            INSERT INTO item_tag (item_id, tag)
+            SELECT item_ref($1), UNNEST($2::TEXT[])
+            ON CONFLICT DO NOTHING
+        "#,
+        item,
+        tags
+    )
+    .execute(&mut **db)
+    .await
+}
+
+pub async fn set_category(
+    db: &mut Transaction<'static, Postgres>,
+    item: &str,
+    category: &str,
+) -> Result<PgQueryResult, sqlx::Error> {
+    sqlx::query!(
+        "UPDATE item SET category = $2 WHERE name = $1",
+        item,
+        category
+    )
+    .execute(&mut **db)
     .await
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn change_stored_item(
     db: &Pool<Postgres>,
     name: &str,
@@ -666,10 +1005,12 @@ pub async fn change_stored_item(
         r#"
             SELECT amount
             FROM stored_item
+            JOIN item ON item.id = stored_item.item_id
+            JOIN location ON location.id = stored_item.container_id
             WHERE
-                item = $1 AND
-                storage = $2 AND
-                container = $3
+                item.name = $1 AND
+                location.storage = $2 AND
+                location.container = $3
         "#,
         name,
         storage,
@@ -678,6 +1019,10 @@ pub async fn change_stored_item(
     .fetch_one(&mut *db)
     .await?;
 
+    if amount < old_amount {
+        db::lot::consume(&mut db, name, storage, container, old_amount - amount).await?;
+    }
+
     let new_storage = if let Some(storage) = new_storage {
         storage
     } else {
@@ -694,15 +1039,13 @@ pub async fn change_stored_item(
         r#"
             UPDATE stored_item
             SET
-                storage = $1,
-                container = $2,
+                container_id = container_ref($1, $2),
                 amount = $3,
                 min = $4,
                 max = $5
             WHERE
-                item = $6 AND
-                storage = $7 AND
-                container = $8
+                item_id = item_ref($6) AND
+                container_id = container_ref($7, $8)
         "#,
         new_storage,
         new_container,
@@ -720,13 +1063,12 @@ pub async fn change_stored_item(
         sqlx::query!(
             r#"
             INSERT INTO log (
-                item,
-                storage,
-                container,
+                item_id,
+                container_id,
                 amount,
                 user_
             )
-            VALUES ($1, $2, $3, $4, $5)
+            VALUES (item_ref($1), container_ref($2, $3), $4, $5)
         "#,
             name,
             new_storage,
@@ -741,6 +1083,7 @@ pub async fn change_stored_item(
     db.commit().await
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn move_item(
     db: &mut Transaction<'static, Postgres>,
     item: &str,
@@ -755,10 +1098,12 @@ pub async fn move_item(
         r#"
             SELECT amount
             FROM stored_item
+            JOIN item ON item.id = stored_item.item_id
+            JOIN location ON location.id = stored_item.container_id
             WHERE
-                item = $1 AND
-                storage = $2 AND
-                container = $3
+                item.name = $1 AND
+                location.storage = $2 AND
+                location.container = $3
         "#,
         item,
         from_storage,
@@ -770,7 +1115,10 @@ pub async fn move_item(
     let amount = amount.unwrap_or(max_amount);
 
     if amount > max_amount {
-        return Err(Error::BadRequest);
+        return Err(Error::Validation(vec![FieldError::new(
+            "amount",
+            "more than is stored",
+        )]));
     }
 
     if sqlx::query!(
@@ -778,9 +1126,8 @@ pub async fn move_item(
             SELECT (1) as "id"
             FROM stored_item
             WHERE
-                item = $1 AND
-                storage = $2 AND
-                container = $3
+                item_id = item_ref($1) AND
+                container_id = container_ref($2, $3)
         "#,
         item,
         to_storage,
@@ -790,14 +1137,24 @@ pub async fn move_item(
     .await?
     .is_some()
     {
+        db::lot::relocate(
+            db,
+            item,
+            from_storage,
+            from_container,
+            to_storage,
+            to_container,
+            amount,
+        )
+        .await?;
+
         sqlx::query!(
             r#"
                 UPDATE stored_item
                 SET amount = amount + $1
                 WHERE
-                    item = $2 AND
-                    storage = $3 AND
-                    container = $4
+                    item_id = item_ref($2) AND
+                    container_id = container_ref($3, $4)
             "#,
             amount,
             item,
@@ -812,9 +1169,8 @@ pub async fn move_item(
                 r#"
                 DELETE FROM stored_item
                 WHERE
-                    item = $1 AND
-                    storage = $2 AND
-                    container = $3
+                    item_id = item_ref($1) AND
+                    container_id = container_ref($2, $3)
             "#,
                 item,
                 from_storage,
@@ -828,9 +1184,8 @@ pub async fn move_item(
                 UPDATE stored_item
                 SET amount = amount - $4
                 WHERE
-                    item = $1 AND
-                    storage = $2 AND
-                    container = $3
+                    item_id = item_ref($1) AND
+                    container_id = container_ref($2, $3)
             "#,
                 item,
                 from_storage,
@@ -844,13 +1199,10 @@ pub async fn move_item(
         let result = sqlx::query!(
             r#"
                 UPDATE stored_item
-                SET
-                    storage = $1, 
-                    container = $2
+                SET container_id = container_ref($1, $2)
                 WHERE
-                    item = $3 AND
-                    storage = $4 AND
-                    container = $5
+                    item_id = item_ref($3) AND
+                    container_id = container_ref($4, $5)
             "#,
             to_storage,
             to_container,
@@ -869,15 +1221,13 @@ pub async fn move_item(
     sqlx::query!(
         r#"
             INSERT INTO move_log (
-                item,
-                from_storage,
-                from_container,
-                to_storage,
-                to_container,
+                item_id,
+                from_container_id,
+                to_container_id,
                 amount,
                 user_
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            VALUES (item_ref($1), container_ref($2, $3), container_ref($4, $5), $6, $7)
         "#,
         item,
         from_storage,
@@ -900,14 +1250,35 @@ pub async fn update_amount_in_transaction(
     container: &str,
     amount: f32,
 ) -> Result<PgQueryResult, sqlx::Error> {
+    let old_amount = sqlx::query_scalar!(
+        r#"
+            SELECT amount
+            FROM stored_item
+            JOIN item ON item.id = stored_item.item_id
+            JOIN location ON location.id = stored_item.container_id
+            WHERE
+                item.name = $1 AND
+                location.storage = $2 AND
+                location.container = $3
+        "#,
+        item,
+        storage,
+        container
+    )
+    .fetch_one(&mut **db)
+    .await?;
+
+    if amount < old_amount {
+        db::lot::consume(db, item, storage, container, old_amount - amount).await?;
+    }
+
     sqlx::query!(
         r#"
             UPDATE stored_item
             SET amount = $1 
             WHERE
-                item = $2 AND
-                storage = $3 AND
-                container = $4
+                item_id = item_ref($2) AND
+                container_id = container_ref($3, $4)
         "#,
         amount,
         item,
@@ -917,16 +1288,18 @@ pub async fn update_amount_in_transaction(
     .execute(&mut **db)
     .await?;
 
+    // clock_timestamp() since the same item can be updated in several containers in one
+    // transaction and log is keyed on (item, time)
     sqlx::query!(
         r#"
             INSERT INTO log (
-                item,
-                storage,
-                container,
+                item_id,
+                container_id,
                 amount,
-                user_
+                user_,
+                time
             )
-            VALUES ($1, $2, $3, $4, $5)
+            VALUES (item_ref($1), container_ref($2, $3), $4, $5, clock_timestamp())
         "#,
         item,
         storage,
@@ -947,8 +1320,8 @@ pub async fn delete_supplier(
         r#"
             DELETE FROM supplier_item
             WHERE
-                item = $1 AND
-                supplier = $2
+                item_id = (SELECT id FROM item WHERE name = $1) AND
+                supplier_id = (SELECT id FROM supplier WHERE name = $2)
         "#,
         item,
         supplier
@@ -966,10 +1339,13 @@ pub async fn delete(
     let result = sqlx::query!(
         r#"
             DELETE FROM stored_item
+            USING item, location
             WHERE
-                item = $1 AND
-                storage = $2 AND
-                container = $3
+                item.id = stored_item.item_id AND
+                location.id = stored_item.container_id AND
+                item.name = $1 AND
+                location.storage = $2 AND
+                location.container = $3
         "#,
         item,
         storage,
@@ -980,9 +1356,10 @@ pub async fn delete(
 
     if sqlx::query!(
         r#"
-            SELECT item
+            SELECT item_id
             FROM stored_item
-            WHERE item = $1
+            JOIN item ON item.id = stored_item.item_id
+            WHERE item.name = $1
         "#,
         item
     )
@@ -1006,6 +1383,7 @@ pub async fn delete(
 
 #[cfg(test)]
 mod test {
+    use chrono::SubsecRound;
     use sqlx::{Pool, Postgres};
 
     use crate::db::{
@@ -1013,8 +1391,10 @@ mod test {
         interval::Interval,
         item::{
             get_all_filtered_basic, BasicItem, BasicItemStorage, DetailedItem, DueContainer,
-            DueItem, DueStorage, Item, MinimalItem, StorageListing, StoredItem,
+            DueItem, DueStorage, Item, MinimalItem, PurchaseOrder, PurchaseOrderItem, ShortageItem,
+            StorageListing, StoredItem,
         },
+        shipment::ShipmentItem,
         OrderState,
     };
 
@@ -1044,6 +1424,7 @@ mod test {
         assert_eq!(
             minimal,
             vec![MinimalItem {
+                id: db::id::item_id(&db, "tejp").await.unwrap(),
                 name: String::from("tejp"),
                 amount: 5.0,
                 unit: String::from("st"),
@@ -1080,7 +1461,9 @@ mod test {
             None,
             None,
             None,
-            &vec![String::from("meta")],
+            None,
+            None,
+            &[String::from("meta")],
         )
         .await
         .unwrap();
@@ -1088,9 +1471,12 @@ mod test {
         assert_eq!(
             item,
             vec![BasicItem {
+                id: db::id::item_id(&db, "tejp").await.unwrap(),
                 name: String::from("tejp"),
                 amount: 7.0,
                 unit: String::from("st"),
+                category: None,
+                tags: vec![],
                 storage: vec![BasicItemStorage {
                     storage: String::from("meta"),
                     container: String::new(),
@@ -1127,7 +1513,9 @@ mod test {
             None,
             None,
             None,
-            &vec![String::from("meta")],
+            None,
+            None,
+            &[String::from("meta")],
         )
         .await
         .unwrap();
@@ -1135,9 +1523,12 @@ mod test {
         assert_eq!(
             item,
             vec![BasicItem {
+                id: db::id::item_id(&db, "tejp").await.unwrap(),
                 name: String::from("tejp"),
                 amount: 7.0,
                 unit: String::from("st"),
+                category: None,
+                tags: vec![],
                 storage: vec![BasicItemStorage {
                     storage: String::from("meta"),
                     container: String::from(""),
@@ -1154,7 +1545,9 @@ mod test {
             None,
             None,
             None,
-            &vec![String::from("meta")],
+            None,
+            None,
+            &[String::from("meta")],
         )
         .await
         .unwrap();
@@ -1189,7 +1582,9 @@ mod test {
             None,
             None,
             None,
-            &vec![String::from("meta")],
+            None,
+            None,
+            &[String::from("meta")],
         )
         .await
         .unwrap();
@@ -1197,9 +1592,12 @@ mod test {
         assert_eq!(
             item,
             vec![BasicItem {
+                id: db::id::item_id(&db, "tejp").await.unwrap(),
                 name: String::from("tejp"),
                 amount: 7.0,
                 unit: String::from("st"),
+                category: None,
+                tags: vec![],
                 storage: vec![BasicItemStorage {
                     storage: String::from("meta"),
                     container: String::from(""),
@@ -1216,7 +1614,9 @@ mod test {
             None,
             None,
             None,
-            &vec![String::from("meta")],
+            None,
+            None,
+            &[String::from("meta")],
         )
         .await
         .unwrap();
@@ -1251,7 +1651,9 @@ mod test {
             None,
             None,
             None,
-            &vec![String::from("meta")],
+            None,
+            None,
+            &[String::from("meta")],
         )
         .await
         .unwrap();
@@ -1259,9 +1661,12 @@ mod test {
         assert_eq!(
             item,
             vec![BasicItem {
+                id: db::id::item_id(&db, "tejp").await.unwrap(),
                 name: String::from("tejp"),
                 amount: 7.0,
                 unit: String::from("st"),
+                category: None,
+                tags: vec![],
                 storage: vec![BasicItemStorage {
                     storage: String::from("meta"),
                     container: String::from(""),
@@ -1278,7 +1683,9 @@ mod test {
             None,
             None,
             None,
-            &vec![String::from("meta")],
+            None,
+            None,
+            &[String::from("meta")],
         )
         .await
         .unwrap();
@@ -1313,7 +1720,9 @@ mod test {
             None,
             Some(6.0),
             Some(8.0),
-            &vec![String::from("meta")],
+            None,
+            None,
+            &[String::from("meta")],
         )
         .await
         .unwrap();
@@ -1321,9 +1730,12 @@ mod test {
         assert_eq!(
             item,
             vec![BasicItem {
+                id: db::id::item_id(&db, "tejp").await.unwrap(),
                 name: String::from("tejp"),
                 amount: 7.0,
                 unit: String::from("st"),
+                category: None,
+                tags: vec![],
                 storage: vec![BasicItemStorage {
                     storage: String::from("meta"),
                     container: String::from(""),
@@ -1340,7 +1752,9 @@ mod test {
             None,
             Some(8.0),
             Some(11.0),
-            &vec![String::from("meta")],
+            None,
+            None,
+            &[String::from("meta")],
         )
         .await
         .unwrap();
@@ -1393,7 +1807,9 @@ mod test {
             None,
             None,
             None,
-            &vec![String::from("meta"), String::from("örådet")],
+            None,
+            None,
+            &[String::from("meta"), String::from("örådet")],
         )
         .await
         .unwrap();
@@ -1401,9 +1817,12 @@ mod test {
         assert_eq!(
             item,
             vec![BasicItem {
+                id: db::id::item_id(&db, "tejp").await.unwrap(),
                 name: String::from("tejp"),
                 amount: 14.0,
                 unit: String::from("st"),
+                category: None,
+                tags: vec![],
                 storage: vec![
                     BasicItemStorage {
                         storage: String::from("meta"),
@@ -1439,15 +1858,19 @@ mod test {
         .await
         .unwrap();
 
-        let item = super::get_item_by_name_detailed(&db, "tejp", &vec![String::from("meta")])
+        let item = super::get_item_by_name_detailed(&db, "tejp", &[String::from("meta")])
             .await
             .unwrap();
 
         assert_eq!(
             item,
             DetailedItem {
+                id: db::id::item_id(&db, "tejp").await.unwrap(),
                 name: String::from("tejp"),
                 unit: String::from("st"),
+                units: Vec::new(),
+                category: None,
+                tags: vec![],
                 inventory_interval: None,
                 storage: vec![StorageListing {
                     storage: String::from("meta"),
@@ -1456,9 +1879,13 @@ mod test {
                     min: Some(5.0),
                     max: Some(10.0),
                     state: OrderState::Good,
-                    next_inventory: None
+                    next_inventory: None,
+                    consumption: None,
+                    stockout: None,
+                    below_min: None,
                 }],
-                supplier: vec![]
+                supplier: vec![],
+                barcodes: Vec::new(),
             }
         )
     }
@@ -1503,7 +1930,7 @@ mod test {
         let item = super::get_item_by_name_detailed(
             &db,
             "tejp",
-            &vec![String::from("meta"), String::from("örådet")],
+            &[String::from("meta"), String::from("örådet")],
         )
         .await
         .unwrap();
@@ -1511,8 +1938,12 @@ mod test {
         assert_eq!(
             item,
             DetailedItem {
+                id: db::id::item_id(&db, "tejp").await.unwrap(),
                 name: String::from("tejp"),
                 unit: String::from("st"),
+                units: Vec::new(),
+                category: None,
+                tags: vec![],
                 inventory_interval: None,
                 storage: vec![
                     StorageListing {
@@ -1522,7 +1953,10 @@ mod test {
                         min: Some(5.0),
                         max: Some(10.0),
                         state: OrderState::Good,
-                        next_inventory: None
+                        next_inventory: None,
+                        consumption: None,
+                        stockout: None,
+                        below_min: None,
                     },
                     StorageListing {
                         storage: String::from("örådet"),
@@ -1532,9 +1966,13 @@ mod test {
                         max: Some(10.0),
                         state: OrderState::Good,
                         next_inventory: None,
+                        consumption: None,
+                        stockout: None,
+                        below_min: None,
                     }
                 ],
-                supplier: vec![]
+                supplier: vec![],
+                barcodes: Vec::new(),
             }
         )
     }
@@ -1581,8 +2019,16 @@ mod test {
         let stored_item = sqlx::query_as!(
             StoredItem,
             r#"
-                SELECT *
+                SELECT
+                    location.storage AS "storage!",
+                    location.container AS "container!",
+                    item.name AS item,
+                    stored_item.min,
+                    stored_item.max,
+                    stored_item.amount
                 FROM stored_item
+                JOIN item ON item.id = stored_item.item_id
+                JOIN location ON location.id = stored_item.container_id
             "#
         )
         .fetch_one(&db)
@@ -1621,7 +2067,7 @@ mod test {
         .await
         .unwrap();
 
-        super::change(&db, "tejp", Some("silvertejp"), "rullar", None)
+        super::change(&db, "tejp", Some("silvertejp"), "rullar", None, None, None)
             .await
             .unwrap();
 
@@ -1633,7 +2079,9 @@ mod test {
             None,
             None,
             None,
-            &vec![String::from("meta")],
+            None,
+            None,
+            &[String::from("meta")],
         )
         .await
         .unwrap();
@@ -1641,9 +2089,12 @@ mod test {
         assert_eq!(
             item,
             vec![BasicItem {
+                id: db::id::item_id(&db, "silvertejp").await.unwrap(),
                 name: String::from("silvertejp"),
                 amount: 7.0,
                 unit: String::from("rullar"),
+                category: None,
+                tags: vec![],
                 storage: vec![BasicItemStorage {
                     storage: String::from("meta"),
                     container: String::from(""),
@@ -1691,15 +2142,19 @@ mod test {
         .await
         .unwrap();
 
-        let item = super::get_item_by_name_detailed(&db, "tejp", &vec![String::from("meta")])
+        let item = super::get_item_by_name_detailed(&db, "tejp", &[String::from("meta")])
             .await
             .unwrap();
 
         assert_eq!(
             item,
             DetailedItem {
+                id: db::id::item_id(&db, "tejp").await.unwrap(),
                 name: String::from("tejp"),
                 unit: String::from("st"),
+                units: Vec::new(),
+                category: None,
+                tags: vec![],
                 inventory_interval: None,
                 storage: vec![StorageListing {
                     storage: String::from("meta"),
@@ -1708,9 +2163,13 @@ mod test {
                     min: Some(6.0),
                     max: Some(11.0),
                     state: OrderState::Good,
-                    next_inventory: None
+                    next_inventory: None,
+                    consumption: Some(0.0),
+                    stockout: None,
+                    below_min: None,
                 }],
-                supplier: vec![]
+                supplier: vec![],
+                barcodes: Vec::new(),
             }
         )
     }
@@ -1811,8 +2270,16 @@ mod test {
         let stored_item = sqlx::query_as!(
             StoredItem,
             r#"
-                SELECT *
+                SELECT
+                    location.storage AS "storage!",
+                    location.container AS "container!",
+                    item.name AS item,
+                    stored_item.min,
+                    stored_item.max,
+                    stored_item.amount
                 FROM stored_item
+                JOIN item ON item.id = stored_item.item_id
+                JOIN location ON location.id = stored_item.container_id
             "#
         )
         .fetch_one(&db)
@@ -1892,8 +2359,16 @@ mod test {
         let stored_item = sqlx::query_as!(
             StoredItem,
             r#"
-                SELECT *
+                SELECT
+                    location.storage AS "storage!",
+                    location.container AS "container!",
+                    item.name AS item,
+                    stored_item.min,
+                    stored_item.max,
+                    stored_item.amount
                 FROM stored_item
+                JOIN item ON item.id = stored_item.item_id
+                JOIN location ON location.id = stored_item.container_id
             "#
         )
         .fetch_one(&db)
@@ -1944,7 +2419,7 @@ mod test {
             r#"
                 UPDATE log
                     SET time = CURRENT_TIMESTAMP - INTERVAL '1 month'
-                WHERE item = 'tejp' AND storage = 'meta' AND container = ''
+                WHERE item_id = item_ref('tejp') AND container_id = container_ref('meta', '')
             "#
         )
         .execute(&db)
@@ -1989,7 +2464,7 @@ mod test {
         .await
         .unwrap();
 
-        let shortage = super::items_due(&db, &vec![String::from("meta"), String::from("örådet")])
+        let shortage = super::items_due(&db, None, &[String::from("meta"), String::from("örådet")])
             .await
             .unwrap();
 
@@ -2002,10 +2477,155 @@ mod test {
                     items: vec![DueItem {
                         name: String::from("tejp"),
                         unit: String::from("st"),
-                        amount: 7.0
+                        amount: 7.0,
+                        category: None
                     },]
                 }]
             },]
         )
     }
+
+    #[sqlx::test]
+    async fn purchase_orders(db: Pool<Postgres>) {
+        db::storage::create(&db, "meta", false, None).await.unwrap();
+        db::storage::create(&db, "örådet", false, None)
+            .await
+            .unwrap();
+        db::supplier::create(&db, "ICA", None, None, None, None, "mister@metadorerna.se")
+            .await
+            .unwrap();
+        db::supplier::create(&db, "Coop", None, None, None, None, "mister@metadorerna.se")
+            .await
+            .unwrap();
+
+        for (storage, item) in [("meta", "tejp"), ("örådet", "tejp"), ("meta", "eltejp")] {
+            super::create(
+                &db,
+                "test",
+                storage,
+                "",
+                item,
+                Some(5.0),
+                Some(10.0),
+                2.0,
+                Some("st"),
+                None,
+            )
+            .await
+            .unwrap();
+        }
+
+        super::add_supplier(&db, "Coop", "tejp", None, false)
+            .await
+            .unwrap();
+        super::add_supplier(&db, "ICA", "tejp", Some("https://ica.se/tejp"), true)
+            .await
+            .unwrap();
+
+        let orders = super::get_purchase_orders(&db, &[]).await.unwrap();
+
+        assert_eq!(
+            orders,
+            vec![
+                PurchaseOrder {
+                    supplier: Some(String::from("ICA")),
+                    supplier_link: None,
+                    items: vec![PurchaseOrderItem {
+                        name: String::from("tejp"),
+                        unit: String::from("st"),
+                        amount_to_buy: 16.0,
+                        link: Some(String::from("https://ica.se/tejp")),
+                        storage: None,
+                        container: None,
+                    }]
+                },
+                PurchaseOrder {
+                    supplier: None,
+                    supplier_link: None,
+                    items: vec![PurchaseOrderItem {
+                        name: String::from("eltejp"),
+                        unit: String::from("st"),
+                        amount_to_buy: 8.0,
+                        link: None,
+                        storage: Some(String::from("meta")),
+                        container: Some(String::new()),
+                    }]
+                },
+            ]
+        )
+    }
+
+    #[sqlx::test]
+    async fn shortage_with_incoming(db: Pool<Postgres>) {
+        db::storage::create(&db, "meta", false, None).await.unwrap();
+        db::storage::create(&db, "örådet", false, None)
+            .await
+            .unwrap();
+
+        for (storage, item) in [("meta", "tejp"), ("örådet", "tejp"), ("meta", "eltejp")] {
+            super::create(
+                &db,
+                "test",
+                storage,
+                "",
+                item,
+                Some(5.0),
+                Some(10.0),
+                2.0,
+                Some("st"),
+                None,
+            )
+            .await
+            .unwrap();
+        }
+
+        // Postgres keeps timestamps to the microsecond
+        let arrival = sqlx::types::chrono::Utc::now().trunc_subsecs(6);
+
+        db::shipment::create_order(
+            &db,
+            "test",
+            arrival,
+            None,
+            vec![
+                ShipmentItem {
+                    item: String::from("tejp"),
+                    amount: 10.0,
+                    received: 0.0,
+                    storage: None,
+                    container: None,
+                    unit: None,
+                },
+                ShipmentItem {
+                    item: String::from("eltejp"),
+                    amount: 8.0,
+                    received: 0.0,
+                    storage: Some(String::from("meta")),
+                    container: Some(String::new()),
+                    unit: None,
+                },
+            ],
+        )
+        .await
+        .unwrap();
+
+        let shortage = super::get_shortage(&db, None, &[]).await.unwrap();
+
+        assert_eq!(
+            shortage,
+            vec![ShortageItem {
+                name: String::from("tejp"),
+                category: None,
+                storage: String::from("örådet"),
+                container: String::new(),
+                amount: 2.0,
+                amount_to_buy: 6.0,
+                incoming: 2.0,
+                expected_arrival: Some(arrival),
+                unit: String::from("st"),
+            }]
+        );
+
+        assert_eq!(super::get_shortage_count(&db, &[]).await.unwrap(), Some(1));
+    }
 }
@@ -29,38 +29,84 @@ pub struct MoveLog {
     user: String,
     amount: f32,
     time: DateTime<Utc>,
-    from_storage: String,
-    from_container: String,
+    /// Not known if the container has been removed since
+    from_storage: Option<String>,
+    from_container: Option<String>,
     to_storage: String,
     to_container: String,
 }
 
-pub async fn get_all_by_item(db: &Pool<Postgres>, item: &str) -> Result<Vec<Log>, sqlx::Error> {
+/// Gets the history of an item, leaving out everything that happened in protected storages the
+/// user can not read
+pub async fn get_all_by_item(
+    db: &Pool<Postgres>,
+    item: &str,
+    access: &[String],
+) -> Result<Vec<Log>, sqlx::Error> {
     let state_log = sqlx::query_as!(
         StateLog,
         r#"
-            SELECT item, user_ as "user", amount, time, storage, container
+            SELECT
+                item.name AS item,
+                user_ as "user",
+                amount,
+                time,
+                location.storage as "storage!",
+                location.container as "container!"
             FROM log
-            WHERE item = $1
+            JOIN item ON item.id = log.item_id
+            JOIN location ON location.id = log.container_id
+            WHERE
+                item.name = $1 AND
+                (
+                    location.protected <> true OR
+                    LOWER(location.storage) IN (SELECT UNNEST($2::TEXT[]))
+                )
         "#,
-        item
+        item,
+        access
     )
     .fetch_all(db)
     .await?;
 
+    // A move is only shown if both ends can be read, containers that no longer exist are treated
+    // as unprotected
     let move_log = sqlx::query_as!(
         MoveLog,
         r#"
-            SELECT item, user_ as "user", amount, time, from_storage, from_container, to_storage, to_container
+            SELECT
+                item.name AS item,
+                user_ as "user",
+                amount,
+                time,
+                from_.storage as "from_storage?",
+                from_.container as "from_container?",
+                to_.storage as "to_storage!",
+                to_.container as "to_container!"
             FROM move_log
-            WHERE item = $1
+            JOIN item ON item.id = move_log.item_id
+            LEFT JOIN location from_ ON from_.id = move_log.from_container_id
+            JOIN location to_ ON to_.id = move_log.to_container_id
+            WHERE
+                item.name = $1 AND
+                (
+                    from_.protected IS NOT TRUE OR
+                    LOWER(from_.storage) IN (SELECT UNNEST($2::TEXT[]))
+                ) AND
+                (
+                    to_.protected <> true OR
+                    LOWER(to_.storage) IN (SELECT UNNEST($2::TEXT[]))
+                )
         "#,
-        item
-    ).fetch_all(db).await?;
+        item,
+        access
+    )
+    .fetch_all(db)
+    .await?;
 
     Ok(state_log
         .into_iter()
-        .map(|log| Log::State(log))
-        .chain(move_log.into_iter().map(|log| Log::Move(log)))
+        .map(Log::State)
+        .chain(move_log.into_iter().map(Log::Move))
         .collect())
 }
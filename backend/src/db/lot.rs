@@ -0,0 +1,558 @@
+use serde::Serialize;
+use sqlx::{
+    postgres::PgQueryResult,
+    types::{
+        chrono::{DateTime, NaiveDate, Utc},
+        Uuid,
+    },
+    Pool, Postgres, Transaction,
+};
+use utoipa::ToSchema;
+
+use crate::error::{Error, FieldError};
+
+/// Items of a stored item that arrived together and expire together
+#[derive(Debug, PartialEq, Serialize, ToSchema)]
+pub struct Lot {
+    /// The lots id
+    pub id: String,
+    /// The items name
+    pub item: String,
+    /// The storage where the lot is located
+    pub storage: String,
+    /// The container where the lot is stored
+    pub container: String,
+    /// The number of items left in the lot
+    pub amount: f32,
+    /// The unit that the amount is measured in
+    pub unit: String,
+    /// The date the items are best before, none if they don't expire
+    pub best_before: Option<NaiveDate>,
+    /// When the lot arrived
+    pub arrived: DateTime<Utc>,
+}
+
+struct LotAmount {
+    id: Uuid,
+    amount: f32,
+}
+
+pub async fn get(db: &Pool<Postgres>, id: Uuid) -> Result<Lot, sqlx::Error> {
+    sqlx::query_as!(
+        Lot,
+        r#"
+            SELECT
+                lot.id::TEXT AS "id!",
+                item.name AS item,
+                location.storage AS "storage!",
+                location.container AS "container!",
+                lot.amount,
+                item.unit,
+                lot.best_before,
+                lot.arrived
+            FROM lot
+            JOIN item ON item.id = lot.item_id
+            JOIN location ON location.id = lot.container_id
+            WHERE lot.id = $1
+        "#,
+        id
+    )
+    .fetch_one(db)
+    .await
+}
+
+/// The lots of an item in the order they are used, first expiry first
+pub async fn get_by_item(
+    db: &Pool<Postgres>,
+    item: &str,
+    permitted_storages: &[String],
+) -> Result<Vec<Lot>, sqlx::Error> {
+    sqlx::query_as!(
+        Lot,
+        r#"
+            SELECT
+                lot.id::TEXT AS "id!",
+                item.name AS item,
+                location.storage AS "storage!",
+                location.container AS "container!",
+                lot.amount,
+                item.unit,
+                lot.best_before,
+                lot.arrived
+            FROM lot
+            JOIN item ON item.id = lot.item_id
+            JOIN location ON location.id = lot.container_id
+            WHERE
+                item.name = $1 AND
+                (location.protected <> true OR LOWER(location.storage) IN (SELECT UNNEST($2::TEXT[])))
+            ORDER BY lot.best_before NULLS LAST, lot.arrived
+        "#,
+        item,
+        permitted_storages
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// The lots that are best before at most days from now, including those that already expired
+pub async fn get_expiring(
+    db: &Pool<Postgres>,
+    days: i32,
+    permitted_storages: &[String],
+) -> Result<Vec<Lot>, sqlx::Error> {
+    sqlx::query_as!(
+        Lot,
+        r#"
+            SELECT
+                lot.id::TEXT AS "id!",
+                item.name AS item,
+                location.storage AS "storage!",
+                location.container AS "container!",
+                lot.amount,
+                item.unit,
+                lot.best_before,
+                lot.arrived
+            FROM lot
+            JOIN item ON item.id = lot.item_id
+            JOIN location ON location.id = lot.container_id
+            WHERE
+                lot.best_before <= CURRENT_DATE + $1::INTEGER AND
+                (location.protected <> true OR LOWER(location.storage) IN (SELECT UNNEST($2::TEXT[])))
+            ORDER BY lot.best_before, item.name, location.storage, location.container
+        "#,
+        days,
+        permitted_storages
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// Adds a lot to an item that is already stored, the stored amount is not changed
+pub async fn add_in_transaction(
+    db: &mut Transaction<'static, Postgres>,
+    item: &str,
+    storage: &str,
+    container: &str,
+    amount: f32,
+    best_before: Option<NaiveDate>,
+) -> Result<Uuid, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+            INSERT INTO lot (item_id, container_id, amount, best_before)
+            VALUES (item_ref($1), container_ref($2, $3), $4, $5)
+            RETURNING id
+        "#,
+        item,
+        storage,
+        container,
+        amount,
+        best_before
+    )
+    .fetch_one(&mut **db)
+    .await
+}
+
+/// Puts a new lot in storage, the stored amount grows by the amount of the lot
+pub async fn receive(
+    db: &Pool<Postgres>,
+    id: &str,
+    item: &str,
+    storage: &str,
+    container: &str,
+    amount: f32,
+    best_before: Option<NaiveDate>,
+) -> Result<Uuid, sqlx::Error> {
+    let mut db = db.begin().await?;
+
+    let new_amount = sqlx::query_scalar!(
+        r#"
+            UPDATE stored_item
+            SET amount = amount + $4
+            WHERE
+                item_id = item_ref($1) AND
+                container_id = container_ref($2, $3)
+            RETURNING amount
+        "#,
+        item,
+        storage,
+        container,
+        amount
+    )
+    .fetch_one(&mut *db)
+    .await?;
+
+    log_amount(&mut db, id, item, storage, container, new_amount).await?;
+    let lot = add_in_transaction(&mut db, item, storage, container, amount, best_before).await?;
+
+    db.commit().await?;
+    Ok(lot)
+}
+
+/// The lots of a stored item in the order they are used, locked until the transaction ends
+async fn fefo(
+    db: &mut Transaction<'static, Postgres>,
+    item: &str,
+    storage: &str,
+    container: &str,
+) -> Result<Vec<LotAmount>, sqlx::Error> {
+    sqlx::query_as!(
+        LotAmount,
+        r#"
+            SELECT id, amount
+            FROM lot
+            WHERE
+                item_id = item_ref($1) AND
+                container_id = container_ref($2, $3)
+            ORDER BY best_before NULLS LAST, arrived
+            FOR UPDATE
+        "#,
+        item,
+        storage,
+        container
+    )
+    .fetch_all(&mut **db)
+    .await
+}
+
+/// Takes an amount that left storage out of the lots, first expiry first out. Lots without a
+/// best-before date go last, and what is more than is in the lots came from the part in no lot.
+pub async fn consume(
+    db: &mut Transaction<'static, Postgres>,
+    item: &str,
+    storage: &str,
+    container: &str,
+    amount: f32,
+) -> Result<(), sqlx::Error> {
+    let mut left = amount;
+
+    for lot in fefo(db, item, storage, container).await? {
+        if left <= 0.0 {
+            break;
+        }
+
+        if lot.amount <= left {
+            sqlx::query!("DELETE FROM lot WHERE id = $1", lot.id)
+                .execute(&mut **db)
+                .await?;
+        } else {
+            sqlx::query!(
+                "UPDATE lot SET amount = amount - $2 WHERE id = $1",
+                lot.id,
+                left
+            )
+            .execute(&mut **db)
+            .await?;
+        }
+        left -= lot.amount;
+    }
+
+    Ok(())
+}
+
+/// Moves an amount of the lots of a stored item to where the item is moved, first expiry first.
+/// A lot that is only partly moved is split in two with the same dates.
+pub async fn relocate(
+    db: &mut Transaction<'static, Postgres>,
+    item: &str,
+    from_storage: &str,
+    from_container: &str,
+    to_storage: &str,
+    to_container: &str,
+    amount: f32,
+) -> Result<(), sqlx::Error> {
+    let mut left = amount;
+
+    for lot in fefo(db, item, from_storage, from_container).await? {
+        if left <= 0.0 {
+            break;
+        }
+
+        if lot.amount <= left {
+            sqlx::query!(
+                "UPDATE lot SET container_id = container_ref($2, $3) WHERE id = $1",
+                lot.id,
+                to_storage,
+                to_container
+            )
+            .execute(&mut **db)
+            .await?;
+        } else {
+            sqlx::query!(
+                r#"
+                    INSERT INTO lot (item_id, container_id, amount, best_before, arrived)
+                    SELECT item_id, container_ref($2, $3), $4, best_before, arrived
+                    FROM lot
+                    WHERE id = $1
+                "#,
+                lot.id,
+                to_storage,
+                to_container,
+                left
+            )
+            .execute(&mut **db)
+            .await?;
+            sqlx::query!(
+                "UPDATE lot SET amount = amount - $2 WHERE id = $1",
+                lot.id,
+                left
+            )
+            .execute(&mut **db)
+            .await?;
+        }
+        left -= lot.amount;
+    }
+
+    Ok(())
+}
+
+/// Takes items of a lot out of storage without them being used, the whole lot if no amount is
+/// given. The reason is kept together with who wrote them off.
+pub async fn write_off(
+    db: &Pool<Postgres>,
+    id: &str,
+    lot: Uuid,
+    amount: Option<f32>,
+    reason: &str,
+) -> Result<(), Error> {
+    let mut db = db.begin().await?;
+
+    let current = sqlx::query!(
+        r#"
+            SELECT
+                item.name AS item,
+                location.storage AS "storage!",
+                location.container AS "container!",
+                lot.amount,
+                lot.best_before
+            FROM lot
+            JOIN item ON item.id = lot.item_id
+            JOIN location ON location.id = lot.container_id
+            WHERE lot.id = $1
+            FOR UPDATE OF lot
+        "#,
+        lot
+    )
+    .fetch_one(&mut *db)
+    .await?;
+
+    let amount = amount.unwrap_or(current.amount);
+    if amount > current.amount {
+        return Err(Error::Validation(vec![FieldError::new(
+            "amount",
+            "more than is in the lot",
+        )]));
+    }
+
+    if amount < current.amount {
+        sqlx::query!(
+            "UPDATE lot SET amount = amount - $2 WHERE id = $1",
+            lot,
+            amount
+        )
+        .execute(&mut *db)
+        .await?;
+    } else {
+        sqlx::query!("DELETE FROM lot WHERE id = $1", lot)
+            .execute(&mut *db)
+            .await?;
+    }
+
+    let new_amount = sqlx::query_scalar!(
+        r#"
+            UPDATE stored_item
+            SET amount = GREATEST(amount - $4, 0)
+            WHERE
+                item_id = item_ref($1) AND
+                container_id = container_ref($2, $3)
+            RETURNING amount
+        "#,
+        current.item,
+        current.storage,
+        current.container,
+        amount
+    )
+    .fetch_one(&mut *db)
+    .await?;
+
+    log_amount(
+        &mut db,
+        id,
+        &current.item,
+        &current.storage,
+        &current.container,
+        new_amount,
+    )
+    .await?;
+
+    sqlx::query!(
+        r#"
+            INSERT INTO write_off (item_id, container_id, amount, best_before, reason, user_)
+            VALUES (item_ref($1), container_ref($2, $3), $4, $5, $6, $7)
+        "#,
+        current.item,
+        current.storage,
+        current.container,
+        amount,
+        current.best_before,
+        reason,
+        id
+    )
+    .execute(&mut *db)
+    .await?;
+
+    db.commit().await?;
+    Ok(())
+}
+
+/// Logs the new amount of a stored item
+async fn log_amount(
+    db: &mut Transaction<'static, Postgres>,
+    id: &str,
+    item: &str,
+    storage: &str,
+    container: &str,
+    amount: f32,
+) -> Result<PgQueryResult, sqlx::Error> {
+    // clock_timestamp() since log is keyed on (item, time) and the item may already have been
+    // logged in the transaction
+    sqlx::query!(
+        r#"
+            INSERT INTO log (
+                item_id,
+                container_id,
+                amount,
+                user_,
+                time
+            )
+            VALUES (item_ref($1), container_ref($2, $3), $4, $5, clock_timestamp())
+        "#,
+        item,
+        storage,
+        container,
+        amount,
+        id
+    )
+    .execute(&mut **db)
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::{types::chrono::Utc, Pool, Postgres};
+
+    use crate::db;
+
+    async fn amounts(db: &Pool<Postgres>, storage: &str) -> Vec<f32> {
+        sqlx::query_scalar!(
+            r#"
+                SELECT lot.amount
+                FROM lot
+                JOIN location ON location.id = lot.container_id
+                WHERE location.storage = $1
+                ORDER BY lot.best_before NULLS LAST, lot.arrived
+            "#,
+            storage
+        )
+        .fetch_all(db)
+        .await
+        .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn first_expiry_first_out(db: Pool<Postgres>) {
+        db::storage::create(&db, "meta", false, None).await.unwrap();
+        db::storage::create(&db, "örådet", false, None)
+            .await
+            .unwrap();
+        db::item::create(
+            &db,
+            "test",
+            "meta",
+            "",
+            "mjölk",
+            None,
+            None,
+            2.0,
+            Some("liter"),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let today = Utc::now().date_naive();
+        let late = super::receive(
+            &db,
+            "test",
+            "mjölk",
+            "meta",
+            "",
+            4.0,
+            today.checked_add_days(chrono::Days::new(10)),
+        )
+        .await
+        .unwrap();
+        super::receive(&db, "test", "mjölk", "meta", "", 3.0, Some(today))
+            .await
+            .unwrap();
+        super::receive(&db, "test", "mjölk", "meta", "", 1.0, None)
+            .await
+            .unwrap();
+
+        let expiring = super::get_expiring(&db, 3, &[]).await.unwrap();
+        assert_eq!(expiring.len(), 1);
+        assert_eq!(expiring[0].amount, 3.0);
+
+        // Taking stock from 10 to 6 uses the 3 expiring today and 1 of the later lot
+        let mut tx = db.begin().await.unwrap();
+        db::item::update_amount_in_transaction(&mut tx, "test", "mjölk", "meta", "", 6.0)
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+        assert_eq!(amounts(&db, "meta").await, [3.0, 1.0]);
+
+        db::item::create(
+            &db, "test", "örådet", "", "mjölk", None, None, 0.0, None, None,
+        )
+        .await
+        .unwrap();
+        let mut tx = db.begin().await.unwrap();
+        db::item::move_item(
+            &mut tx,
+            "mjölk",
+            Some(3.5),
+            "meta",
+            "",
+            "örådet",
+            "",
+            "test",
+        )
+        .await
+        .unwrap_or_else(|_| panic!("the item to be moved"));
+        tx.commit().await.unwrap();
+        assert_eq!(amounts(&db, "meta").await, [0.5]);
+        assert_eq!(amounts(&db, "örådet").await, [3.0, 0.5]);
+
+        super::write_off(&db, "test", late, None, "the milk went sour")
+            .await
+            .unwrap_or_else(|_| panic!("the lot to be written off"));
+        assert_eq!(amounts(&db, "örådet").await, [0.5]);
+        let stored = sqlx::query_scalar!(
+            r#"
+                SELECT amount
+                FROM stored_item
+                JOIN item ON item.id = stored_item.item_id
+                JOIN location ON location.id = stored_item.container_id
+                WHERE item.name = 'mjölk' AND location.storage = 'örådet'
+            "#
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        assert_eq!(stored, 0.5);
+        let reason = sqlx::query_scalar!("SELECT reason FROM write_off")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(reason, "the milk went sour");
+    }
+}
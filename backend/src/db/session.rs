@@ -0,0 +1,90 @@
+use sqlx::{types::Uuid, Pool, Postgres};
+
+/// Starts a new session for the user, returns its id
+pub async fn create(db: &Pool<Postgres>, user: &str) -> Result<Uuid, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+            INSERT INTO session (user_)
+            VALUES ($1)
+            RETURNING id
+        "#,
+        user
+    )
+    .fetch_one(db)
+    .await
+}
+
+/// If the session exists and has not been revoked
+pub async fn is_active(db: &Pool<Postgres>, id: Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+            SELECT EXISTS (
+                SELECT 1 FROM session
+                WHERE id = $1 AND time_revoked IS NULL
+            ) as "active!"
+        "#,
+        id
+    )
+    .fetch_one(db)
+    .await
+}
+
+pub async fn revoke(db: &Pool<Postgres>, id: Uuid, revoked_by: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+            UPDATE session
+            SET time_revoked = CURRENT_TIMESTAMP, revoked_by = $2
+            WHERE id = $1 AND time_revoked IS NULL
+        "#,
+        id,
+        revoked_by
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Revokes every active session of the user, returns how many there were
+pub async fn revoke_all(
+    db: &Pool<Postgres>,
+    user: &str,
+    revoked_by: &str,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+            UPDATE session
+            SET time_revoked = CURRENT_TIMESTAMP, revoked_by = $2
+            WHERE user_ = $1 AND time_revoked IS NULL
+        "#,
+        user,
+        revoked_by
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::{types::Uuid, Pool, Postgres};
+
+    #[sqlx::test]
+    async fn revoke(db: Pool<Postgres>) {
+        let first = super::create(&db, "mister").await.unwrap();
+        let second = super::create(&db, "mister").await.unwrap();
+        let other = super::create(&db, "turetek").await.unwrap();
+
+        assert!(super::is_active(&db, first).await.unwrap());
+        assert!(!super::is_active(&db, Uuid::nil()).await.unwrap());
+
+        super::revoke(&db, first, "mister").await.unwrap();
+        assert!(!super::is_active(&db, first).await.unwrap());
+        assert!(super::is_active(&db, second).await.unwrap());
+
+        assert_eq!(super::revoke_all(&db, "mister", "admin").await.unwrap(), 1);
+        assert!(!super::is_active(&db, second).await.unwrap());
+        assert!(super::is_active(&db, other).await.unwrap());
+    }
+}
@@ -2,130 +2,906 @@ use serde::{Deserialize, Serialize};
 use sqlx::{
     postgres::PgQueryResult,
     types::{
-        chrono::{DateTime, Utc},
+        chrono::{DateTime, NaiveDate, Utc},
         Uuid,
     },
-    Pool, Postgres,
+    Pool, Postgres, Transaction,
 };
 use utoipa::ToSchema;
 
-// struct Shipment {
-//     id: Uuid,
-//     time_created: DateTime<Utc>,
-//     time_arive: DateTime<Utc>,
-// }
-//
-// struct ShipmentItem {
-//     shipment: Uuid,
-//     item: String,
-//     amount: f32,
-// }
+use crate::{
+    db,
+    error::{Error, FieldError},
+};
+
+/// The state of a shipment in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "shipment_state", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ShipmentState {
+    Ordered,
+    Shipped,
+    PartiallyReceived,
+    Received,
+    Cancelled,
+}
+
+impl ShipmentState {
+    /// If no more changes can be made to a shipment in this state
+    pub fn is_closed(&self) -> bool {
+        matches!(self, ShipmentState::Received | ShipmentState::Cancelled)
+    }
+}
 
 #[derive(Debug)]
 pub struct Shipment {
     pub id: Uuid,
+    pub state: ShipmentState,
+    pub supplier: Option<String>,
     pub time_created: DateTime<Utc>,
     pub time_arive: DateTime<Utc>,
+    pub time_received: Option<DateTime<Utc>>,
     pub items: Vec<ShipmentItem>,
+    pub history: Vec<ShipmentEvent>,
 }
 
-#[derive(Debug, sqlx::Type, Serialize, Deserialize, ToSchema)]
+/// An item that is part of a shipment
+#[derive(Debug, PartialEq, sqlx::Type, Serialize, Deserialize, ToSchema)]
 #[sqlx(type_name = "shipment_listing")]
 pub struct ShipmentItem {
-    item: String,
-    amount: f32,
+    /// The items name, an id when ordering is also accepted
+    pub item: String,
+    /// The number of items ordered
+    pub amount: f32,
+    /// The number of items that have been received so far
+    #[serde(skip_deserializing)]
+    pub received: f32,
+    /// The storage the items are meant to be put in, by name or id when ordering
+    pub storage: Option<String>,
+    /// The container the items are meant to be put in, by name or id when ordering
+    pub container: Option<String>,
+    /// The unit the amounts are counted in, when ordering one of the items units the amount is
+    /// given in which defaults to its own
+    pub unit: Option<String>,
+}
+
+/// A state transition of a shipment
+#[derive(Debug, PartialEq, sqlx::Type, Serialize, ToSchema)]
+#[sqlx(type_name = "shipment_event_listing")]
+pub struct ShipmentEvent {
+    /// The state the shipment was put in
+    pub state: ShipmentState,
+    /// When the transition happened
+    pub time: DateTime<Utc>,
+    /// The user that made the transition
+    pub user: String,
+}
+
+/// An amount of a shipment line that has arrived and where it was put
+#[derive(Debug)]
+pub struct ReceivedItem {
+    /// The items name
+    pub item: String,
+    /// The number of items that arrived
+    pub amount: f32,
+    /// The storage the items are put in
+    pub storage: String,
+    /// The container the items are put in
+    pub container: String,
+    /// The date the items are best before, they are put in storage as a lot if it is given
+    pub best_before: Option<NaiveDate>,
 }
 
-pub async fn get_all_orders(db: &Pool<Postgres>) -> Result<Vec<Shipment>, sqlx::Error> {
-    Ok(sqlx::query_as!(
+/// Gets the shipments, lines going to protected storages the user can not read are left out and
+/// so are shipments where every line is
+pub async fn get_all_orders(
+    db: &Pool<Postgres>,
+    state: Option<ShipmentState>,
+    access: &[String],
+) -> Result<Vec<Shipment>, sqlx::Error> {
+    sqlx::query_as!(
         Shipment,
         r#"
             SELECT
-                id,
+                shipment.id,
+                state as "state: ShipmentState",
+                supplier.name as "supplier?",
                 time_created,
                 time_arive,
+                time_received,
                 ARRAY(
                     SELECT (
-                        shipment_item.item,
-                        shipment_item.amount
+                        item.name,
+                        shipment_item.amount,
+                        shipment_item.received,
+                        location.storage,
+                        location.container,
+                        item.unit
                     )::shipment_listing
                     FROM shipment_item
-                    WHERE shipment.id = shipment_item.shipment
-                ) as "items!: Vec<ShipmentItem>"
+                    JOIN item ON item.id = shipment_item.item_id
+                    LEFT JOIN location ON location.id = shipment_item.container_id
+                    WHERE
+                        shipment.id = shipment_item.shipment AND
+                        (
+                            location.protected IS NOT TRUE OR
+                            LOWER(location.storage) IN (SELECT UNNEST($2::TEXT[]))
+                        )
+                ) as "items!: Vec<ShipmentItem>",
+                ARRAY(
+                    SELECT (
+                        shipment_event.state,
+                        shipment_event.time,
+                        shipment_event.user_
+                    )::shipment_event_listing
+                    FROM shipment_event
+                    WHERE shipment.id = shipment_event.shipment
+                    ORDER BY shipment_event.time
+                ) as "history!: Vec<ShipmentEvent>"
             FROM shipment
-        "#
+            LEFT JOIN supplier ON supplier.id = shipment.supplier_id
+            WHERE
+                ($1::shipment_state IS NULL OR state = $1) AND
+                (
+                    NOT EXISTS (
+                        SELECT 1
+                        FROM shipment_item
+                        WHERE shipment.id = shipment_item.shipment
+                    ) OR
+                    EXISTS (
+                        SELECT 1
+                        FROM shipment_item
+                        LEFT JOIN location ON location.id = shipment_item.container_id
+                        WHERE
+                            shipment.id = shipment_item.shipment AND
+                            (
+                                location.protected IS NOT TRUE OR
+                                LOWER(location.storage) IN (SELECT UNNEST($2::TEXT[]))
+                            )
+                    )
+                )
+            ORDER BY time_arive
+        "#,
+        state as Option<ShipmentState>,
+        access
     )
     .fetch_all(db)
-    .await?)
+    .await
 }
 
-pub async fn get_order_by_id(db: &Pool<Postgres>, id: Uuid) -> Result<Shipment, sqlx::Error> {
-    Ok(sqlx::query_as!(
+pub async fn get_order_by_id(
+    db: &Pool<Postgres>,
+    id: Uuid,
+    access: &[String],
+) -> Result<Shipment, sqlx::Error> {
+    sqlx::query_as!(
         Shipment,
         r#"
             SELECT
-                id,
+                shipment.id,
+                state as "state: ShipmentState",
+                supplier.name as "supplier?",
                 time_created,
                 time_arive,
+                time_received,
                 ARRAY(
                     SELECT (
-                        shipment_item.item,
-                        shipment_item.amount
+                        item.name,
+                        shipment_item.amount,
+                        shipment_item.received,
+                        location.storage,
+                        location.container,
+                        item.unit
                     )::shipment_listing
                     FROM shipment_item
-                    WHERE shipment.id = shipment_item.shipment
-                ) as "items!: Vec<ShipmentItem>"
+                    JOIN item ON item.id = shipment_item.item_id
+                    LEFT JOIN location ON location.id = shipment_item.container_id
+                    WHERE
+                        shipment.id = shipment_item.shipment AND
+                        (
+                            location.protected IS NOT TRUE OR
+                            LOWER(location.storage) IN (SELECT UNNEST($2::TEXT[]))
+                        )
+                ) as "items!: Vec<ShipmentItem>",
+                ARRAY(
+                    SELECT (
+                        shipment_event.state,
+                        shipment_event.time,
+                        shipment_event.user_
+                    )::shipment_event_listing
+                    FROM shipment_event
+                    WHERE shipment.id = shipment_event.shipment
+                    ORDER BY shipment_event.time
+                ) as "history!: Vec<ShipmentEvent>"
             FROM shipment
-            WHERE id = $1
+            LEFT JOIN supplier ON supplier.id = shipment.supplier_id
+            WHERE
+                shipment.id = $1 AND
+                (
+                    NOT EXISTS (
+                        SELECT 1
+                        FROM shipment_item
+                        WHERE shipment.id = shipment_item.shipment
+                    ) OR
+                    EXISTS (
+                        SELECT 1
+                        FROM shipment_item
+                        LEFT JOIN location ON location.id = shipment_item.container_id
+                        WHERE
+                            shipment.id = shipment_item.shipment AND
+                            (
+                                location.protected IS NOT TRUE OR
+                                LOWER(location.storage) IN (SELECT UNNEST($2::TEXT[]))
+                            )
+                    )
+                )
         "#,
-        id
+        id,
+        access
     )
     .fetch_one(db)
-    .await?)
+    .await
 }
 
 pub async fn create_order(
     db: &Pool<Postgres>,
+    id: &str,
     arival: DateTime<Utc>,
+    supplier: Option<&str>,
     items: Vec<ShipmentItem>,
-) -> Result<(), sqlx::Error> {
-    let id = sqlx::query!(
+) -> Result<Uuid, sqlx::Error> {
+    let mut db = db.begin().await?;
+
+    let shipment = sqlx::query!(
         r#"
-            INSERT INTO shipment (id, time_created, time_arive)
-            VALUES (gen_random_uuid(), CURRENT_TIMESTAMP, $1)
+            INSERT INTO shipment (id, time_created, time_arive, supplier_id)
+            VALUES (gen_random_uuid(), CURRENT_TIMESTAMP, $1, supplier_ref($2))
             RETURNING id
         "#,
-        arival
+        arival,
+        supplier
     )
-    .fetch_one(db)
+    .fetch_one(&mut *db)
     .await?
     .id;
 
-    for ShipmentItem { item, amount } in items {
+    for ShipmentItem {
+        item,
+        amount,
+        storage,
+        container,
+        ..
+    } in items
+    {
         sqlx::query!(
             r#"
-                INSERT INTO shipment_item (shipment, item, amount)
-                VALUES ($1, $2, $3)
+                INSERT INTO shipment_item (shipment, item_id, amount, container_id)
+                VALUES ($1, item_ref($2), $3, container_ref($4, $5))
             "#,
-            id,
+            shipment,
             item,
-            amount
+            amount,
+            storage,
+            container
         )
-        .execute(db)
+        .execute(&mut *db)
         .await?;
     }
 
-    Ok(())
+    add_event(&mut db, id, shipment, ShipmentState::Ordered).await?;
+
+    db.commit().await?;
+
+    Ok(shipment)
 }
 
-pub async fn delete(db: &Pool<Postgres>, id: Uuid) -> Result<PgQueryResult, sqlx::Error> {
+async fn add_event(
+    db: &mut Transaction<'static, Postgres>,
+    id: &str,
+    shipment: Uuid,
+    state: ShipmentState,
+) -> Result<PgQueryResult, sqlx::Error> {
     sqlx::query!(
         r#"
-            DELETE FROM shipment
-            WHERE id = $1
+            INSERT INTO shipment_event (shipment, state, user_)
+            VALUES ($1, $2, $3)
         "#,
+        shipment,
+        state as ShipmentState,
         id
     )
-    .execute(db)
+    .execute(&mut **db)
     .await
 }
+
+/// Locks the shipment for the rest of the transaction and returns its state
+async fn lock(
+    db: &mut Transaction<'static, Postgres>,
+    shipment: Uuid,
+) -> Result<ShipmentState, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+            SELECT state as "state: ShipmentState"
+            FROM shipment
+            WHERE id = $1
+            FOR UPDATE
+        "#,
+        shipment
+    )
+    .fetch_one(&mut **db)
+    .await
+}
+
+/// Marks a shipment as shipped or cancelled, the received states are only reached through
+/// [receive]
+pub async fn set_state(
+    db: &Pool<Postgres>,
+    id: &str,
+    shipment: Uuid,
+    state: ShipmentState,
+) -> Result<(), Error> {
+    let mut db = db.begin().await?;
+
+    let current = lock(&mut db, shipment).await?;
+
+    let allowed = match state {
+        ShipmentState::Shipped => current == ShipmentState::Ordered,
+        ShipmentState::Cancelled => !current.is_closed(),
+        _ => false,
+    };
+
+    if !allowed {
+        return Err(Error::Conflict(format!(
+            "a {current:?} shipment can't be {state:?}"
+        )));
+    }
+
+    sqlx::query!(
+        r#"
+            UPDATE shipment
+            SET state = $2
+            WHERE id = $1
+        "#,
+        shipment,
+        state as ShipmentState
+    )
+    .execute(&mut *db)
+    .await?;
+
+    add_event(&mut db, id, shipment, state).await?;
+
+    Ok(db.commit().await?)
+}
+
+/// Books the received items into storage and marks the shipment as (partially) received
+pub async fn receive(
+    db: &Pool<Postgres>,
+    id: &str,
+    shipment: Uuid,
+    items: Vec<ReceivedItem>,
+) -> Result<(), Error> {
+    let mut db = db.begin().await?;
+
+    if lock(&mut db, shipment).await?.is_closed() {
+        return Err(Error::Conflict(String::from("the shipment is closed")));
+    }
+
+    for ReceivedItem {
+        item,
+        amount,
+        storage,
+        container,
+        best_before,
+    } in items
+    {
+        let line = sqlx::query!(
+            r#"
+                SELECT amount, received
+                FROM shipment_item
+                WHERE shipment = $1 AND item_id = item_ref($2)
+                FOR UPDATE
+            "#,
+            shipment,
+            item
+        )
+        .fetch_one(&mut *db)
+        .await?;
+
+        if amount <= 0.0 || line.received + amount > line.amount {
+            return Err(Error::Validation(vec![FieldError::new(
+                "amount",
+                "must be positive and at most what is left to receive",
+            )]));
+        }
+
+        sqlx::query!(
+            r#"
+                UPDATE shipment_item
+                SET received = received + $3
+                WHERE shipment = $1 AND item_id = item_ref($2)
+            "#,
+            shipment,
+            item,
+            amount
+        )
+        .execute(&mut *db)
+        .await?;
+
+        let new_amount = sqlx::query_scalar!(
+            r#"
+                INSERT INTO stored_item (container_id, item_id, amount)
+                VALUES (container_ref($1, $2), item_ref($3), $4)
+                ON CONFLICT (item_id, container_id)
+                    DO UPDATE SET amount = stored_item.amount + EXCLUDED.amount
+                RETURNING amount
+            "#,
+            storage,
+            container,
+            item,
+            amount
+        )
+        .fetch_one(&mut *db)
+        .await?;
+
+        // clock_timestamp() since the same item can be received into several locations in
+        // one transaction and log is keyed on (item, time)
+        sqlx::query!(
+            r#"
+                INSERT INTO log (
+                    item_id,
+                    container_id,
+                    amount,
+                    user_,
+                    time
+                )
+                VALUES (item_ref($1), container_ref($2, $3), $4, $5, clock_timestamp())
+            "#,
+            item,
+            storage,
+            container,
+            new_amount,
+            id
+        )
+        .execute(&mut *db)
+        .await?;
+
+        if best_before.is_some() {
+            db::lot::add_in_transaction(&mut db, &item, &storage, &container, amount, best_before)
+                .await?;
+        }
+    }
+
+    let outstanding = sqlx::query_scalar!(
+        r#"
+            SELECT EXISTS (
+                SELECT 1
+                FROM shipment_item
+                WHERE shipment = $1 AND received < amount
+            ) as "outstanding!"
+        "#,
+        shipment
+    )
+    .fetch_one(&mut *db)
+    .await?;
+
+    let state = if outstanding {
+        ShipmentState::PartiallyReceived
+    } else {
+        ShipmentState::Received
+    };
+
+    sqlx::query!(
+        r#"
+            UPDATE shipment
+            SET
+                state = $2::shipment_state,
+                time_received = CASE WHEN $2 = 'received'::shipment_state THEN CURRENT_TIMESTAMP END
+            WHERE id = $1
+        "#,
+        shipment,
+        state as ShipmentState
+    )
+    .execute(&mut *db)
+    .await?;
+
+    add_event(&mut db, id, shipment, state).await?;
+
+    Ok(db.commit().await?)
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::{types::chrono::Utc, Pool, Postgres};
+
+    use crate::db::{
+        self,
+        item::{BasicItem, BasicItemStorage},
+        shipment::{ReceivedItem, ShipmentItem, ShipmentState},
+        OrderState,
+    };
+
+    async fn setup(db: &Pool<Postgres>) -> sqlx::types::Uuid {
+        db::storage::create(db, "meta", false, None).await.unwrap();
+
+        db::item::create(
+            db,
+            "test",
+            "meta",
+            "",
+            "tejp",
+            Some(5.0),
+            Some(10.0),
+            2.0,
+            Some("st"),
+            None,
+        )
+        .await
+        .unwrap();
+
+        super::create_order(
+            db,
+            "test",
+            Utc::now(),
+            None,
+            vec![ShipmentItem {
+                item: String::from("tejp"),
+                amount: 8.0,
+                received: 0.0,
+                storage: None,
+                container: None,
+                unit: None,
+            }],
+        )
+        .await
+        .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn receive(db: Pool<Postgres>) {
+        let id = setup(&db).await;
+
+        super::receive(
+            &db,
+            "test",
+            id,
+            vec![ReceivedItem {
+                item: String::from("tejp"),
+                amount: 8.0,
+                storage: String::from("meta"),
+                container: String::new(),
+                best_before: None,
+            }],
+        )
+        .await
+        .unwrap();
+
+        let shipment = super::get_order_by_id(&db, id, &[]).await.unwrap();
+
+        assert_eq!(shipment.state, ShipmentState::Received);
+        assert!(shipment.time_received.is_some());
+        assert_eq!(
+            shipment.items,
+            vec![ShipmentItem {
+                item: String::from("tejp"),
+                amount: 8.0,
+                received: 8.0,
+                storage: None,
+                container: None,
+                unit: Some(String::from("st")),
+            }]
+        );
+
+        let item = db::item::get_all_filtered_basic(
+            &db,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &[String::from("meta")],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            item,
+            vec![BasicItem {
+                id: db::id::item_id(&db, "tejp").await.unwrap(),
+                name: String::from("tejp"),
+                amount: 10.0,
+                unit: String::from("st"),
+                category: None,
+                tags: vec![],
+                storage: vec![BasicItemStorage {
+                    storage: String::from("meta"),
+                    container: String::new(),
+                    state: OrderState::Good
+                }]
+            }]
+        )
+    }
+
+    #[sqlx::test]
+    async fn receive_partial(db: Pool<Postgres>) {
+        let id = setup(&db).await;
+        db::container::create(&db, "tejplåda", "meta", None)
+            .await
+            .unwrap();
+
+        super::receive(
+            &db,
+            "test",
+            id,
+            vec![
+                ReceivedItem {
+                    item: String::from("tejp"),
+                    amount: 2.0,
+                    storage: String::from("meta"),
+                    container: String::new(),
+                    best_before: None,
+                },
+                ReceivedItem {
+                    item: String::from("tejp"),
+                    amount: 3.0,
+                    storage: String::from("meta"),
+                    container: String::from("tejplåda"),
+                    best_before: None,
+                },
+            ],
+        )
+        .await
+        .unwrap();
+
+        let shipment = super::get_order_by_id(&db, id, &[]).await.unwrap();
+
+        assert_eq!(shipment.state, ShipmentState::PartiallyReceived);
+        assert!(shipment.time_received.is_none());
+        assert_eq!(
+            shipment.items,
+            vec![ShipmentItem {
+                item: String::from("tejp"),
+                amount: 8.0,
+                received: 5.0,
+                storage: None,
+                container: None,
+                unit: Some(String::from("st")),
+            }]
+        );
+
+        let logs = sqlx::query_scalar!(
+            r#"
+                SELECT user_
+                FROM log
+                JOIN item ON item.id = log.item_id
+                WHERE item.name = 'tejp'
+            "#
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap();
+
+        assert_eq!(logs.len(), 3);
+
+        let item = db::item::get_all_filtered_basic(
+            &db,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &[String::from("meta")],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(item[0].amount, 7.0);
+        assert!(item[0]
+            .storage
+            .iter()
+            .all(|storage| storage.state == OrderState::Incoming));
+    }
+
+    #[sqlx::test]
+    async fn receive_more_than_ordered(db: Pool<Postgres>) {
+        let id = setup(&db).await;
+
+        assert!(super::receive(
+            &db,
+            "test",
+            id,
+            vec![ReceivedItem {
+                item: String::from("tejp"),
+                amount: 9.0,
+                storage: String::from("meta"),
+                container: String::new(),
+                best_before: None,
+            }],
+        )
+        .await
+        .is_err());
+
+        let shipment = super::get_order_by_id(&db, id, &[]).await.unwrap();
+
+        assert_eq!(shipment.items[0].received, 0.0);
+    }
+
+    #[sqlx::test]
+    async fn lifecycle(db: Pool<Postgres>) {
+        let id = setup(&db).await;
+
+        super::set_state(&db, "test", id, ShipmentState::Shipped)
+            .await
+            .unwrap();
+
+        assert!(super::set_state(&db, "test", id, ShipmentState::Received)
+            .await
+            .is_err());
+
+        super::set_state(&db, "other", id, ShipmentState::Cancelled)
+            .await
+            .unwrap();
+
+        let shipment = super::get_order_by_id(&db, id, &[]).await.unwrap();
+
+        assert_eq!(shipment.state, ShipmentState::Cancelled);
+        assert_eq!(
+            shipment
+                .history
+                .iter()
+                .map(|event| (event.state, event.user.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (ShipmentState::Ordered, "test"),
+                (ShipmentState::Shipped, "test"),
+                (ShipmentState::Cancelled, "other"),
+            ]
+        );
+
+        assert!(super::receive(
+            &db,
+            "test",
+            id,
+            vec![ReceivedItem {
+                item: String::from("tejp"),
+                amount: 1.0,
+                storage: String::from("meta"),
+                container: String::new(),
+                best_before: None,
+            }],
+        )
+        .await
+        .is_err());
+
+        let item = db::item::get_all_filtered_basic(
+            &db,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &[String::from("meta")],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(item[0].storage[0].state, OrderState::Critical);
+    }
+
+    #[sqlx::test]
+    async fn filter_by_state(db: Pool<Postgres>) {
+        let id = setup(&db).await;
+
+        super::set_state(&db, "test", id, ShipmentState::Cancelled)
+            .await
+            .unwrap();
+
+        assert!(
+            super::get_all_orders(&db, Some(ShipmentState::Ordered), &[])
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        assert_eq!(
+            super::get_all_orders(&db, Some(ShipmentState::Cancelled), &[])
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        assert_eq!(
+            super::get_all_orders(&db, None, &[]).await.unwrap().len(),
+            1
+        );
+    }
+
+    #[sqlx::test]
+    async fn incoming_only_at_destination(db: Pool<Postgres>) {
+        db::storage::create(&db, "meta", false, None).await.unwrap();
+        db::storage::create(&db, "örådet", false, None)
+            .await
+            .unwrap();
+        db::supplier::create(&db, "ICA", None, None, None, None, "mister@metadorerna.se")
+            .await
+            .unwrap();
+
+        for storage in ["meta", "örådet"] {
+            db::item::create(
+                &db,
+                "test",
+                storage,
+                "",
+                "tejp",
+                Some(5.0),
+                Some(10.0),
+                2.0,
+                Some("st"),
+                None,
+            )
+            .await
+            .unwrap();
+        }
+
+        let id = super::create_order(
+            &db,
+            "test",
+            Utc::now(),
+            Some("ICA"),
+            vec![ShipmentItem {
+                item: String::from("tejp"),
+                amount: 8.0,
+                received: 0.0,
+                storage: Some(String::from("örådet")),
+                container: Some(String::new()),
+                unit: None,
+            }],
+        )
+        .await
+        .unwrap();
+
+        let shipment = super::get_order_by_id(&db, id, &[]).await.unwrap();
+
+        assert_eq!(shipment.supplier, Some(String::from("ICA")));
+
+        let item = db::item::get_all_filtered_basic(
+            &db,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &[String::from("meta")],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            item[0].storage,
+            vec![
+                BasicItemStorage {
+                    storage: String::from("meta"),
+                    container: String::new(),
+                    state: OrderState::Critical
+                },
+                BasicItemStorage {
+                    storage: String::from("örådet"),
+                    container: String::new(),
+                    state: OrderState::Incoming
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,586 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    types::{
+        chrono::{DateTime, Utc},
+        Uuid,
+    },
+    Pool, Postgres, Transaction,
+};
+use utoipa::ToSchema;
+
+use crate::{
+    db,
+    error::{Error, FieldError},
+};
+
+/// The state of a stocktake session
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "stocktake_state", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum StocktakeState {
+    Open,
+    Finalized,
+    Abandoned,
+}
+
+#[derive(Debug)]
+pub struct Stocktake {
+    pub id: Uuid,
+    pub storage: String,
+    pub state: StocktakeState,
+    pub time_created: DateTime<Utc>,
+    pub time_closed: Option<DateTime<Utc>>,
+    pub user: String,
+    pub closed_by: Option<String>,
+}
+
+/// A count of an item made by one person
+#[derive(Debug, PartialEq, sqlx::Type, Serialize, ToSchema)]
+#[sqlx(type_name = "stocktake_count_listing")]
+pub struct StocktakeCount {
+    /// The user that counted the item
+    pub user: String,
+    /// The number of items counted
+    pub amount: f32,
+    /// When the item was counted
+    pub time: DateTime<Utc>,
+}
+
+/// A line in the variance report of a stocktake
+#[derive(Debug, PartialEq, Serialize, ToSchema)]
+pub struct StocktakeLine {
+    /// The items name
+    pub item: String,
+    /// The container where the item is stored
+    pub container: String,
+    /// The unit that the amounts are measured in
+    pub unit: String,
+    /// The number of items the system thinks are in storage
+    pub amount: f32,
+    /// The latest count of the item, none if it has not been counted
+    pub counted: Option<f32>,
+    /// The counted amount minus the system amount
+    pub variance: Option<f32>,
+    /// Every count made of the item, oldest first
+    pub counts: Vec<StocktakeCount>,
+}
+
+/// A counted amount of an item in the storage being counted
+#[derive(Debug)]
+pub struct CountedItem {
+    /// The items name
+    pub item: String,
+    /// The container where the item is stored
+    pub container: String,
+    /// The number of items counted
+    pub amount: f32,
+}
+
+pub async fn get_all(
+    db: &Pool<Postgres>,
+    storage: Option<&str>,
+    access: &[String],
+) -> Result<Vec<Stocktake>, sqlx::Error> {
+    sqlx::query_as!(
+        Stocktake,
+        r#"
+            SELECT
+                stocktake.id,
+                storage.name AS storage,
+                stocktake.state as "state: StocktakeState",
+                stocktake.time_created,
+                stocktake.time_closed,
+                stocktake.user_ as "user",
+                stocktake.closed_by
+            FROM stocktake
+            JOIN storage ON storage.id = stocktake.storage_id
+            WHERE
+                ($1::TEXT IS NULL OR storage.name = $1) AND
+                (
+                    storage.protected <> true OR
+                    LOWER(storage.name) IN (SELECT UNNEST($2::TEXT[]))
+                )
+            ORDER BY stocktake.time_created DESC
+        "#,
+        storage,
+        access
+    )
+    .fetch_all(db)
+    .await
+}
+
+pub async fn get_by_id(db: &Pool<Postgres>, stocktake: Uuid) -> Result<Stocktake, sqlx::Error> {
+    sqlx::query_as!(
+        Stocktake,
+        r#"
+            SELECT
+                stocktake.id,
+                storage.name AS storage,
+                stocktake.state as "state: StocktakeState",
+                stocktake.time_created,
+                stocktake.time_closed,
+                stocktake.user_ as "user",
+                stocktake.closed_by
+            FROM stocktake
+            JOIN storage ON storage.id = stocktake.storage_id
+            WHERE stocktake.id = $1
+        "#,
+        stocktake
+    )
+    .fetch_one(db)
+    .await
+}
+
+/// Opens a new session for a storage, only one session per storage can be open at a time
+pub async fn open(db: &Pool<Postgres>, id: &str, storage: &str) -> Result<Uuid, Error> {
+    let mut db = db.begin().await?;
+
+    let already_open = sqlx::query_scalar!(
+        r#"
+            SELECT EXISTS (
+                SELECT 1
+                FROM stocktake
+                JOIN storage ON storage.id = stocktake.storage_id
+                WHERE storage.name = $1 AND stocktake.state = 'open'
+            ) as "already_open!"
+        "#,
+        storage
+    )
+    .fetch_one(&mut *db)
+    .await?;
+
+    if already_open {
+        return Err(Error::Conflict(String::from(
+            "the storage already has an open stocktake",
+        )));
+    }
+
+    let stocktake = sqlx::query_scalar!(
+        r#"
+            INSERT INTO stocktake (storage_id, user_)
+            VALUES (storage_ref($1), $2)
+            RETURNING id
+        "#,
+        storage,
+        id
+    )
+    .fetch_one(&mut *db)
+    .await?;
+
+    db.commit().await?;
+
+    Ok(stocktake)
+}
+
+/// Locks the session for the rest of the transaction and returns its storage if it is open
+async fn lock_open(
+    db: &mut Transaction<'static, Postgres>,
+    stocktake: Uuid,
+) -> Result<String, Error> {
+    let session = sqlx::query!(
+        r#"
+            SELECT storage.name AS storage, stocktake.state as "state: StocktakeState"
+            FROM stocktake
+            JOIN storage ON storage.id = stocktake.storage_id
+            WHERE stocktake.id = $1
+            FOR UPDATE OF stocktake
+        "#,
+        stocktake
+    )
+    .fetch_one(&mut **db)
+    .await?;
+
+    if session.state != StocktakeState::Open {
+        return Err(Error::Conflict(String::from("the stocktake is closed")));
+    }
+
+    Ok(session.storage)
+}
+
+/// Records the counts of one person, stock is not changed until the session is finalized
+pub async fn count(
+    db: &Pool<Postgres>,
+    id: &str,
+    stocktake: Uuid,
+    items: Vec<CountedItem>,
+) -> Result<(), Error> {
+    let mut db = db.begin().await?;
+
+    let storage = lock_open(&mut db, stocktake).await?;
+
+    for CountedItem {
+        item,
+        container,
+        amount,
+    } in items
+    {
+        if amount < 0.0 {
+            return Err(Error::Validation(vec![FieldError::new(
+                "amount",
+                "must not be negative",
+            )]));
+        }
+
+        let counted = sqlx::query!(
+            r#"
+                INSERT INTO stocktake_count (stocktake, item_id, container_id, amount, user_)
+                SELECT $1, stored_item.item_id, stored_item.container_id, $5, $6
+                FROM stored_item
+                JOIN item ON item.id = stored_item.item_id
+                JOIN location ON location.id = stored_item.container_id
+                WHERE item.name = $2 AND location.storage = $3 AND location.container = $4
+                ON CONFLICT (stocktake, item_id, container_id, user_)
+                    DO UPDATE SET amount = EXCLUDED.amount, time = EXCLUDED.time
+            "#,
+            stocktake,
+            item,
+            storage,
+            container,
+            amount,
+            id
+        )
+        .execute(&mut *db)
+        .await?;
+
+        // The item is not stored in the container
+        if counted.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+    }
+
+    Ok(db.commit().await?)
+}
+
+/// Compares the counts of a session against the amounts in storage, items that have not been
+/// counted yet are included without a count
+pub async fn get_report(
+    db: &Pool<Postgres>,
+    stocktake: Uuid,
+) -> Result<Vec<StocktakeLine>, sqlx::Error> {
+    sqlx::query_as!(
+        StocktakeLine,
+        r#"
+            SELECT
+                item.name AS item,
+                location.container AS "container!",
+                item.unit,
+                stored_item.amount,
+                latest.amount as "counted?",
+                latest.amount - stored_item.amount as "variance?",
+                ARRAY(
+                    SELECT (
+                        stocktake_count.user_,
+                        stocktake_count.amount,
+                        stocktake_count.time
+                    )::stocktake_count_listing
+                    FROM stocktake_count
+                    WHERE
+                        stocktake_count.stocktake = stocktake.id AND
+                        stocktake_count.item_id = stored_item.item_id AND
+                        stocktake_count.container_id = stored_item.container_id
+                    ORDER BY stocktake_count.time
+                ) as "counts!: Vec<StocktakeCount>"
+            FROM stocktake
+            JOIN location ON location.storage_id = stocktake.storage_id
+            JOIN stored_item ON stored_item.container_id = location.id
+            JOIN item ON item.id = stored_item.item_id
+            LEFT JOIN LATERAL (
+                SELECT stocktake_count.amount
+                FROM stocktake_count
+                WHERE
+                    stocktake_count.stocktake = stocktake.id AND
+                    stocktake_count.item_id = stored_item.item_id AND
+                    stocktake_count.container_id = stored_item.container_id
+                ORDER BY stocktake_count.time DESC
+                LIMIT 1
+            ) latest ON true
+            WHERE stocktake.id = $1
+            ORDER BY location.container, item.name
+        "#,
+        stocktake
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// Sets every counted item to its latest count and closes the session, uncounted items are left
+/// as they are
+pub async fn finalize(db: &Pool<Postgres>, id: &str, stocktake: Uuid) -> Result<(), Error> {
+    let mut db = db.begin().await?;
+
+    let storage = lock_open(&mut db, stocktake).await?;
+
+    let counts = sqlx::query!(
+        r#"
+            SELECT DISTINCT ON (item.name, location.container)
+                item.name AS item,
+                location.container AS "container!",
+                stocktake_count.amount
+            FROM stocktake_count
+            JOIN item ON item.id = stocktake_count.item_id
+            JOIN location ON location.id = stocktake_count.container_id
+            WHERE stocktake_count.stocktake = $1
+            ORDER BY item.name, location.container, stocktake_count.time DESC
+        "#,
+        stocktake
+    )
+    .fetch_all(&mut *db)
+    .await?;
+
+    for count in counts {
+        db::item::update_amount_in_transaction(
+            &mut db,
+            id,
+            &count.item,
+            &storage,
+            &count.container,
+            count.amount,
+        )
+        .await?;
+    }
+
+    close(&mut db, id, stocktake, StocktakeState::Finalized).await?;
+
+    Ok(db.commit().await?)
+}
+
+/// Closes the session without touching the stock
+pub async fn abandon(db: &Pool<Postgres>, id: &str, stocktake: Uuid) -> Result<(), Error> {
+    let mut db = db.begin().await?;
+
+    lock_open(&mut db, stocktake).await?;
+
+    close(&mut db, id, stocktake, StocktakeState::Abandoned).await?;
+
+    Ok(db.commit().await?)
+}
+
+async fn close(
+    db: &mut Transaction<'static, Postgres>,
+    id: &str,
+    stocktake: Uuid,
+    state: StocktakeState,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+            UPDATE stocktake
+            SET
+                state = $2,
+                time_closed = CURRENT_TIMESTAMP,
+                closed_by = $3
+            WHERE id = $1
+        "#,
+        stocktake,
+        state as StocktakeState,
+        id
+    )
+    .execute(&mut **db)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::{types::Uuid, Pool, Postgres};
+
+    use crate::db::{
+        self,
+        stocktake::{CountedItem, StocktakeState},
+    };
+
+    async fn setup(db: &Pool<Postgres>) -> Uuid {
+        db::storage::create(db, "meta", false, None).await.unwrap();
+
+        db::container::create(db, "tejplåda", "meta", None)
+            .await
+            .unwrap();
+
+        db::item::create(
+            db,
+            "test",
+            "meta",
+            "",
+            "tejp",
+            Some(5.0),
+            Some(10.0),
+            7.0,
+            Some("st"),
+            None,
+        )
+        .await
+        .unwrap();
+
+        db::item::create(
+            db,
+            "test",
+            "meta",
+            "tejplåda",
+            "tejp",
+            None,
+            None,
+            3.0,
+            Some("st"),
+            None,
+        )
+        .await
+        .unwrap();
+
+        db::item::create(
+            db,
+            "test",
+            "meta",
+            "",
+            "gaffatejp",
+            None,
+            None,
+            2.0,
+            Some("rullar"),
+            None,
+        )
+        .await
+        .unwrap();
+
+        super::open(db, "test", "meta").await.unwrap()
+    }
+
+    fn counted(item: &str, container: &str, amount: f32) -> CountedItem {
+        CountedItem {
+            item: String::from(item),
+            container: String::from(container),
+            amount,
+        }
+    }
+
+    async fn amount(db: &Pool<Postgres>, item: &str, container: &str) -> f32 {
+        sqlx::query_scalar!(
+            r#"
+                SELECT amount
+                FROM stored_item
+                WHERE item_id = item_ref($1) AND container_id = container_ref('meta', $2)
+            "#,
+            item,
+            container
+        )
+        .fetch_one(db)
+        .await
+        .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn one_open_per_storage(db: Pool<Postgres>) {
+        let stocktake = setup(&db).await;
+
+        assert!(super::open(&db, "test", "meta").await.is_err());
+
+        super::abandon(&db, "test", stocktake).await.unwrap();
+
+        super::open(&db, "test", "meta").await.unwrap();
+    }
+
+    #[sqlx::test]
+    async fn variance_report(db: Pool<Postgres>) {
+        let stocktake = setup(&db).await;
+
+        super::count(&db, "test", stocktake, vec![counted("tejp", "", 6.0)])
+            .await
+            .unwrap();
+        super::count(&db, "other", stocktake, vec![counted("tejp", "", 5.0)])
+            .await
+            .unwrap();
+        super::count(
+            &db,
+            "other",
+            stocktake,
+            vec![counted("tejp", "tejplåda", 3.0)],
+        )
+        .await
+        .unwrap();
+
+        let report = super::get_report(&db, stocktake).await.unwrap();
+
+        assert_eq!(report.len(), 3);
+
+        let tejp = report
+            .iter()
+            .find(|line| line.item == "tejp" && line.container.is_empty())
+            .unwrap();
+        assert_eq!(tejp.amount, 7.0);
+        assert_eq!(tejp.counted, Some(5.0));
+        assert_eq!(tejp.variance, Some(-2.0));
+        assert_eq!(
+            tejp.counts
+                .iter()
+                .map(|count| (count.user.as_str(), count.amount))
+                .collect::<Vec<_>>(),
+            vec![("test", 6.0), ("other", 5.0)]
+        );
+
+        let gaffatejp = report.iter().find(|line| line.item == "gaffatejp").unwrap();
+        assert_eq!(gaffatejp.counted, None);
+        assert_eq!(gaffatejp.variance, None);
+        assert!(gaffatejp.counts.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn count_unknown_item(db: Pool<Postgres>) {
+        let stocktake = setup(&db).await;
+
+        assert!(
+            super::count(&db, "test", stocktake, vec![counted("lim", "", 1.0)])
+                .await
+                .is_err()
+        );
+    }
+
+    #[sqlx::test]
+    async fn finalize(db: Pool<Postgres>) {
+        let stocktake = setup(&db).await;
+
+        super::count(
+            &db,
+            "test",
+            stocktake,
+            vec![counted("tejp", "", 4.0), counted("tejp", "tejplåda", 1.0)],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(amount(&db, "tejp", "").await, 7.0);
+
+        super::finalize(&db, "test", stocktake).await.unwrap();
+
+        assert_eq!(amount(&db, "tejp", "").await, 4.0);
+        assert_eq!(amount(&db, "tejp", "tejplåda").await, 1.0);
+        assert_eq!(amount(&db, "gaffatejp", "").await, 2.0);
+
+        let session = super::get_by_id(&db, stocktake).await.unwrap();
+        assert_eq!(session.state, StocktakeState::Finalized);
+        assert_eq!(session.closed_by.as_deref(), Some("test"));
+
+        assert!(super::finalize(&db, "test", stocktake).await.is_err());
+        assert!(
+            super::count(&db, "test", stocktake, vec![counted("tejp", "", 2.0)])
+                .await
+                .is_err()
+        );
+    }
+
+    #[sqlx::test]
+    async fn abandon(db: Pool<Postgres>) {
+        let stocktake = setup(&db).await;
+
+        super::count(&db, "test", stocktake, vec![counted("tejp", "", 1.0)])
+            .await
+            .unwrap();
+
+        super::abandon(&db, "test", stocktake).await.unwrap();
+
+        assert_eq!(amount(&db, "tejp", "").await, 7.0);
+        assert_eq!(
+            super::get_by_id(&db, stocktake).await.unwrap().state,
+            StocktakeState::Abandoned
+        );
+        assert!(super::finalize(&db, "test", stocktake).await.is_err());
+    }
+}
@@ -10,7 +10,9 @@ use crate::db::{self, interval::Interval};
 /// Info about a storage location
 #[derive(Debug, PartialEq, Serialize, ToSchema, sqlx::FromRow)]
 pub struct Storage {
-    /// The suppliers name
+    /// The storages id
+    pub id: String,
+    /// The storages name
     pub name: String,
     /// If the storage require a read permission
     pub protected: bool,
@@ -22,7 +24,7 @@ pub async fn get_all_unprotected(db: &Pool<Postgres>) -> Result<Vec<Storage>, sq
     sqlx::query_as!(
         Storage,
         r#"
-            SELECT name, protected, inventory_interval as "inventory_interval: Interval"
+            SELECT id::TEXT AS "id!", name, protected, inventory_interval as "inventory_interval: Interval"
             FROM storage
         "#,
     )
@@ -30,11 +32,25 @@ pub async fn get_all_unprotected(db: &Pool<Postgres>) -> Result<Vec<Storage>, sq
     .await
 }
 
+pub async fn get_by_name(db: &Pool<Postgres>, name: &str) -> Result<Storage, sqlx::Error> {
+    sqlx::query_as!(
+        Storage,
+        r#"
+            SELECT id::TEXT AS "id!", name, protected, inventory_interval as "inventory_interval: Interval"
+            FROM storage
+            WHERE name = $1
+        "#,
+        name
+    )
+    .fetch_one(db)
+    .await
+}
+
 pub async fn get_all(db: &Pool<Postgres>, access: &[String]) -> Result<Vec<Storage>, sqlx::Error> {
     sqlx::query_as!(
         Storage,
         r#"
-            SELECT name, protected, inventory_interval as "inventory_interval: Interval"
+            SELECT id::TEXT AS "id!", name, protected, inventory_interval as "inventory_interval: Interval"
             FROM storage
             WHERE protected <> true OR LOWER(name) IN (SELECT UNNEST($1::TEXT[]))
         "#,
@@ -102,7 +118,7 @@ pub async fn destroy(db: &Pool<Postgres>, name: &str) -> Result<(), sqlx::Error>
     sqlx::query!(
         r#"
             DELETE FROM container
-            WHERE storage = $1 AND name = ''
+            WHERE storage_id = (SELECT id FROM storage WHERE name = $1) AND name = ''
         "#,
         name,
     )
@@ -124,7 +140,7 @@ pub async fn destroy(db: &Pool<Postgres>, name: &str) -> Result<(), sqlx::Error>
 
 #[cfg(test)]
 mod test {
-    use sqlx::{postgres::types::PgInterval, Pool, Postgres};
+    use sqlx::{Pool, Postgres};
 
     use crate::db::{self, interval::Interval, storage::Storage};
 
@@ -137,6 +153,7 @@ mod test {
         assert_eq!(
             storage,
             vec![Storage {
+                id: db::id::storage_id(&db, "meta").await.unwrap(),
                 name: String::from("meta"),
                 protected: false,
                 inventory_interval: None
@@ -161,7 +178,7 @@ mod test {
         super::create(&db, "ESCapen", false, None).await.unwrap();
         super::create(&db, "spritis", true, None).await.unwrap();
 
-        let storages = super::get_all(&db, &vec![String::from("spritis")])
+        let storages = super::get_all(&db, &[String::from("spritis")])
             .await
             .unwrap();
 
@@ -175,7 +192,7 @@ mod test {
         let storage = sqlx::query_as!(
             super::Storage,
             r#"
-                SELECT name, protected, inventory_interval as "inventory_interval: Interval"
+                SELECT id::TEXT AS "id!", name, protected, inventory_interval as "inventory_interval: Interval"
                 FROM storage
             "#
         )
@@ -184,7 +201,7 @@ mod test {
         .unwrap();
 
         assert_eq!(storage.name, "meta");
-        assert_eq!(storage.protected, false);
+        assert!(!storage.protected);
         assert_eq!(storage.inventory_interval, None);
     }
 
@@ -197,7 +214,7 @@ mod test {
         let storages = sqlx::query_as!(
             super::Storage,
             r#"
-                SELECT name, protected, inventory_interval as "inventory_interval: Interval"
+                SELECT id::TEXT AS "id!", name, protected, inventory_interval as "inventory_interval: Interval"
                 FROM storage
             "#
         )
@@ -215,8 +232,9 @@ mod test {
         let container = sqlx::query_as!(
             db::container::Container,
             r#"
-            SELECT name, storage, inventory_interval as "inventory_interval: Interval"
+            SELECT container.name, storage.name AS storage, container.inventory_interval as "inventory_interval: Interval"
             FROM container
+            JOIN storage ON storage.id = container.storage_id
         "#,
         )
         .fetch_one(&db)
@@ -239,7 +257,7 @@ mod test {
         let storage = sqlx::query_as!(
             super::Storage,
             r#"
-                SELECT name, protected, inventory_interval as "inventory_interval: Interval"
+                SELECT id::TEXT AS "id!", name, protected, inventory_interval as "inventory_interval: Interval"
                 FROM storage
             "#
         )
@@ -248,14 +266,15 @@ mod test {
         .unwrap();
 
         assert_eq!(storage.name, "ESCapen");
-        assert_eq!(storage.protected, false);
+        assert!(!storage.protected);
         assert_eq!(storage.inventory_interval, None);
 
         let container = sqlx::query_as!(
             db::container::Container,
             r#"
-            SELECT name, storage, inventory_interval as "inventory_interval: Interval"
+            SELECT container.name, storage.name AS storage, container.inventory_interval as "inventory_interval: Interval"
             FROM container
+            JOIN storage ON storage.id = container.storage_id
         "#,
         )
         .fetch_one(&db)
@@ -278,7 +297,7 @@ mod test {
         let storage = sqlx::query_as!(
             super::Storage,
             r#"
-                SELECT name, protected, inventory_interval as "inventory_interval: Interval"
+                SELECT id::TEXT AS "id!", name, protected, inventory_interval as "inventory_interval: Interval"
                 FROM storage
             "#
         )
@@ -287,7 +306,7 @@ mod test {
         .unwrap();
 
         assert_eq!(storage.name, "meta");
-        assert_eq!(storage.protected, true);
+        assert!(storage.protected);
         assert_eq!(storage.inventory_interval, Some(Interval::new(0, 1, 0)));
     }
 
@@ -300,7 +319,7 @@ mod test {
         let storage = sqlx::query_as!(
             super::Storage,
             r#"
-                SELECT name, protected, inventory_interval as "inventory_interval: Interval"
+                SELECT id::TEXT AS "id!", name, protected, inventory_interval as "inventory_interval: Interval"
                 FROM storage
             "#
         )
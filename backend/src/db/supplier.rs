@@ -5,6 +5,8 @@ use utoipa::ToSchema;
 /// General info about a supplier
 #[derive(Debug, PartialEq, Serialize, ToSchema)]
 pub struct Supplier {
+    /// The suppliers id
+    id: String,
     /// The suppliers name
     name: String,
     /// Notes ex. order info
@@ -39,7 +41,7 @@ pub async fn get_all_by_mandate(
     sqlx::query_as!(
         Supplier,
         r#"
-            SELECT *
+            SELECT id::TEXT AS "id!", name, notes, username, password, link, mandate
             FROM supplier
             WHERE mandate IN (SELECT UNNEST($1::TEXT[]))
         "#,
@@ -53,7 +55,7 @@ pub async fn get_by_name(db: &Pool<Postgres>, name: &str) -> Result<Supplier, sq
     sqlx::query_as!(
         Supplier,
         r#"
-            SELECT *
+            SELECT id::TEXT AS "id!", name, notes, username, password, link, mandate
             FROM supplier
             WHERE name = $1
         "#,
@@ -88,6 +90,7 @@ pub async fn create(
     .await
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn change(
     db: &Pool<Postgres>,
     name: &str,
@@ -144,7 +147,7 @@ pub async fn destroy(db: &Pool<Postgres>, name: &str) -> Result<PgQueryResult, s
 mod test {
     use sqlx::{Pool, Postgres};
 
-    use crate::db::supplier::Supplier;
+    use crate::db::{self, supplier::Supplier};
 
     #[sqlx::test]
     async fn get(db: Pool<Postgres>) {
@@ -152,13 +155,14 @@ mod test {
             .await
             .unwrap();
 
-        let supplier = super::get_all_by_mandate(&db, &vec![String::from("mister@metadorerna.se")])
+        let supplier = super::get_all_by_mandate(&db, &[String::from("mister@metadorerna.se")])
             .await
             .unwrap();
 
         assert_eq!(
             supplier,
             vec![Supplier {
+                id: db::id::supplier_id(&db, "ICA").await.unwrap(),
                 name: String::from("ICA"),
                 notes: None,
                 username: None,
@@ -189,7 +193,7 @@ mod test {
         let supplier = sqlx::query_as!(
             Supplier,
             r#"
-                SELECT *
+                SELECT id::TEXT AS "id!", name, notes, username, password, link, mandate
                 FROM supplier
             "#,
         )
@@ -200,6 +204,7 @@ mod test {
         assert_eq!(
             supplier,
             Supplier {
+                id: db::id::supplier_id(&db, "ICA").await.unwrap(),
                 name: String::from("ICA"),
                 notes: None,
                 username: None,
@@ -232,7 +237,7 @@ mod test {
         let supplier = sqlx::query_as!(
             Supplier,
             r#"
-                SELECT *
+                SELECT id::TEXT AS "id!", name, notes, username, password, link, mandate
                 FROM supplier
             "#,
         )
@@ -243,6 +248,7 @@ mod test {
         assert_eq!(
             supplier,
             Supplier {
+                id: db::id::supplier_id(&db, "IKEA").await.unwrap(),
                 name: String::from("IKEA"),
                 notes: None,
                 username: None,
@@ -261,7 +267,7 @@ mod test {
 
         super::destroy(&db, "ICA").await.unwrap();
 
-        let supplier = super::get_all_by_mandate(&db, &vec![String::from("mister@metadorerna.se")])
+        let supplier = super::get_all_by_mandate(&db, &[String::from("mister@metadorerna.se")])
             .await
             .unwrap();
 
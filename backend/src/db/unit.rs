@@ -0,0 +1,193 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgQueryResult, Pool, Postgres, Transaction};
+use utoipa::ToSchema;
+
+use crate::error::{Error, FieldError};
+
+/// A unit an item can be counted in besides its own, ex. a 24-pack of cans
+#[derive(Debug, PartialEq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "unit_listing")]
+pub struct ItemUnit {
+    /// The units name
+    pub name: String,
+    /// How many of the items own unit one of this unit is
+    pub factor: f32,
+}
+
+/// Adds a unit to an item, it can't have the name of the items own unit. The field is the one
+/// reported if it does.
+pub async fn add(
+    db: &mut Transaction<'static, Postgres>,
+    item: &str,
+    unit: &ItemUnit,
+    field: &str,
+) -> Result<PgQueryResult, Error> {
+    let own = sqlx::query_scalar!("SELECT unit FROM item WHERE name = $1", item)
+        .fetch_one(&mut **db)
+        .await?;
+    if own == unit.name {
+        return Err(Error::Validation(vec![FieldError::new(
+            field,
+            "is already the unit of the item",
+        )]));
+    }
+
+    Ok(sqlx::query!(
+        "INSERT INTO item_unit (item_id, name, factor) VALUES (item_ref($1), $2, $3)",
+        item,
+        unit.name,
+        unit.factor
+    )
+    .execute(&mut **db)
+    .await?)
+}
+
+pub async fn delete(
+    db: &Pool<Postgres>,
+    item: &str,
+    unit: &str,
+) -> Result<PgQueryResult, sqlx::Error> {
+    sqlx::query!(
+        r#"
+            DELETE FROM item_unit
+            WHERE item_id = (SELECT id FROM item WHERE name = $1) AND name = $2
+        "#,
+        item,
+        unit
+    )
+    .execute(db)
+    .await
+}
+
+/// Converts an amount given in one of the units of an item to the items own unit, amounts
+/// without a unit already are in it. The field is the one reported if the unit is unknown.
+pub async fn to_base(
+    db: &Pool<Postgres>,
+    item: &str,
+    unit: Option<&str>,
+    amount: f32,
+    field: &str,
+) -> Result<f32, Error> {
+    let Some(unit) = unit else {
+        return Ok(amount);
+    };
+
+    let factor = sqlx::query_scalar!(
+        r#"
+            SELECT factor AS "factor!"
+            FROM (
+                SELECT 1::REAL AS factor, 0 AS rank
+                FROM item
+                WHERE name = $1 AND unit = $2
+                UNION ALL
+                SELECT item_unit.factor, 1
+                FROM item_unit
+                JOIN item ON item.id = item_unit.item_id
+                WHERE item.name = $1 AND item_unit.name = $2
+            ) AS units
+            ORDER BY rank
+            LIMIT 1
+        "#,
+        item,
+        unit
+    )
+    .fetch_optional(db)
+    .await?;
+
+    match factor {
+        Some(factor) => Ok(amount * factor),
+        None => Err(Error::Validation(vec![FieldError::new(
+            field,
+            "is not a unit of the item",
+        )])),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::{Pool, Postgres};
+
+    use super::ItemUnit;
+    use crate::{db, error::Error};
+
+    #[sqlx::test]
+    async fn conversion(db: Pool<Postgres>) {
+        db::storage::create(&db, "meta", false, None).await.unwrap();
+        db::item::create(
+            &db,
+            "test",
+            "meta",
+            "",
+            "coca-cola",
+            None,
+            None,
+            48.0,
+            Some("burk"),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let mut tx = db.begin().await.unwrap();
+        super::add(
+            &mut tx,
+            "coca-cola",
+            &ItemUnit {
+                name: String::from("24-pack"),
+                factor: 24.0,
+            },
+            "unit",
+        )
+        .await
+        .unwrap();
+        let own = ItemUnit {
+            name: String::from("burk"),
+            factor: 2.0,
+        };
+        assert!(matches!(
+            super::add(&mut tx, "coca-cola", &own, "unit").await,
+            Err(Error::Validation(_))
+        ));
+        tx.commit().await.unwrap();
+
+        let to_base = |unit: Option<&'static str>, amount: f32| {
+            let db = db.clone();
+            async move { super::to_base(&db, "coca-cola", unit, amount, "unit").await }
+        };
+        assert_eq!(to_base(None, 3.0).await.unwrap(), 3.0);
+        assert_eq!(to_base(Some("burk"), 3.0).await.unwrap(), 3.0);
+        assert_eq!(to_base(Some("24-pack"), 1.5).await.unwrap(), 36.0);
+        assert!(matches!(
+            to_base(Some("flak"), 1.0).await,
+            Err(Error::Validation(_))
+        ));
+
+        // Units follow the item when it is renamed
+        db::item::change(&db, "coca-cola", Some("cola"), "burk", None, None, None)
+            .await
+            .unwrap();
+
+        // Nor can the items own unit get the name of another unit
+        assert!(matches!(
+            db::item::change(&db, "cola", None, "24-pack", None, None, None).await,
+            Err(Error::Validation(_))
+        ));
+        assert_eq!(
+            db::item::get_item_by_name_detailed(&db, "cola", &[])
+                .await
+                .unwrap()
+                .units,
+            vec![ItemUnit {
+                name: String::from("24-pack"),
+                factor: 24.0,
+            }]
+        );
+
+        super::delete(&db, "cola", "24-pack").await.unwrap();
+        assert!(db::item::get_item_by_name_detailed(&db, "cola", &[])
+            .await
+            .unwrap()
+            .units
+            .is_empty());
+    }
+}
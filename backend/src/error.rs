@@ -7,25 +7,170 @@ use openidconnect::{
     HttpClientError, RequestTokenError, SignatureVerificationError, SigningError,
     StandardErrorResponse,
 };
-use utoipa::{ToResponse, ToSchema};
+use serde::Serialize;
+use sqlx::error::ErrorKind;
+use utoipa::ToSchema;
 
-#[derive(Debug, Display, ToResponse, ToSchema)]
+use crate::auth::LOGIN_PATH;
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Display)]
 pub enum Error {
+    /// The details are logged but never sent to the client
     InternalServerError(String),
+    /// Not logged in
     Unauthorized,
+    /// Logged in, but not allowed to do this
+    #[display("forbidden: {_0:?}")]
+    Forbidden(Refusal),
     BadRequest,
+    /// What the request refers to does not exist
+    NotFound,
+    /// The request clashes with the current state, ex. a name that is taken
+    Conflict(String),
+    /// Fields of the request are invalid
+    #[display("{_0:?}")]
+    Validation(Vec<FieldError>),
+}
+
+/// The body of every error response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorResponse {
+    /// Stable code to tell errors apart by: bad_request, unauthorized, forbidden, not_found,
+    /// conflict, validation or internal_server_error
+    pub error: &'static str,
+    /// What went wrong, meant for people
+    pub message: String,
+    /// The fields that failed validation
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+    /// What was missing when forbidden
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refused: Option<Refusal>,
+    /// Where to send the user to log in when unauthorized
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub login: Option<&'static str>,
+}
+
+/// Why a logged in user was not allowed to do something
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Refusal {
+    /// The check that failed: any, storage, item, move_item, move_container, supplier_create,
+    /// supplier, admin or read
+    pub check: &'static str,
+    /// The missing permission: read, write, admin, or group for membership in a Hive group
+    pub permission: &'static str,
+    /// Having the permission for any one of these would have been enough, empty if the scope
+    /// does not matter
+    pub scope: Vec<String>,
+}
+
+impl Refusal {
+    pub fn new(check: &'static str, permission: &'static str, scope: &[&str]) -> Self {
+        let mut scopes: Vec<String> = Vec::new();
+        for scope in scope.iter().filter(|scope| !scope.is_empty()) {
+            // Hive scopes are lowercase, groups are matched as they are
+            let scope = match permission {
+                "group" => scope.to_string(),
+                _ => scope.to_lowercase(),
+            };
+            if !scopes.contains(&scope) {
+                scopes.push(scope);
+            }
+        }
+
+        Refusal {
+            check,
+            permission,
+            scope: scopes,
+        }
+    }
+}
+
+/// A field of the request and why it is invalid
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: &str) -> Self {
+        FieldError {
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Error {
+    /// Maps the error of removing something, where a broken reference means it is still in use
+    pub fn removing(value: sqlx::Error) -> Self {
+        if let Some(error) = value.as_database_error() {
+            if matches!(error.kind(), ErrorKind::ForeignKeyViolation) {
+                log::info!("sqlx: {}", value);
+                let table = error.table().unwrap_or("row");
+                return Error::Conflict(format!("the {table} is still in use"));
+            }
+        }
+        value.into()
+    }
+
+    /// The code in the error body
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::InternalServerError(_) => "internal_server_error",
+            Error::Unauthorized => "unauthorized",
+            Error::Forbidden(_) => "forbidden",
+            Error::BadRequest => "bad_request",
+            Error::NotFound => "not_found",
+            Error::Conflict(_) => "conflict",
+            Error::Validation(_) => "validation",
+        }
+    }
 }
 
 impl ResponseError for Error {
     fn error_response(&self) -> actix_web::HttpResponse<actix_web::body::BoxBody> {
-        HttpResponse::build(self.status_code()).body(self.to_string())
+        let mut body = ErrorResponse {
+            error: self.code(),
+            message: String::new(),
+            fields: Vec::new(),
+            refused: None,
+            login: None,
+        };
+
+        body.message = match self {
+            Error::InternalServerError(_) => String::from("Internal Server Error"),
+            Error::Unauthorized => {
+                body.login = Some(LOGIN_PATH);
+                String::from("Not logged in")
+            }
+            Error::Forbidden(refusal) => {
+                body.refused = Some(refusal.clone());
+                String::from("Not allowed")
+            }
+            Error::BadRequest => String::from("Bad Request"),
+            Error::NotFound => String::from("Not found"),
+            Error::Conflict(message) => message.clone(),
+            Error::Validation(fields) => {
+                body.fields = fields.clone();
+                String::from("Invalid fields")
+            }
+        };
+
+        HttpResponse::build(self.status_code()).json(body)
     }
 
     fn status_code(&self) -> actix_web::http::StatusCode {
         match self {
             Error::InternalServerError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Error::Unauthorized => StatusCode::UNAUTHORIZED,
+            Error::Forbidden(_) => StatusCode::FORBIDDEN,
             Error::BadRequest => StatusCode::BAD_REQUEST,
+            Error::NotFound => StatusCode::NOT_FOUND,
+            Error::Conflict(_) => StatusCode::CONFLICT,
+            Error::Validation(_) => StatusCode::BAD_REQUEST,
         }
     }
 }
@@ -37,13 +182,55 @@ impl From<sqlx::types::uuid::Error> for Error {
     }
 }
 
+/// Missing rows and broken constraints are the clients fault, anything else is ours
 impl From<sqlx::Error> for Error {
     fn from(value: sqlx::Error) -> Self {
+        if let sqlx::Error::RowNotFound = value {
+            return Error::NotFound;
+        }
+
+        if let Some(error) = value.as_database_error() {
+            let table = error.table().unwrap_or("row");
+            let constraint = error.constraint().unwrap_or_default();
+
+            match error.kind() {
+                ErrorKind::UniqueViolation => {
+                    log::info!("sqlx: {}", value);
+                    return Error::Conflict(format!("the {table} already exists"));
+                }
+                // A write refering to something missing, removals map it with Error::removing
+                ErrorKind::ForeignKeyViolation => {
+                    log::info!("sqlx: {}", value);
+                    return Error::NotFound;
+                }
+                ErrorKind::CheckViolation => {
+                    log::info!("sqlx: {}", value);
+                    return Error::Validation(vec![FieldError::new(
+                        checked_field(table, constraint),
+                        &format!("violates {constraint}"),
+                    )]);
+                }
+                _ => {}
+            }
+        }
+
         log::error!("sqlx: {}", value);
         Error::InternalServerError(format!("sql: {}", value))
     }
 }
 
+/// The column of a check constraint named by Postgres, ex. amount for stored_item_amount_check,
+/// or the table if it checks several columns
+fn checked_field<'a>(table: &'a str, constraint: &'a str) -> &'a str {
+    constraint
+        .strip_prefix(table)
+        .and_then(|rest| rest.strip_prefix('_'))
+        .and_then(|rest| rest.split_once("_check"))
+        .map(|(field, _)| field)
+        .filter(|field| !field.is_empty())
+        .unwrap_or(table)
+}
+
 impl From<serde_json::Error> for Error {
     fn from(value: serde_json::Error) -> Self {
         log::error!("serde: {}", value);
@@ -105,3 +292,29 @@ where
         Error::InternalServerError(format!("token request error: {}", value))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::checked_field;
+
+    #[test]
+    fn check_constraint_fields() {
+        assert_eq!(
+            checked_field("stored_item", "stored_item_amount_check"),
+            "amount"
+        );
+        assert_eq!(
+            checked_field("stored_item", "stored_item_amount_check1"),
+            "amount"
+        );
+        assert_eq!(
+            checked_field("item", "item_inventory_interval_check"),
+            "inventory_interval"
+        );
+        assert_eq!(
+            checked_field("stored_item", "stored_item_check"),
+            "stored_item"
+        );
+        assert_eq!(checked_field("shipment_item", "custom"), "shipment_item");
+    }
+}
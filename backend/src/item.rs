@@ -9,21 +9,27 @@ use utoipa::{IntoParams, ToSchema};
 use utoipa_actix_web::service_config::ServiceConfig;
 
 use crate::{
-    auth::{check_auth, get_permitted_storages, types::HivePermission, CheckType},
+    auth::{
+        check_auth, get_readable_storages,
+        types::{HivePermission},
+        CheckType,
+    },
     db::{
         self,
         interval::Interval,
         item::{BasicItem, DetailedItem},
+        unit::ItemUnit,
     },
-    error::Error,
+    error::{Error, ErrorResponse, FieldError},
+    validate::{self, Validate, Validator},
 };
 
 /// Info to add an item to storage
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
 struct ItemAddRequest {
-    /// The storage location the item is at
+    /// The storage location the item is at, by name or id
     storage: String,
-    /// The container the item is stored in, can be "default" (loose)
+    /// The container the item is stored in, can be "default" (loose), by name or id
     container: String,
     /// The items name
     name: String,
@@ -33,18 +39,52 @@ struct ItemAddRequest {
     max: Option<f32>,
     /// The number of items currently in storage
     amount: f32,
-    /// The unit in which amount is counted (st, 6-pack, %, etc)
+    /// The unit of the item, amounts are counted and stored in it (st, burk, %, etc)
     unit: Option<String>,
+    /// The unit amount is given in, one of the items units, defaults to its own
+    amount_unit: Option<String>,
+    /// Other units the item can be counted in, ex. a 24-pack of cans
+    #[serde(default)]
+    units: Vec<ItemUnit>,
     /// The time between the item should be inventoried
     inventory_interval: Option<Interval>,
+    /// Barcodes that identify the item when scanned (EAN, UPC or codes of our own)
+    #[serde(default)]
+    barcodes: Vec<String>,
+    /// The category the item is in
+    category: Option<String>,
+    /// Free-form tags on the item
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+impl Validate for ItemAddRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.name("storage", &self.storage)
+            .container("container", &self.container)
+            .new_name("name", &self.name)
+            .limits(self.min, self.max)
+            .amount("amount", self.amount)
+            .optional_name("unit", self.unit.as_deref())
+            .optional_name("amount_unit", self.amount_unit.as_deref())
+            .interval("inventory_interval", self.inventory_interval.as_ref())
+            .each("units", &self.units);
+        for (i, code) in self.barcodes.iter().enumerate() {
+            v.barcode(&format!("barcodes[{i}]"), code);
+        }
+        v.optional_name("category", self.category.as_deref());
+        for (i, tag) in self.tags.iter().enumerate() {
+            v.name(&format!("tags[{i}]"), tag);
+        }
+    }
 }
 
 /// Info used to add a supplier to an item
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
 struct SupplierAddRequest {
-    /// The suppliers name
+    /// The suppliers name or id
     supplier: String,
-    /// The items name
+    /// The items name or id
     name: String,
     /// Link to the item och the suppliers webshop
     link: Option<String>,
@@ -52,10 +92,18 @@ struct SupplierAddRequest {
     prefered: bool,
 }
 
+impl Validate for SupplierAddRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.name("supplier", &self.supplier)
+            .name("name", &self.name)
+            .text("link", self.link.as_deref());
+    }
+}
+
 /// Info to change the name of an item (accross all storages)
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
 struct ItemChangeNameRequest {
-    /// The name to change from
+    /// The name or id of the item to change
     name: String,
     /// The name to change to
     new_name: Option<String>,
@@ -63,37 +111,74 @@ struct ItemChangeNameRequest {
     unit: String,
     /// The interval between the item needs to be inventoried
     inventory_interval: Option<Interval>,
+    /// The category the item is in, null to take it out of its category and left out to keep it
+    #[serde(default, deserialize_with = "validate::present")]
+    category: Option<Option<String>>,
+    /// Free-form tags on the item replacing the ones it has, left out to keep them
+    tags: Option<Vec<String>>,
+}
+
+impl Validate for ItemChangeNameRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.name("name", &self.name)
+            .optional_new_name("new_name", self.new_name.as_deref())
+            .name("unit", &self.unit)
+            .interval("inventory_interval", self.inventory_interval.as_ref())
+            .optional_name(
+                "category",
+                self.category.as_ref().and_then(Option::as_deref),
+            );
+        for (i, tag) in self.tags.iter().flatten().enumerate() {
+            v.name(&format!("tags[{i}]"), tag);
+        }
+    }
 }
 
 /// Info used to move an item
 #[derive(Debug, Deserialize, ToSchema)]
 struct ItemMoveRequest {
-    /// The items name
-    name: String,
+    /// The items name or id, or
+    name: Option<String>,
+    /// a barcode of the item
+    barcode: Option<String>,
     /// The amount of items to move
     amount: f32,
-    /// The name of the storage the items is moved from
+    /// The unit amount is given in, one of the items units, defaults to its own
+    unit: Option<String>,
+    /// The name or id of the storage the items is moved from
     from_storage: String,
-    /// The name of the container the items is moved from
+    /// The name or id of the container the items is moved from
     from_container: String,
-    /// The name of the storage the items is moved to
+    /// The name or id of the storage the items is moved to
     to_storage: String,
-    /// The name of the container the items is moved to
+    /// The name or id of the container the items is moved to
     to_container: String,
 }
 
+impl Validate for ItemMoveRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.item(self.name.as_deref(), self.barcode.as_deref())
+            .positive("amount", self.amount)
+            .optional_name("unit", self.unit.as_deref())
+            .name("from_storage", &self.from_storage)
+            .container("from_container", &self.from_container)
+            .name("to_storage", &self.to_storage)
+            .container("to_container", &self.to_container);
+    }
+}
+
 /// Info used to update an item at a particular storage location
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
 struct StoredUpdateRequest {
-    /// The storage the item is located in
+    /// The storage the item is located in, by name or id
     storage: String,
-    /// The storage to move the item to
+    /// The storage to move the item to, by name or id
     new_storage: Option<String>,
-    /// The container the item is located in
+    /// The container the item is located in, by name or id
     container: String,
-    /// The container to move the item to
+    /// The container to move the item to, by name or id
     new_container: Option<String>,
-    /// The items name
+    /// The items name or id
     name: String,
     /// The lower limit on how many items should be in storage (order limit)
     min: Option<f32>,
@@ -103,47 +188,119 @@ struct StoredUpdateRequest {
     amount: f32,
 }
 
+impl Validate for StoredUpdateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.name("storage", &self.storage)
+            .optional_name("new_storage", self.new_storage.as_deref())
+            .container("container", &self.container)
+            .optional_container("new_container", self.new_container.as_deref())
+            .name("name", &self.name)
+            .limits(self.min, self.max)
+            .amount("amount", self.amount);
+    }
+}
+
+/// Info used to add a barcode to an item
+#[derive(Debug, Deserialize, ToSchema)]
+struct BarcodeAddRequest {
+    /// The items name or id
+    name: String,
+    /// The code to identify the item by when scanned
+    barcode: String,
+}
+
+impl Validate for BarcodeAddRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.name("name", &self.name).barcode("barcode", &self.barcode);
+    }
+}
+
+/// Info used to add a unit to an item
+#[derive(Debug, Deserialize, ToSchema)]
+struct UnitAddRequest {
+    /// The items name or id
+    name: String,
+    /// The units name
+    unit: String,
+    /// How many of the items own unit one of the unit is
+    factor: f32,
+}
+
+impl Validate for UnitAddRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.name("name", &self.name)
+            .name("unit", &self.unit)
+            .positive("factor", self.factor);
+    }
+}
+
+impl Validate for ItemUnit {
+    fn validate(&self, v: &mut Validator) {
+        v.name("name", &self.name).positive("factor", self.factor);
+    }
+}
+
 /// Info used when filtering the items list
 #[derive(Deserialize, Debug, IntoParams, ToSchema)]
 struct ItemsGetQuery {
     /// The items name
     name: Option<String>,
-    /// The storages name
+    /// The storages name or id
     storage: Option<String>,
-    /// The containers name (requier storage to also be set)
+    /// The containers name or id (requier storage to also be set)
     container: Option<String>,
-    /// The suppliers name
+    /// The suppliers name or id
     supplier: Option<String>,
     /// Atleast this many items should exist
     min: Option<f32>,
     /// Atmost this many items should exist
     max: Option<f32>,
+    /// Only items in this category or one of its subcategories
+    category: Option<String>,
+    /// Only items with this tag
+    tag: Option<String>,
 }
 
 /// Info used to get a specific item
 #[derive(Deserialize, Debug, IntoParams)]
 struct ItemGetQuery {
-    /// The items name
+    /// The items name or id
     name: String,
 }
 
+/// A scanned barcode
+#[derive(Debug, Deserialize, IntoParams)]
+struct BarcodeQuery {
+    /// The scanned code
+    barcode: String,
+}
+
+/// Info used to remove a unit from an item
+#[derive(Debug, Deserialize, IntoParams)]
+struct UnitDeleteQuery {
+    /// The items name or id
+    name: String,
+    /// The units name
+    unit: String,
+}
+
 /// Info used to delete an item from storage
 #[derive(Debug, Deserialize, IntoParams)]
 struct ItemDeleteQuery {
-    /// The items name
+    /// The items name or id
     name: String,
-    /// The storage locations name
+    /// The storage locations name or id
     storage: String,
-    /// The containers name
+    /// The containers name or id
     container: String,
 }
 
 /// Info used to remove a supplier from an item
 #[derive(Debug, Deserialize, IntoParams)]
 struct SupplierRemoveQuery {
-    /// The items name
+    /// The items name or id
     name: String,
-    /// The suppliers name
+    /// The suppliers name or id
     supplier: String,
 }
 
@@ -158,7 +315,12 @@ pub fn config() -> impl FnOnce(&mut ServiceConfig) {
             .service(supply_item)
             .service(change_supply_item)
             .service(delete_item)
-            .service(unsupply_item);
+            .service(unsupply_item)
+            .service(scan_item)
+            .service(add_barcode)
+            .service(delete_barcode)
+            .service(add_unit)
+            .service(delete_unit);
     }
 }
 
@@ -173,10 +335,12 @@ pub fn config() -> impl FnOnce(&mut ServiceConfig) {
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -187,7 +351,18 @@ async fn get_items(
     query: web::Query<ItemsGetQuery>,
     permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
-    let permitted_storages = get_permitted_storages(&db, &permissions).await?;
+    let mut query = query.into_inner();
+    if let Some(storage) = &mut query.storage {
+        db::id::resolve_storage(&db, storage).await?;
+        if let Some(container) = &mut query.container {
+            db::id::resolve_container(&db, storage, container).await?;
+        }
+    }
+    if let Some(supplier) = &mut query.supplier {
+        db::id::resolve_supplier(&db, supplier).await?;
+    }
+
+    let readable_storages = get_readable_storages(&db, &permissions).await?;
 
     let items = db::item::get_all_filtered_basic(
         &db,
@@ -197,7 +372,9 @@ async fn get_items(
         query.supplier.as_deref(),
         query.min,
         query.max,
-        &permitted_storages,
+        query.category.as_deref(),
+        query.tag.as_deref(),
+        &readable_storages,
     )
     .await?;
     Ok(HttpResponse::Ok().json(items))
@@ -214,10 +391,22 @@ async fn get_items(
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -228,10 +417,27 @@ async fn get_item(
     query: web::Query<ItemGetQuery>,
     permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
-    let permitted_storages = get_permitted_storages(&db, &permissions).await?;
+    let mut name = query.into_inner().name;
+    db::id::resolve_item(&db, &mut name).await?;
+    Ok(HttpResponse::Ok().json(readable_item(&db, &name, &permissions).await?))
+}
+
+async fn readable_item(
+    db: &Pool<Postgres>,
+    name: &str,
+    permissions: &[HivePermission],
+) -> Result<DetailedItem, Error> {
+    let readable_storages = get_readable_storages(db, permissions).await?;
 
-    let item = db::item::get_item_by_name_detailed(&db, &query.name, &permitted_storages).await?;
-    Ok(HttpResponse::Ok().json(item))
+    let item = db::item::get_item_by_name_detailed(db, name, &readable_storages).await?;
+
+    // An item only stored in protected storages the user can't read is answered as if it did not
+    // exist, so neither the item nor where it is kept leaks
+    if item.storage.is_empty() && !db::item::get_location(db, name).await?.is_empty() {
+        return Err(Error::NotFound);
+    }
+
+    Ok(item)
 }
 
 #[utoipa::path(
@@ -240,18 +446,37 @@ async fn get_item(
     responses(
         (
             status = StatusCode::OK,
-            description = "Success"
+            body = String,
+            description = "The id of the item"
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -263,7 +488,8 @@ async fn create_item(
     id: web::ReqData<String>,
     permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
-    let item: ItemAddRequest = serde_json::from_str(&body)?;
+    let mut item: ItemAddRequest = validate::parse(&body)?;
+    db::id::resolve_location(&db, &mut item.storage, &mut item.container).await?;
 
     check_auth(
         CheckType::Storage {
@@ -275,15 +501,19 @@ async fn create_item(
     )
     .await?;
 
-    db::item::create(
-        &db,
+    let amount = added_amount(&db, &item).await?;
+
+    let mut transaction = db.begin().await?;
+
+    db::item::create_in_transaction(
+        &mut transaction,
         &id,
         &item.storage,
         &item.container,
         &item.name,
         item.min,
         item.max,
-        item.amount,
+        amount,
         item.unit.as_deref(),
         item.inventory_interval.and_then(|interval| {
             if interval < Interval::new(0, 1, 0) {
@@ -295,7 +525,50 @@ async fn create_item(
     )
     .await?;
 
-    Ok(HttpResponse::Ok().finish())
+    for code in &item.barcodes {
+        db::barcode::add(&mut transaction, &item.name, code).await?;
+    }
+    for (i, unit) in item.units.iter().enumerate() {
+        db::unit::add(
+            &mut transaction,
+            &item.name,
+            unit,
+            &format!("units[{i}].name"),
+        )
+        .await?;
+    }
+    if let Some(category) = &item.category {
+        db::item::set_category(&mut transaction, &item.name, category).await?;
+    }
+    db::item::tag(&mut transaction, &item.name, &item.tags).await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(db::id::item_id(&db, &item.name).await?))
+}
+
+/// The amount of an added item in its own unit, the amount can be given in a unit defined in the
+/// same request or in the unit a new item is created with
+async fn added_amount(db: &Pool<Postgres>, item: &ItemAddRequest) -> Result<f32, Error> {
+    let Some(unit) = item.amount_unit.as_deref() else {
+        return Ok(item.amount);
+    };
+
+    if let Some(defined) = item.units.iter().find(|defined| defined.name == unit) {
+        return Ok(item.amount * defined.factor);
+    }
+
+    match db::id::item_id(db, &item.name).await {
+        Err(sqlx::Error::RowNotFound) if unit == item.unit.as_deref().unwrap_or("st") => {
+            Ok(item.amount)
+        }
+        Err(sqlx::Error::RowNotFound) => Err(Error::Validation(vec![FieldError::new(
+            "amount_unit",
+            "is not a unit of the item",
+        )])),
+        Err(err) => Err(err.into()),
+        Ok(_) => db::unit::to_base(db, &item.name, Some(unit), item.amount, "amount_unit").await,
+    }
 }
 
 #[utoipa::path(
@@ -308,14 +581,32 @@ async fn create_item(
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -326,7 +617,9 @@ async fn supply_item(
     db: web::Data<Pool<Postgres>>,
     permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
-    let supplier: SupplierAddRequest = serde_json::from_str(&body)?;
+    let mut supplier: SupplierAddRequest = validate::parse(&body)?;
+    db::id::resolve_item(&db, &mut supplier.name).await?;
+    db::id::resolve_supplier(&db, &mut supplier.supplier).await?;
 
     check_auth(CheckType::Item(&supplier.name), &db, &permissions).await?;
 
@@ -352,14 +645,27 @@ async fn supply_item(
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -370,7 +676,9 @@ async fn change_supply_item(
     db: web::Data<Pool<Postgres>>,
     permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
-    let supplier: SupplierAddRequest = serde_json::from_str(&body)?;
+    let mut supplier: SupplierAddRequest = validate::parse(&body)?;
+    db::id::resolve_item(&db, &mut supplier.name).await?;
+    db::id::resolve_supplier(&db, &mut supplier.supplier).await?;
 
     check_auth(CheckType::Item(&supplier.name), &db, &permissions).await?;
 
@@ -396,14 +704,32 @@ async fn change_supply_item(
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -414,7 +740,8 @@ async fn change_item(
     db: web::Data<Pool<Postgres>>,
     permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
-    let item: ItemChangeNameRequest = serde_json::from_str(&body)?;
+    let mut item: ItemChangeNameRequest = validate::parse(&body)?;
+    db::id::resolve_item(&db, &mut item.name).await?;
     check_auth(CheckType::Item(&item.name), &db, &permissions).await?;
 
     db::item::change(
@@ -429,6 +756,8 @@ async fn change_item(
                 Some(interval)
             }
         }),
+        item.category.as_ref().map(Option::as_deref),
+        item.tags.as_deref(),
     )
     .await?;
 
@@ -445,14 +774,32 @@ async fn change_item(
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -464,7 +811,19 @@ async fn change_stored_item(
     permissions: web::ReqData<Vec<HivePermission>>,
     id: web::ReqData<String>,
 ) -> Result<HttpResponse, Error> {
-    let stored_item: StoredUpdateRequest = serde_json::from_str(&body)?;
+    let mut stored_item: StoredUpdateRequest = validate::parse(&body)?;
+    db::id::resolve_item(&db, &mut stored_item.name).await?;
+    db::id::resolve_location(&db, &mut stored_item.storage, &mut stored_item.container).await?;
+    if let Some(new_storage) = &mut stored_item.new_storage {
+        db::id::resolve_storage(&db, new_storage).await?;
+    }
+    if let Some(new_container) = &mut stored_item.new_container {
+        let storage = stored_item
+            .new_storage
+            .as_ref()
+            .unwrap_or(&stored_item.storage);
+        db::id::resolve_container(&db, storage, new_container).await?;
+    }
     check_auth(CheckType::Item(&stored_item.name), &db, &permissions).await?;
 
     db::item::change_stored_item(
@@ -494,14 +853,27 @@ async fn change_stored_item(
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -513,7 +885,9 @@ async fn move_item(
     permissions: web::ReqData<Vec<HivePermission>>,
     id: web::ReqData<String>,
 ) -> Result<HttpResponse, Error> {
-    let item: ItemMoveRequest = serde_json::from_str(&body)?;
+    let mut item: ItemMoveRequest = validate::parse(&body)?;
+    db::id::resolve_location(&db, &mut item.from_storage, &mut item.from_container).await?;
+    db::id::resolve_location(&db, &mut item.to_storage, &mut item.to_container).await?;
 
     check_auth(
         CheckType::MoveItem {
@@ -527,12 +901,15 @@ async fn move_item(
     )
     .await?;
 
+    let name = db::barcode::item_name(&db, item.name.as_deref(), item.barcode.as_deref()).await?;
+    let amount = db::unit::to_base(&db, &name, item.unit.as_deref(), item.amount, "unit").await?;
+
     let mut db = db.begin().await?;
 
     db::item::move_item(
         &mut db,
-        &item.name,
-        Some(item.amount),
+        &name,
+        Some(amount),
         &item.from_storage,
         &item.from_container,
         &item.to_storage,
@@ -556,14 +933,22 @@ async fn move_item(
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -574,6 +959,10 @@ async fn delete_item(
     db: web::Data<Pool<Postgres>>,
     permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
+    let mut query = query.into_inner();
+    db::id::resolve_item(&db, &mut query.name).await?;
+    db::id::resolve_location(&db, &mut query.storage, &mut query.container).await?;
+
     check_auth(
         CheckType::Storage {
             storage: &query.storage,
@@ -597,14 +986,22 @@ async fn delete_item(
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -615,7 +1012,269 @@ async fn unsupply_item(
     db: web::Data<Pool<Postgres>>,
     permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
+    let mut query = query.into_inner();
+    db::id::resolve_item(&db, &mut query.name).await?;
+    db::id::resolve_supplier(&db, &mut query.supplier).await?;
+
     check_auth(CheckType::Item(&query.name), &db, &permissions).await?;
     db::item::delete_supplier(&db, &query.name, &query.supplier).await?;
     Ok(HttpResponse::Ok().finish())
 }
+
+#[utoipa::path(
+    tag = "item",
+    params(BarcodeQuery),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = DetailedItem,
+            description = "The item with the barcode and where it is stored"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[get("/item/scan")]
+async fn scan_item(
+    db: web::Data<Pool<Postgres>>,
+    query: web::Query<BarcodeQuery>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let name = db::barcode::get_item(&db, &query.barcode).await?;
+
+    Ok(HttpResponse::Ok().json(readable_item(&db, &name, &permissions).await?))
+}
+
+#[utoipa::path(
+    tag = "item",
+    request_body = BarcodeAddRequest,
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "Success"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[post("/item/barcode")]
+async fn add_barcode(
+    body: String,
+    db: web::Data<Pool<Postgres>>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let mut barcode: BarcodeAddRequest = validate::parse(&body)?;
+    db::id::resolve_item(&db, &mut barcode.name).await?;
+
+    check_auth(CheckType::Item(&barcode.name), &db, &permissions).await?;
+
+    let mut db = db.begin().await?;
+    db::barcode::add(&mut db, &barcode.name, &barcode.barcode).await?;
+    db.commit().await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[utoipa::path(
+    tag = "item",
+    params(BarcodeQuery),
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "Success"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[delete("/item/barcode")]
+async fn delete_barcode(
+    query: web::Query<BarcodeQuery>,
+    db: web::Data<Pool<Postgres>>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let name = db::barcode::get_item(&db, &query.barcode).await?;
+    check_auth(CheckType::Item(&name), &db, &permissions).await?;
+    db::barcode::delete(&db, &query.barcode).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[utoipa::path(
+    tag = "item",
+    request_body = UnitAddRequest,
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "Success"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[post("/item/unit")]
+async fn add_unit(
+    body: String,
+    db: web::Data<Pool<Postgres>>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let mut unit: UnitAddRequest = validate::parse(&body)?;
+    db::id::resolve_item(&db, &mut unit.name).await?;
+
+    check_auth(CheckType::Item(&unit.name), &db, &permissions).await?;
+
+    let mut db = db.begin().await?;
+    db::unit::add(
+        &mut db,
+        &unit.name,
+        &ItemUnit {
+            name: unit.unit,
+            factor: unit.factor,
+        },
+        "unit",
+    )
+    .await?;
+    db.commit().await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[utoipa::path(
+    tag = "item",
+    params(UnitDeleteQuery),
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "Success"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[delete("/item/unit")]
+async fn delete_unit(
+    query: web::Query<UnitDeleteQuery>,
+    db: web::Data<Pool<Postgres>>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let mut query = query.into_inner();
+    db::id::resolve_item(&db, &mut query.name).await?;
+
+    check_auth(CheckType::Item(&query.name), &db, &permissions).await?;
+    db::unit::delete(&db, &query.name, &query.unit).await?;
+    Ok(HttpResponse::Ok().finish())
+}
@@ -5,8 +5,9 @@ use utoipa::IntoParams;
 use utoipa_actix_web::service_config::ServiceConfig;
 
 use crate::{
+    auth::{get_readable_storages, types::HivePermission},
     db::{self, log::Log},
-    error::Error,
+    error::{Error, ErrorResponse},
 };
 
 /// Info used to filter the logs to return
@@ -33,6 +34,7 @@ pub(crate) fn config() -> impl FnOnce(&mut ServiceConfig) {
         ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -41,8 +43,11 @@ pub(crate) fn config() -> impl FnOnce(&mut ServiceConfig) {
 pub(crate) async fn get_log(
     query: web::Query<LogGetQuery>,
     db: web::Data<Pool<Postgres>>,
+    permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
-    let logs: Vec<Log> = db::log::get_all_by_item(&db, &query.name).await?;
+    let protected = get_readable_storages(&db, &permissions).await?;
+
+    let logs: Vec<Log> = db::log::get_all_by_item(&db, &query.name, &protected).await?;
 
     Ok(HttpResponse::Ok().json(logs))
 }
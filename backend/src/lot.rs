@@ -0,0 +1,306 @@
+use actix_web::{get, post, web, HttpResponse};
+use serde::Deserialize;
+use sqlx::{
+    types::{chrono::NaiveDate, Uuid},
+    Pool, Postgres,
+};
+use utoipa::{IntoParams, ToSchema};
+use utoipa_actix_web::service_config::ServiceConfig;
+
+use crate::{
+    auth::{
+        check_auth, get_readable_storages,
+        types::{HivePermission},
+        CheckType,
+    },
+    db::{self, lot::Lot},
+    error::{Error, ErrorResponse, FieldError},
+    validate::{self, Validate, Validator},
+};
+
+/// Days ahead a lot counts as expiring soon when nothing else is asked for
+const EXPIRING_DAYS: i32 = 7;
+
+/// Info used to put a lot in storage
+#[derive(Debug, Deserialize, ToSchema)]
+struct LotAddRequest {
+    /// The items name or id
+    item: String,
+    /// The storage the lot is put in, by name or id
+    storage: String,
+    /// The container the lot is put in, by name or id
+    container: String,
+    /// The number of items in the lot
+    amount: f32,
+    /// The date the items are best before, none if they don't expire
+    best_before: Option<NaiveDate>,
+}
+
+impl Validate for LotAddRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.name("item", &self.item)
+            .name("storage", &self.storage)
+            .container("container", &self.container)
+            .positive("amount", self.amount);
+    }
+}
+
+/// Info used to write off items of a lot
+#[derive(Debug, Deserialize, ToSchema)]
+struct WriteOffRequest {
+    /// The lots id
+    id: String,
+    /// The number of items to write off, the whole lot if none
+    amount: Option<f32>,
+    /// Why the items are written off, ex. expired
+    reason: String,
+}
+
+impl Validate for WriteOffRequest {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(amount) = self.amount {
+            v.positive("amount", amount);
+        }
+        v.text("reason", Some(&self.reason));
+    }
+}
+
+/// Info used to get the lots of an item
+#[derive(Debug, Deserialize, IntoParams)]
+struct LotsGetQuery {
+    /// The items name or id
+    name: String,
+}
+
+/// Info used to pick how soon lots have to expire to be returned
+#[derive(Debug, Deserialize, IntoParams)]
+struct LotsExpiringQuery {
+    /// Return lots best before at most this many days from today, defaults to a week
+    days: Option<i32>,
+}
+
+pub(crate) fn config() -> impl FnOnce(&mut ServiceConfig) {
+    |cfg: &mut ServiceConfig| {
+        cfg.service(get_lots)
+            .service(get_expiring)
+            .service(add_lot)
+            .service(write_off);
+    }
+}
+
+#[utoipa::path(
+    tag = "inventory",
+    params(LotsGetQuery),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<Lot>,
+            description = "The lots of the item, in the order they are used"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[get("/lots")]
+async fn get_lots(
+    db: web::Data<Pool<Postgres>>,
+    query: web::Query<LotsGetQuery>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let mut name = query.into_inner().name;
+    db::id::resolve_item(&db, &mut name).await?;
+
+    let protected = get_readable_storages(&db, &permissions).await?;
+
+    Ok(HttpResponse::Ok().json(db::lot::get_by_item(&db, &name, &protected).await?))
+}
+
+#[utoipa::path(
+    tag = "inventory",
+    params(LotsExpiringQuery),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<Lot>,
+            description = "Lots close to or past their best-before date, first expiry first"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[get("/lots/expiring")]
+async fn get_expiring(
+    db: web::Data<Pool<Postgres>>,
+    query: web::Query<LotsExpiringQuery>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let days = query.days.unwrap_or(EXPIRING_DAYS);
+    if days < 0 {
+        return Err(Error::Validation(vec![FieldError::new(
+            "days",
+            "must not be negative",
+        )]));
+    }
+
+    let protected = get_readable_storages(&db, &permissions).await?;
+
+    Ok(HttpResponse::Ok().json(db::lot::get_expiring(&db, days, &protected).await?))
+}
+
+#[utoipa::path(
+    tag = "inventory",
+    request_body = LotAddRequest,
+    responses(
+        (
+            status = StatusCode::OK,
+            body = String,
+            description = "The id of the lot"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[post("/lot")]
+async fn add_lot(
+    body: String,
+    db: web::Data<Pool<Postgres>>,
+    id: web::ReqData<String>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let mut lot: LotAddRequest = validate::parse(&body)?;
+    db::id::resolve_item(&db, &mut lot.item).await?;
+    db::id::resolve_location(&db, &mut lot.storage, &mut lot.container).await?;
+
+    check_auth(
+        CheckType::Storage {
+            storage: &lot.storage,
+            container: Some(&lot.container),
+        },
+        &db,
+        &permissions,
+    )
+    .await?;
+
+    let lot = db::lot::receive(
+        &db,
+        &id,
+        &lot.item,
+        &lot.storage,
+        &lot.container,
+        lot.amount,
+        lot.best_before,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(lot.to_string()))
+}
+
+#[utoipa::path(
+    tag = "inventory",
+    request_body = WriteOffRequest,
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "Success"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[post("/lot/write-off")]
+async fn write_off(
+    body: String,
+    db: web::Data<Pool<Postgres>>,
+    id: web::ReqData<String>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let write_off: WriteOffRequest = validate::parse(&body)?;
+    let lot_id = Uuid::parse_str(&write_off.id)?;
+
+    let lot = db::lot::get(&db, lot_id).await?;
+    check_auth(
+        CheckType::Storage {
+            storage: &lot.storage,
+            container: Some(&lot.container),
+        },
+        &db,
+        &permissions,
+    )
+    .await?;
+
+    db::lot::write_off(&db, &id, lot_id, write_off.amount, &write_off.reason).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -1,4 +1,3 @@
-#[warn(clippy::pedantic)]
 use actix_cors::Cors;
 use actix_web::{
     http::Method,
@@ -12,19 +11,25 @@ use utoipa::OpenApi;
 use utoipa_actix_web::{scope, AppExt};
 use utoipa_redoc::{Redoc, Servable};
 
+mod app;
 mod auth;
+mod category;
 mod db;
 mod error;
 mod item;
 mod logging;
+mod lot;
 mod serve;
 mod shipment;
 mod shortage;
 mod stats;
+mod stocktake;
 mod storage;
 mod supplier;
+mod validate;
 
-use auth::types::{AuthMiddleware, OIDCData};
+use app::AppData;
+use auth::types::SessionConfig;
 use db::init_db;
 use serve::serve_frontend;
 
@@ -45,9 +50,13 @@ async fn main() -> std::io::Result<()> {
         .await
         .expect("migrations to run");
 
-    let (oidc, auth_path) = OIDCData::get_oidc().await;
-    let oidc = Data::new(oidc);
-    let auth_url = auth_path.clone();
+    let (identity, permissions) = auth::provider::from_env();
+    let data = AppData {
+        db: pool,
+        identity: Data::from(identity),
+        permissions: Data::from(permissions),
+        session: SessionConfig::from_env(),
+    };
 
     HttpServer::new(move || {
         let cors = if env::var("APP_ENV") == Ok(String::from("development")) {
@@ -65,28 +74,12 @@ async fn main() -> std::io::Result<()> {
                 .allowed_origin("https://sso.datasektionen.se")
         };
 
-        App::new()
+        let app = App::new()
             .into_utoipa_app()
             .openapi(ApiDoc::openapi())
-            .map(|app| {
-                app.wrap(cors)
-                    .wrap(Logger::default())
-                    .app_data(pool.clone())
-                    .app_data(oidc.clone())
-            })
-            .service(utoipa_actix_web::scope("/auth").configure(auth::config()))
-            .service(
-                scope("/api") // Every thing in this scope requires the user to be logged in using sso
-                    .wrap(AuthMiddleware::new(auth_url.clone()))
-                    .configure(item::config())
-                    .configure(supplier::config())
-                    .configure(storage::config())
-                    .configure(shortage::config())
-                    .configure(stats::config())
-                    .configure(logging::config())
-                    .configure(shipment::config())
-                    .service(auth::user_info),
-            )
+            .map(|app| app.wrap(cors).wrap(Logger::default()));
+
+        app::api(app, &data)
             .openapi_service(|api| Redoc::with_url("/docs/api", api))
             .service(scope("").map(|app| {
                 app
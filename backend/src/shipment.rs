@@ -1,8 +1,8 @@
-use actix_web::{delete, get, post, web, HttpResponse};
+use actix_web::{delete, get, patch, post, web, HttpResponse};
 use serde::{Deserialize, Serialize};
 use sqlx::{
     types::{
-        chrono::{DateTime, Utc},
+        chrono::{DateTime, NaiveDate, Utc},
         Uuid,
     },
     Pool, Postgres,
@@ -11,25 +11,128 @@ use utoipa::{IntoParams, ToSchema};
 use utoipa_actix_web::service_config::ServiceConfig;
 
 use crate::{
-    auth::{check_auth, types::HivePermission, CheckType},
-    db::{self, shipment::ShipmentItem},
-    error::Error,
+    auth::{
+        check_auth, get_readable_storages,
+        types::{Group, HivePermission},
+        CheckType,
+    },
+    db::{
+        self,
+        shipment::{ReceivedItem, Shipment, ShipmentEvent, ShipmentItem, ShipmentState},
+    },
+    error::{Error, ErrorResponse, FieldError},
+    validate::{self, Validate, Validator},
 };
 
 #[derive(Debug, Serialize, ToSchema)]
 struct ShipmentGetResponse {
     id: String,
+    state: ShipmentState,
+    supplier: Option<String>,
     time_created: DateTime<Utc>,
     time_arive: DateTime<Utc>,
+    time_received: Option<DateTime<Utc>>,
     items: Vec<ShipmentItem>,
+    history: Vec<ShipmentEvent>,
 }
 
+impl From<Shipment> for ShipmentGetResponse {
+    fn from(shipment: Shipment) -> Self {
+        ShipmentGetResponse {
+            id: shipment.id.to_string(),
+            state: shipment.state,
+            supplier: shipment.supplier,
+            time_created: shipment.time_created,
+            time_arive: shipment.time_arive,
+            time_received: shipment.time_received,
+            items: shipment.items,
+            history: shipment.history,
+        }
+    }
+}
+
+/// Info used to create a shipment
 #[derive(Debug, Deserialize, ToSchema)]
 struct ShipmentCreateRequest {
+    /// When the shipment is expected to arrive
     arrival_time: DateTime<Utc>,
+    /// The supplier the shipment is ordered from, by name or id
+    supplier: Option<String>,
+    /// The items in the shipment and where they are meant to go
     items: Vec<ShipmentItem>,
 }
 
+impl Validate for ShipmentCreateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.optional_name("supplier", self.supplier.as_deref())
+            .not_empty("items", &self.items)
+            .each("items", &self.items);
+    }
+}
+
+/// Info used to receive a shipment into storage
+#[derive(Debug, Deserialize, ToSchema)]
+struct ShipmentReceiveRequest {
+    /// The shipments id
+    id: String,
+    /// The items that arrived, lines not listed stay outstanding
+    items: Vec<ShipmentReceiveItem>,
+}
+
+impl Validate for ShipmentItem {
+    fn validate(&self, v: &mut Validator) {
+        v.name("item", &self.item)
+            .positive("amount", self.amount)
+            .destination(self.storage.as_deref(), self.container.as_deref())
+            .optional_name("unit", self.unit.as_deref());
+    }
+}
+
+impl Validate for ShipmentReceiveRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.not_empty("items", &self.items).each("items", &self.items);
+    }
+}
+
+/// An amount of a shipment line that has arrived
+#[derive(Debug, Deserialize, ToSchema)]
+struct ShipmentReceiveItem {
+    /// The items name or id
+    item: String,
+    /// The number of items that arrived
+    amount: f32,
+    /// The storage the items are put in by name or id, defaults to the lines destination
+    storage: Option<String>,
+    /// The container the items are put in by name or id, defaults to the lines destination
+    container: Option<String>,
+    /// The date the items are best before, they are put in storage as a lot if it is given
+    best_before: Option<NaiveDate>,
+}
+
+impl Validate for ShipmentReceiveItem {
+    fn validate(&self, v: &mut Validator) {
+        v.name("item", &self.item)
+            .positive("amount", self.amount)
+            .destination(self.storage.as_deref(), self.container.as_deref());
+    }
+}
+
+/// Info used to move a shipment to a new state
+#[derive(Debug, Deserialize, ToSchema)]
+struct ShipmentStateRequest {
+    /// The shipments id
+    id: String,
+    /// The new state, only shipped and cancelled can be set directly
+    state: ShipmentState,
+}
+
+/// Info used to filter the shipments to return
+#[derive(Debug, Deserialize, IntoParams)]
+struct ShipmentGetQuery {
+    /// Only return shipments in this state
+    state: Option<ShipmentState>,
+}
+
 #[derive(Debug, Deserialize, IntoParams)]
 struct ShipmentDeleteQuery {
     id: String,
@@ -37,12 +140,56 @@ struct ShipmentDeleteQuery {
 
 pub(crate) fn config() -> impl FnOnce(&mut ServiceConfig) {
     |cfg: &mut ServiceConfig| {
-        cfg.service(get_shipment).service(create).service(destroy);
+        cfg.service(get_shipment)
+            .service(create)
+            .service(receive)
+            .service(change_state)
+            .service(destroy);
+    }
+}
+
+/// Checks that the user may order the items from the supplier to their destinations
+pub(crate) async fn check_create_auth(
+    db: &Pool<Postgres>,
+    permissions: &[HivePermission],
+    groups: &[Group],
+    supplier: Option<&str>,
+    items: &[ShipmentItem],
+) -> Result<(), Error> {
+    check_auth(CheckType::Any, db, permissions).await?;
+
+    if let Some(supplier) = supplier {
+        check_auth(
+            CheckType::Supplier {
+                mandates: groups,
+                name: supplier,
+            },
+            db,
+            permissions,
+        )
+        .await?;
+    }
+
+    for item in items {
+        if let Some(storage) = &item.storage {
+            check_auth(
+                CheckType::Storage {
+                    storage,
+                    container: item.container.as_deref(),
+                },
+                db,
+                permissions,
+            )
+            .await?;
+        }
     }
+
+    Ok(())
 }
 
 #[utoipa::path(
     tag = "shipment",
+    params(ShipmentGetQuery),
     responses(
         (
             status = StatusCode::OK,
@@ -51,30 +198,34 @@ pub(crate) fn config() -> impl FnOnce(&mut ServiceConfig) {
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
 )]
 #[get("/shipment")]
-pub(crate) async fn get_shipment(db: web::Data<Pool<Postgres>>) -> Result<HttpResponse, Error> {
-    let shipments = db::shipment::get_all_orders(&db).await?;
+pub(crate) async fn get_shipment(
+    db: web::Data<Pool<Postgres>>,
+    query: web::Query<ShipmentGetQuery>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let protected = get_readable_storages(&db, &permissions).await?;
+
+    let shipments = db::shipment::get_all_orders(&db, query.state, &protected).await?;
 
     let shipments = shipments
         .into_iter()
-        .map(|shipment| ShipmentGetResponse {
-            id: shipment.id.to_string(),
-            time_arive: shipment.time_arive.into(),
-            time_created: shipment.time_created.into(),
-            items: shipment.items,
-        })
+        .map(ShipmentGetResponse::from)
         .collect::<Vec<ShipmentGetResponse>>();
 
     Ok(HttpResponse::Ok().json(shipments))
@@ -90,14 +241,27 @@ pub(crate) async fn get_shipment(db: web::Data<Pool<Postgres>>) -> Result<HttpRe
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -106,17 +270,214 @@ pub(crate) async fn get_shipment(db: web::Data<Pool<Postgres>>) -> Result<HttpRe
 async fn create(
     body: String,
     db: web::Data<Pool<Postgres>>,
+    id: web::ReqData<String>,
     permissions: web::ReqData<Vec<HivePermission>>,
+    groups: web::ReqData<Vec<Group>>,
 ) -> Result<HttpResponse, Error> {
-    let shipment: ShipmentCreateRequest = serde_json::from_str(&body)?;
+    let mut shipment: ShipmentCreateRequest = validate::parse(&body)?;
+    if let Some(supplier) = &mut shipment.supplier {
+        db::id::resolve_supplier(&db, supplier).await?;
+    }
+    for (i, item) in shipment.items.iter_mut().enumerate() {
+        db::id::resolve_item(&db, &mut item.item).await?;
+        if let (Some(storage), Some(container)) = (&mut item.storage, &mut item.container) {
+            db::id::resolve_location(&db, storage, container).await?;
+        }
+        item.amount = db::unit::to_base(
+            &db,
+            &item.item,
+            item.unit.as_deref(),
+            item.amount,
+            &format!("items[{i}].unit"),
+        )
+        .await?;
+    }
+
+    check_create_auth(
+        &db,
+        &permissions,
+        &groups,
+        shipment.supplier.as_deref(),
+        &shipment.items,
+    )
+    .await?;
+
+    db::shipment::create_order(
+        &db,
+        &id,
+        shipment.arrival_time,
+        shipment.supplier.as_deref(),
+        shipment.items,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[utoipa::path(
+    tag = "shipment",
+    request_body = ShipmentReceiveRequest,
+    responses(
+        (
+            status = StatusCode::OK,
+            body = ShipmentGetResponse,
+            description = "The shipment after the items have been received"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[post("/shipment/receive")]
+async fn receive(
+    body: String,
+    db: web::Data<Pool<Postgres>>,
+    id: web::ReqData<String>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let mut shipment: ShipmentReceiveRequest = validate::parse(&body)?;
+    for item in &mut shipment.items {
+        db::id::resolve_item(&db, &mut item.item).await?;
+        if let (Some(storage), Some(container)) = (&mut item.storage, &mut item.container) {
+            db::id::resolve_location(&db, storage, container).await?;
+        }
+    }
+    let shipment_id = Uuid::parse_str(&shipment.id)?;
+    let protected = get_readable_storages(&db, &permissions).await?;
+
+    let lines = db::shipment::get_order_by_id(&db, shipment_id, &protected)
+        .await?
+        .items;
+
+    let mut items = Vec::new();
+    for item in shipment.items {
+        let line = lines
+            .iter()
+            .find(|line| line.item == item.item)
+            .ok_or(Error::NotFound)?;
+
+        let (Some(storage), Some(container)) = (
+            item.storage.or(line.storage.clone()),
+            item.container.or(line.container.clone()),
+        ) else {
+            return Err(Error::Validation(vec![FieldError::new(
+                "storage",
+                "the line has no destination so one has to be given",
+            )]));
+        };
+
+        check_auth(
+            CheckType::Storage {
+                storage: &storage,
+                container: Some(&container),
+            },
+            &db,
+            &permissions,
+        )
+        .await?;
+
+        items.push(ReceivedItem {
+            item: item.item,
+            amount: item.amount,
+            storage,
+            container,
+            best_before: item.best_before,
+        });
+    }
+
+    db::shipment::receive(&db, &id, shipment_id, items).await?;
+
+    let shipment: ShipmentGetResponse = db::shipment::get_order_by_id(&db, shipment_id, &protected)
+        .await?
+        .into();
+
+    Ok(HttpResponse::Ok().json(shipment))
+}
+
+#[utoipa::path(
+    tag = "shipment",
+    request_body = ShipmentStateRequest,
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "Success"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[patch("/shipment/state")]
+async fn change_state(
+    body: String,
+    db: web::Data<Pool<Postgres>>,
+    id: web::ReqData<String>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let shipment: ShipmentStateRequest = serde_json::from_str(&body)?;
 
     check_auth(CheckType::Any, &db, &permissions).await?;
 
-    db::shipment::create_order(&db, shipment.arrival_time.into(), shipment.items).await?;
+    db::shipment::set_state(&db, &id, Uuid::parse_str(&shipment.id)?, shipment.state).await?;
 
     Ok(HttpResponse::Ok().finish())
 }
 
+/// Cancels the shipment, it is kept with its history for reporting
 #[utoipa::path(
     tag = "shipment",
     params(ShipmentDeleteQuery),
@@ -127,14 +488,22 @@ async fn create(
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -143,11 +512,18 @@ async fn create(
 async fn destroy(
     query: web::Query<ShipmentDeleteQuery>,
     db: web::Data<Pool<Postgres>>,
+    id: web::ReqData<String>,
     permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
     check_auth(CheckType::Any, &db, &permissions).await?;
 
-    db::shipment::delete(&db, Uuid::parse_str(&query.id)?).await?;
+    db::shipment::set_state(
+        &db,
+        &id,
+        Uuid::parse_str(&query.id)?,
+        ShipmentState::Cancelled,
+    )
+    .await?;
 
     Ok(HttpResponse::Ok().finish())
 }
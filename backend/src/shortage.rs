@@ -1,16 +1,27 @@
 use actix_web::{get, post, web, HttpResponse};
-use serde::Deserialize;
-use sqlx::{Pool, Postgres};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    types::chrono::{DateTime, Utc},
+    Pool, Postgres,
+};
 use utoipa::{IntoParams, ToSchema};
 use utoipa_actix_web::service_config::ServiceConfig;
 
 use crate::{
-    auth::{get_permitted_storages, types::HivePermission},
+    auth::{
+        check_auth, get_permitted_storages, get_readable_storages,
+        types::{Group, HivePermission},
+        CheckType,
+    },
     db::{
         self,
-        item::{DueStorage, ShortageItem},
+        forecast::{Forecast, ForecastSort},
+        item::{DueStorage, PurchaseOrder, ShortageItem},
+        shipment::ShipmentItem,
     },
-    error::Error,
+    error::{Error, ErrorResponse},
+    shipment::check_create_auth,
+    validate::{self, Validate, Validator},
 };
 
 /// Info used to take inventory
@@ -20,21 +31,90 @@ struct StockUpdateRequest {
     items: Vec<StockUpdate>,
 }
 
+impl Validate for StockUpdateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.each("items", &self.items);
+    }
+}
+
 #[derive(Deserialize, ToSchema)]
 struct StockUpdate {
-    /// The items name
-    name: String,
-    /// The storage where the item is located
+    /// The items name or id, or
+    name: Option<String>,
+    /// a barcode of the item
+    barcode: Option<String>,
+    /// The storage where the item is located, by name or id
     storage: String,
-    /// The container where the item is stored
+    /// The container where the item is stored, by name or id
     container: String,
     /// The number of items currently in storage
     amount: f32,
+    /// The unit amount is counted in, one of the items units, defaults to its own
+    unit: Option<String>,
+}
+
+impl Validate for StockUpdate {
+    fn validate(&self, v: &mut Validator) {
+        v.item(self.name.as_deref(), self.barcode.as_deref())
+            .name("storage", &self.storage)
+            .container("container", &self.container)
+            .amount("amount", self.amount)
+            .optional_name("unit", self.unit.as_deref());
+    }
+}
+
+/// The locations a stock update was refused for, nothing is updated if any location is refused.
+/// Shaped as other error responses with the locations added.
+#[derive(Debug, Serialize, ToSchema)]
+struct StockUpdateRefused {
+    /// Always forbidden, as in other error responses
+    error: &'static str,
+    /// What went wrong, meant for people
+    message: String,
+    /// The locations the user is not allowed to write to
+    locations: Vec<RefusedLocation>,
+}
+
+#[derive(Debug, PartialEq, Serialize, ToSchema)]
+struct RefusedLocation {
+    /// The storage that was refused
+    storage: String,
+    /// The container that was refused
+    container: String,
+}
+
+/// Info used to turn a purchase order into a shipment
+#[derive(Deserialize, ToSchema)]
+struct PurchaseOrderCreateRequest {
+    /// The supplier whose purchase order to order, none for the unsourced items
+    supplier: Option<String>,
+    /// When the shipment is expected to arrive
+    arrival_time: DateTime<Utc>,
+}
+
+/// Info used to only get the part of a list in a category
+#[derive(Debug, Deserialize, IntoParams)]
+struct CategoryQuery {
+    /// Only items in this category or one of its subcategories
+    category: Option<String>,
+}
+
+/// Info used to pick which forecasts to return
+#[derive(Debug, Deserialize, IntoParams)]
+struct ForecastQuery {
+    /// What to sort the items on, defaults to when they run out
+    #[serde(default)]
+    sort: ForecastSort,
+    /// Only return items running out within this many days
+    days: Option<i32>,
 }
 
 pub fn config() -> impl FnOnce(&mut ServiceConfig) {
     |cfg: &mut ServiceConfig| {
         cfg.service(get_shortage)
+            .service(get_purchase_orders)
+            .service(order_shortage)
+            .service(get_forecast)
             .service(items_due)
             .service(take_stock);
     }
@@ -42,6 +122,7 @@ pub fn config() -> impl FnOnce(&mut ServiceConfig) {
 
 #[utoipa::path(
     tag = "inventory",
+    params(CategoryQuery),
     responses(
         (
             status = StatusCode::OK,
@@ -50,10 +131,12 @@ pub fn config() -> impl FnOnce(&mut ServiceConfig) {
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -61,26 +144,173 @@ pub fn config() -> impl FnOnce(&mut ServiceConfig) {
 #[get("/shortage")]
 async fn get_shortage(
     db: web::Data<Pool<Postgres>>,
+    query: web::Query<CategoryQuery>,
     permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
-    let protected: Vec<String> = permissions
-        .iter()
-        .filter_map(|perm| {
-            if perm.id == "read" {
-                perm.scope.clone()
-            } else {
-                None
-            }
+    let protected = get_readable_storages(&db, &permissions).await?;
+
+    let items = db::item::get_shortage(&db, query.category.as_deref(), &protected).await?;
+
+    Ok(HttpResponse::Ok().json(items))
+}
+
+#[utoipa::path(
+    tag = "inventory",
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<PurchaseOrder>,
+            description = "Shortages grouped by the supplier they should be bought from"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[get("/shortage/order")]
+async fn get_purchase_orders(
+    db: web::Data<Pool<Postgres>>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let protected = get_readable_storages(&db, &permissions).await?;
+
+    let orders = db::item::get_purchase_orders(&db, &protected).await?;
+
+    Ok(HttpResponse::Ok().json(orders))
+}
+
+#[utoipa::path(
+    tag = "inventory",
+    params(ForecastQuery),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<Forecast>,
+            description = "List of items that are being consumed, the ones running out soonest first"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[get("/forecast")]
+async fn get_forecast(
+    db: web::Data<Pool<Postgres>>,
+    query: web::Query<ForecastQuery>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let protected = get_readable_storages(&db, &permissions).await?;
+
+    let forecasts = db::forecast::get_running_out(&db, query.sort, query.days, &protected).await?;
+
+    Ok(HttpResponse::Ok().json(forecasts))
+}
+
+#[utoipa::path(
+    tag = "inventory",
+    request_body = PurchaseOrderCreateRequest,
+    responses(
+        (
+            status = StatusCode::OK,
+            body = String,
+            description = "The id of the created shipment"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[post("/shortage/order")]
+async fn order_shortage(
+    body: String,
+    db: web::Data<Pool<Postgres>>,
+    id: web::ReqData<String>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+    groups: web::ReqData<Vec<Group>>,
+) -> Result<HttpResponse, Error> {
+    let request: PurchaseOrderCreateRequest = serde_json::from_str(&body)?;
+
+    let protected = get_readable_storages(&db, &permissions).await?;
+
+    let order = db::item::get_purchase_orders(&db, &protected)
+        .await?
+        .into_iter()
+        .find(|order| order.supplier == request.supplier)
+        .ok_or(Error::NotFound)?;
+
+    let items: Vec<ShipmentItem> = order
+        .items
+        .into_iter()
+        .map(|item| ShipmentItem {
+            item: item.name,
+            amount: item.amount_to_buy,
+            received: 0.0,
+            storage: item.storage,
+            container: item.container,
+            unit: Some(item.unit),
         })
         .collect();
 
-    let items = db::item::get_shortage(&db, &protected).await?;
+    check_create_auth(
+        &db,
+        &permissions,
+        &groups,
+        order.supplier.as_deref(),
+        &items,
+    )
+    .await?;
 
-    Ok(HttpResponse::Ok().json(items))
+    let shipment = db::shipment::create_order(
+        &db,
+        &id,
+        request.arrival_time,
+        order.supplier.as_deref(),
+        items,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(shipment.to_string()))
 }
 
 #[utoipa::path(
     tag = "inventory",
+    params(CategoryQuery),
     responses(
         (
             status = StatusCode::OK,
@@ -89,10 +319,12 @@ async fn get_shortage(
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -100,11 +332,12 @@ async fn get_shortage(
 #[get("/inventory")]
 async fn items_due(
     db: web::Data<Pool<Postgres>>,
+    query: web::Query<CategoryQuery>,
     permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
     let permitted_storages = get_permitted_storages(&db, &permissions).await?;
 
-    let items = db::item::items_due(&db, &permitted_storages).await?;
+    let items = db::item::items_due(&db, query.category.as_deref(), &permitted_storages).await?;
 
     Ok(HttpResponse::Ok().json(items))
 }
@@ -119,10 +352,22 @@ async fn items_due(
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = StockUpdateRefused,
+            description = "Forbidden, lists every location the user is not allowed to update"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -131,21 +376,76 @@ async fn items_due(
 async fn take_stock(
     db: web::Data<Pool<Postgres>>,
     id: web::ReqData<String>,
+    permissions: web::ReqData<Vec<HivePermission>>,
     body: String,
 ) -> Result<HttpResponse, Error> {
+    let mut items: StockUpdateRequest = validate::parse(&body)?;
+    for item in &mut items.items {
+        db::id::resolve_location(&db, &mut item.storage, &mut item.container).await?;
+    }
+
+    let mut refused: Vec<RefusedLocation> = Vec::new();
+    for item in &items.items {
+        match check_auth(
+            CheckType::Storage {
+                storage: &item.storage,
+                container: Some(&item.container),
+            },
+            &db,
+            &permissions,
+        )
+        .await
+        {
+            Ok(()) => {}
+            Err(Error::Forbidden(_)) => {
+                let location = RefusedLocation {
+                    storage: item.storage.clone(),
+                    container: item.container.clone(),
+                };
+                if !refused.contains(&location) {
+                    refused.push(location);
+                }
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    if !refused.is_empty() {
+        return Ok(HttpResponse::Forbidden().json(StockUpdateRefused {
+            error: "forbidden",
+            message: String::from("Not allowed"),
+            locations: refused,
+        }));
+    }
+
+    // Every barcode and unit has to be known before anything is updated
+    let mut amounts = Vec::new();
+    for (i, item) in items.items.iter().enumerate() {
+        let name =
+            db::barcode::item_name(&db, item.name.as_deref(), item.barcode.as_deref()).await?;
+        let amount = db::unit::to_base(
+            &db,
+            &name,
+            item.unit.as_deref(),
+            item.amount,
+            &format!("items[{i}].unit"),
+        )
+        .await?;
+        amounts.push((name, amount));
+    }
+
     let mut db = db.get_ref().begin().await?;
 
-    let items: StockUpdateRequest = serde_json::from_str(&body)?;
-
-    for StockUpdate {
-        name,
-        storage,
-        container,
-        amount,
-    } in items.items
-    {
-        db::item::update_amount_in_transaction(&mut db, &id, &name, &storage, &container, amount)
-            .await?;
+    for (item, (name, amount)) in items.items.iter().zip(amounts) {
+        db::item::update_amount_in_transaction(
+            &mut db,
+            &id,
+            &name,
+            &item.storage,
+            &item.container,
+            amount,
+        )
+        .await?;
     }
 
     db.commit().await?;
@@ -8,7 +8,11 @@ use sqlx::{Pool, Postgres};
 use utoipa::ToSchema;
 use utoipa_actix_web::service_config::ServiceConfig;
 
-use crate::{db, error::Error};
+use crate::{
+    auth::{get_readable_storages, types::HivePermission},
+    db,
+    error::{Error, ErrorResponse},
+};
 
 /// Genaral info about the state of all storages
 #[derive(Debug, Serialize, ToSchema)]
@@ -37,15 +41,23 @@ pub(crate) fn config() -> impl FnOnce(&mut ServiceConfig) {
         ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
 )]
 #[get("/stats")]
-async fn get_stats(db: web::Data<Pool<Postgres>>) -> Result<HttpResponse, Error> {
-    let item_count = db::item::get_count(&db).await?.unwrap_or(0);
+async fn get_stats(
+    db: web::Data<Pool<Postgres>>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let protected = get_readable_storages(&db, &permissions).await?;
+
+    let item_count = db::item::get_count(&db, &protected).await?.unwrap_or(0);
     let supplier_count = db::supplier::get_count(&db).await?.unwrap_or(0);
-    let shortage_count = db::item::get_shortage_count(&db).await?.unwrap_or(0);
+    let shortage_count = db::item::get_shortage_count(&db, &protected)
+        .await?
+        .unwrap_or(0);
 
     let stats = StatsGetResponse {
         items: item_count,
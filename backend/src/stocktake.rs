@@ -0,0 +1,513 @@
+use actix_web::{delete, get, post, web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    types::{
+        chrono::{DateTime, Utc},
+        Uuid,
+    },
+    Pool, Postgres,
+};
+use utoipa::{IntoParams, ToSchema};
+use utoipa_actix_web::service_config::ServiceConfig;
+
+use crate::{
+    auth::{
+        check_auth, get_readable_storages,
+        types::{HivePermission},
+        CheckType,
+    },
+    db::{
+        self,
+        stocktake::{CountedItem, Stocktake, StocktakeLine, StocktakeState},
+    },
+    error::{Error, ErrorResponse},
+    validate::{self, Validate, Validator},
+};
+
+#[derive(Debug, Serialize, ToSchema)]
+struct StocktakeGetResponse {
+    id: String,
+    storage: String,
+    state: StocktakeState,
+    time_created: DateTime<Utc>,
+    time_closed: Option<DateTime<Utc>>,
+    /// The user that opened the session
+    user: String,
+    /// The user that finalized or abandoned the session
+    closed_by: Option<String>,
+}
+
+impl From<Stocktake> for StocktakeGetResponse {
+    fn from(stocktake: Stocktake) -> Self {
+        StocktakeGetResponse {
+            id: stocktake.id.to_string(),
+            storage: stocktake.storage,
+            state: stocktake.state,
+            time_created: stocktake.time_created,
+            time_closed: stocktake.time_closed,
+            user: stocktake.user,
+            closed_by: stocktake.closed_by,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct StocktakeReportResponse {
+    stocktake: StocktakeGetResponse,
+    /// Every item in the storage with its counts
+    items: Vec<StocktakeLine>,
+}
+
+/// Info used to open a stocktake session
+#[derive(Debug, Deserialize, ToSchema)]
+struct StocktakeCreateRequest {
+    /// The storage to take stock of, by name or id
+    storage: String,
+}
+
+impl Validate for StocktakeCreateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.name("storage", &self.storage);
+    }
+}
+
+/// Counts made by the current user
+#[derive(Debug, Deserialize, ToSchema)]
+struct StocktakeCountRequest {
+    /// The sessions id
+    id: String,
+    /// The counted items, counting an item again replaces the users previous count
+    items: Vec<StocktakeCountItem>,
+}
+
+impl Validate for StocktakeCountRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.each("items", &self.items);
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct StocktakeCountItem {
+    /// The items name or id
+    item: String,
+    /// The container where the item is stored, by name or id
+    container: String,
+    /// The number of items counted
+    amount: f32,
+}
+
+impl Validate for StocktakeCountItem {
+    fn validate(&self, v: &mut Validator) {
+        v.name("item", &self.item)
+            .container("container", &self.container)
+            .amount("amount", self.amount);
+    }
+}
+
+/// Info used to finalize a stocktake session
+#[derive(Debug, Deserialize, ToSchema)]
+struct StocktakeFinalizeRequest {
+    /// The sessions id
+    id: String,
+}
+
+/// Info used to filter the sessions to return
+#[derive(Debug, Deserialize, IntoParams)]
+struct StocktakeGetQuery {
+    /// Only return sessions for this storage
+    storage: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+struct StocktakeIdQuery {
+    id: String,
+}
+
+pub(crate) fn config() -> impl FnOnce(&mut ServiceConfig) {
+    |cfg: &mut ServiceConfig| {
+        cfg.service(get_stocktakes)
+            .service(get_report)
+            .service(open)
+            .service(count)
+            .service(finalize)
+            .service(abandon);
+    }
+}
+
+/// Checks that the user is allowed to take stock of the sessions storage
+async fn check_stocktake_auth(
+    db: &Pool<Postgres>,
+    permissions: &[HivePermission],
+    stocktake: Uuid,
+) -> Result<Stocktake, Error> {
+    let stocktake = db::stocktake::get_by_id(db, stocktake).await?;
+
+    check_auth(
+        CheckType::Storage {
+            storage: &stocktake.storage,
+            container: None,
+        },
+        db,
+        permissions,
+    )
+    .await?;
+
+    Ok(stocktake)
+}
+
+#[utoipa::path(
+    tag = "inventory",
+    params(StocktakeGetQuery),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<StocktakeGetResponse>,
+            description = "List of stocktake sessions, newest first"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[get("/stocktake")]
+async fn get_stocktakes(
+    db: web::Data<Pool<Postgres>>,
+    query: web::Query<StocktakeGetQuery>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let protected = get_readable_storages(&db, &permissions).await?;
+
+    let stocktakes = db::stocktake::get_all(&db, query.storage.as_deref(), &protected)
+        .await?
+        .into_iter()
+        .map(StocktakeGetResponse::from)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(stocktakes))
+}
+
+#[utoipa::path(
+    tag = "inventory",
+    params(StocktakeIdQuery),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = StocktakeReportResponse,
+            description = "The counts of the session compared to the amounts in storage"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[get("/stocktake/report")]
+async fn get_report(
+    db: web::Data<Pool<Postgres>>,
+    query: web::Query<StocktakeIdQuery>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let stocktake_id = Uuid::parse_str(&query.id)?;
+
+    let stocktake = check_stocktake_auth(&db, &permissions, stocktake_id).await?;
+
+    let items = db::stocktake::get_report(&db, stocktake_id).await?;
+
+    Ok(HttpResponse::Ok().json(StocktakeReportResponse {
+        stocktake: stocktake.into(),
+        items,
+    }))
+}
+
+#[utoipa::path(
+    tag = "inventory",
+    request_body = StocktakeCreateRequest,
+    responses(
+        (
+            status = StatusCode::OK,
+            body = String,
+            description = "The id of the new session"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[post("/stocktake")]
+async fn open(
+    body: String,
+    db: web::Data<Pool<Postgres>>,
+    id: web::ReqData<String>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let mut stocktake: StocktakeCreateRequest = validate::parse(&body)?;
+    db::id::resolve_storage(&db, &mut stocktake.storage).await?;
+
+    check_auth(
+        CheckType::Storage {
+            storage: &stocktake.storage,
+            container: None,
+        },
+        &db,
+        &permissions,
+    )
+    .await?;
+
+    let stocktake = db::stocktake::open(&db, &id, &stocktake.storage).await?;
+
+    Ok(HttpResponse::Ok().json(stocktake.to_string()))
+}
+
+#[utoipa::path(
+    tag = "inventory",
+    request_body = StocktakeCountRequest,
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "Success"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[post("/stocktake/count")]
+async fn count(
+    body: String,
+    db: web::Data<Pool<Postgres>>,
+    id: web::ReqData<String>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let counts: StocktakeCountRequest = validate::parse(&body)?;
+    let stocktake_id = Uuid::parse_str(&counts.id)?;
+
+    let stocktake = db::stocktake::get_by_id(&db, stocktake_id).await?;
+
+    let mut items = Vec::new();
+    for StocktakeCountItem {
+        mut item,
+        mut container,
+        amount,
+    } in counts.items
+    {
+        db::id::resolve_item(&db, &mut item).await?;
+        db::id::resolve_container(&db, &stocktake.storage, &mut container).await?;
+
+        check_auth(
+            CheckType::Storage {
+                storage: &stocktake.storage,
+                container: Some(&container),
+            },
+            &db,
+            &permissions,
+        )
+        .await?;
+
+        items.push(CountedItem {
+            item,
+            container,
+            amount,
+        });
+    }
+
+    db::stocktake::count(&db, &id, stocktake_id, items).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Sets every counted item to its latest count in one transaction and closes the session
+#[utoipa::path(
+    tag = "inventory",
+    request_body = StocktakeFinalizeRequest,
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "Success"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[post("/stocktake/finalize")]
+async fn finalize(
+    body: String,
+    db: web::Data<Pool<Postgres>>,
+    id: web::ReqData<String>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let stocktake: StocktakeFinalizeRequest = serde_json::from_str(&body)?;
+    let stocktake_id = Uuid::parse_str(&stocktake.id)?;
+
+    check_stocktake_auth(&db, &permissions, stocktake_id).await?;
+
+    db::stocktake::finalize(&db, &id, stocktake_id).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Abandons the session, the stock is left untouched
+#[utoipa::path(
+    tag = "inventory",
+    params(StocktakeIdQuery),
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "Success"
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
+            description = "Bad Request"
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
+            description = "Internal Server Error"
+        )
+    )
+)]
+#[delete("/stocktake")]
+async fn abandon(
+    query: web::Query<StocktakeIdQuery>,
+    db: web::Data<Pool<Postgres>>,
+    id: web::ReqData<String>,
+    permissions: web::ReqData<Vec<HivePermission>>,
+) -> Result<HttpResponse, Error> {
+    let stocktake_id = Uuid::parse_str(&query.id)?;
+
+    check_stocktake_auth(&db, &permissions, stocktake_id).await?;
+
+    db::stocktake::abandon(&db, &id, stocktake_id).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -5,27 +5,32 @@ use utoipa::{IntoParams, ToSchema};
 use utoipa_actix_web::service_config::ServiceConfig;
 
 use crate::{
-    auth::{check_auth, types::HivePermission, CheckType},
+    auth::{
+        check_auth, get_readable_storages,
+        types::{HivePermission},
+        CheckType,
+    },
     db::{
         self,
         container::{ContainerItem, ContainerStorage},
         interval::Interval,
         storage::Storage,
     },
-    error::Error,
+    error::{Error, ErrorResponse},
+    validate::{self, Validate, Validator},
 };
 
 /// Used to get a specific storage
 #[derive(Debug, Deserialize, IntoParams)]
 struct StorageGetQuery {
-    /// The name of the requested storage
+    /// The name or id of the requested storage
     name: String,
 }
 
 /// Info used to create a storage location
 #[derive(Deserialize, ToSchema)]
 struct StorageCreateRequest {
-    /// The suppliers name
+    /// The storages name
     name: String,
     /// If the storage should require a read permission
     protected: bool,
@@ -33,10 +38,17 @@ struct StorageCreateRequest {
     inventory_interval: Option<Interval>,
 }
 
+impl Validate for StorageCreateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.new_name("name", &self.name)
+            .interval("inventory_interval", self.inventory_interval.as_ref());
+    }
+}
+
 /// Info used to update a storage location
 #[derive(Deserialize, ToSchema)]
 struct StorageUpdateRequest {
-    /// The suppliers name
+    /// The storages name or id
     name: String,
     /// The new name of the storage if it is changed
     new_name: Option<String>,
@@ -46,10 +58,18 @@ struct StorageUpdateRequest {
     inventory_interval: Option<Interval>,
 }
 
+impl Validate for StorageUpdateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.name("name", &self.name)
+            .optional_new_name("new_name", self.new_name.as_deref())
+            .interval("inventory_interval", self.inventory_interval.as_ref());
+    }
+}
+
 /// Info used to delete a storage location
 #[derive(Deserialize, IntoParams)]
 struct StorageDeleteQuery {
-    /// The storages name
+    /// The storages name or id
     name: String,
 }
 
@@ -58,42 +78,66 @@ struct StorageDeleteQuery {
 struct ContainerCreateRequest {
     /// The containers name
     name: String,
-    /// The storage location the container is at
+    /// The storage location the container is at, by name or id
     storage: String,
     /// The time between when the container should be inventoried
     inventory_interval: Option<Interval>,
 }
 
+impl Validate for ContainerCreateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.new_name("name", &self.name)
+            .name("storage", &self.storage)
+            .interval("inventory_interval", self.inventory_interval.as_ref());
+    }
+}
+
 /// Info used to update a container
 #[derive(Deserialize, ToSchema)]
 struct ContainerUpdateRequest {
-    /// The containers name
+    /// The containers name or id
     name: String,
     /// The containers new name if it should be changed
     new_name: Option<String>,
-    /// The storage location the container should be at
+    /// The storage location the container is at, by name or id
     storage: String,
 }
 
+impl Validate for ContainerUpdateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.name("name", &self.name)
+            .optional_new_name("new_name", self.new_name.as_deref())
+            .name("storage", &self.storage);
+    }
+}
+
 /// Info used to move a container
 #[derive(Deserialize, ToSchema)]
 struct ContainerMoveRequest {
-    /// The containers name
+    /// The containers name or id
     name: String,
-    /// The name of the storage the container is moved from
+    /// The name or id of the storage the container is moved from
     from_storage: String,
-    /// The name of the storage the container is moved to
+    /// The name or id of the storage the container is moved to
     to_storage: String,
     /// Spcifies if containers with the same name should be merged
     merge: bool,
 }
 
+impl Validate for ContainerMoveRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.name("name", &self.name)
+            .name("from_storage", &self.from_storage)
+            .name("to_storage", &self.to_storage);
+    }
+}
+
 /// Info used to delete a container
 #[derive(Deserialize, IntoParams)]
 struct ContainerDeleteQuery {
-    /// The containers name
+    /// The containers name or id
     name: String,
-    /// The name of the storage the container is at
+    /// The name or id of the storage the container is at
     storage: String,
 }
 
@@ -122,10 +166,12 @@ pub(crate) fn config() -> impl FnOnce(&mut ServiceConfig) {
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -135,25 +181,9 @@ async fn get_storages(
     db: web::Data<Pool<Postgres>>,
     permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
-    let storages = if check_auth(CheckType::Admin, &db, &permissions)
-        .await
-        .is_ok()
-    {
-        db::storage::get_all_unprotected(&db).await?
-    } else {
-        let protected: Vec<String> = permissions
-            .iter()
-            .filter_map(|perm| {
-                if perm.id == "read" {
-                    perm.scope.clone()
-                } else {
-                    None
-                }
-            })
-            .collect();
-
-        db::storage::get_all(&db, &protected).await?
-    };
+    let protected = get_readable_storages(&db, &permissions).await?;
+
+    let storages = db::storage::get_all(&db, &protected).await?;
 
     Ok(HttpResponse::Ok().json(storages))
 }
@@ -168,10 +198,12 @@ async fn get_storages(
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -181,16 +213,7 @@ async fn get_storage_container_tree(
     db: web::Data<Pool<Postgres>>,
     permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
-    let protected: Vec<String> = permissions
-        .iter()
-        .filter_map(|perm| {
-            if perm.id == "read" {
-                perm.scope.clone()
-            } else {
-                None
-            }
-        })
-        .collect();
+    let protected = get_readable_storages(&db, &permissions).await?;
 
     let storages: Vec<ContainerStorage> =
         db::container::get_all_containers_grouped_by_storage(&db, &protected).await?;
@@ -209,10 +232,27 @@ async fn get_storage_container_tree(
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
+            description = "Unauthorized"
+        ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -221,7 +261,20 @@ async fn get_storage_container_tree(
 async fn get_container_item_tree_for_storage(
     db: web::Data<Pool<Postgres>>,
     query: web::Query<StorageGetQuery>,
+    permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
+    let mut query = query.into_inner();
+    db::id::resolve_storage(&db, &mut query.name).await?;
+
+    check_auth(
+        CheckType::Read {
+            storage: &query.name,
+        },
+        &db,
+        &permissions,
+    )
+    .await?;
+
     let storage = db::container::get_all_containers_in_storage_with_items(&db, &query.name).await?;
 
     Ok(HttpResponse::Ok().json(storage))
@@ -233,18 +286,32 @@ async fn get_container_item_tree_for_storage(
     responses(
         (
             status = StatusCode::OK,
-            description = "Success"
+            body = String,
+            description = "The id of the storage"
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -255,7 +322,7 @@ async fn create_storage(
     db: web::Data<Pool<Postgres>>,
     permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
-    let storage: StorageCreateRequest = serde_json::from_str(&body)?;
+    let storage: StorageCreateRequest = validate::parse(&body)?;
 
     check_auth(CheckType::Admin, &db, &permissions).await?;
 
@@ -273,7 +340,7 @@ async fn create_storage(
     )
     .await?;
 
-    Ok(HttpResponse::Ok().finish())
+    Ok(HttpResponse::Ok().json(db::id::storage_id(&db, &storage.name).await?))
 }
 
 #[utoipa::path(
@@ -286,14 +353,32 @@ async fn create_storage(
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -304,7 +389,8 @@ async fn change_storage(
     db: web::Data<Pool<Postgres>>,
     permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
-    let storage: StorageUpdateRequest = serde_json::from_str(&body)?;
+    let mut storage: StorageUpdateRequest = validate::parse(&body)?;
+    db::id::resolve_storage(&db, &mut storage.name).await?;
 
     check_auth(CheckType::Admin, &db, &permissions).await?;
 
@@ -336,14 +422,27 @@ async fn change_storage(
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -354,8 +453,13 @@ async fn destroy_storage(
     db: web::Data<Pool<Postgres>>,
     permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
+    let mut query = query.into_inner();
+    db::id::resolve_storage(&db, &mut query.name).await?;
+
     check_auth(CheckType::Admin, &db, &permissions).await?;
-    db::storage::destroy(&db, &query.name).await?;
+    db::storage::destroy(&db, &query.name)
+        .await
+        .map_err(Error::removing)?;
     Ok(HttpResponse::Ok().finish())
 }
 
@@ -365,18 +469,37 @@ async fn destroy_storage(
     responses(
         (
             status = StatusCode::OK,
-            description = "Success"
+            body = String,
+            description = "The id of the container"
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -387,7 +510,8 @@ async fn create_container(
     db: web::Data<Pool<Postgres>>,
     permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
-    let container: ContainerCreateRequest = serde_json::from_str(&body)?;
+    let mut container: ContainerCreateRequest = validate::parse(&body)?;
+    db::id::resolve_storage(&db, &mut container.storage).await?;
 
     check_auth(
         CheckType::Storage {
@@ -413,7 +537,8 @@ async fn create_container(
     )
     .await?;
 
-    Ok(HttpResponse::Ok().finish())
+    Ok(HttpResponse::Ok()
+        .json(db::id::container_id(&db, &container.storage, &container.name).await?))
 }
 
 #[utoipa::path(
@@ -426,14 +551,32 @@ async fn create_container(
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -444,7 +587,8 @@ async fn change_container(
     db: web::Data<Pool<Postgres>>,
     permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
-    let container: ContainerUpdateRequest = serde_json::from_str(&body)?;
+    let mut container: ContainerUpdateRequest = validate::parse(&body)?;
+    db::id::resolve_location(&db, &mut container.storage, &mut container.name).await?;
 
     check_auth(
         CheckType::Storage {
@@ -477,14 +621,32 @@ async fn change_container(
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -496,7 +658,9 @@ async fn move_container(
     permissions: web::ReqData<Vec<HivePermission>>,
     id: web::ReqData<String>,
 ) -> Result<HttpResponse, Error> {
-    let container: ContainerMoveRequest = serde_json::from_str(&body)?;
+    let mut container: ContainerMoveRequest = validate::parse(&body)?;
+    db::id::resolve_location(&db, &mut container.from_storage, &mut container.name).await?;
+    db::id::resolve_storage(&db, &mut container.to_storage).await?;
 
     check_auth(
         CheckType::MoveContainer {
@@ -532,14 +696,27 @@ async fn move_container(
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -550,7 +727,12 @@ async fn destroy_container(
     db: web::Data<Pool<Postgres>>,
     permissions: web::ReqData<Vec<HivePermission>>,
 ) -> Result<HttpResponse, Error> {
+    let mut query = query.into_inner();
+    db::id::resolve_location(&db, &mut query.storage, &mut query.name).await?;
+
     check_auth(CheckType::Admin, &db, &permissions).await?;
-    db::container::destroy(&db, &query.name, &query.storage).await?;
+    db::container::destroy(&db, &query.name, &query.storage)
+        .await
+        .map_err(Error::removing)?;
     Ok(HttpResponse::Ok().finish())
 }
@@ -10,8 +10,9 @@ use crate::{
         types::{Group, HivePermission},
         CheckType,
     },
-    db::{self, item::BasicItem, supplier::Supplier},
-    error::Error,
+    db::{self, supplier::Supplier},
+    error::{Error, ErrorResponse},
+    validate::{self, Validate, Validator},
 };
 
 /// Info used to create a supplier
@@ -31,12 +32,23 @@ struct SupplierCreateRequest {
     group: String,
 }
 
+impl Validate for SupplierCreateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.new_name("name", &self.name)
+            .text("link", self.link.as_deref())
+            .text("notes", self.notes.as_deref())
+            .text("username", self.username.as_deref())
+            .text("password", self.password.as_deref())
+            .name("group", &self.group);
+    }
+}
+
 /// Info used to update a supplier
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 struct SupplierUpdateRequest {
-    /// The name the supplier is supposed to have
+    /// The name the supplier is supposed to have, or its id if the name stays the same
     name: String,
-    /// The name of the supplier before update (only if changeing)
+    /// The name or id of the supplier before update (only if changeing)
     old_name: Option<String>,
     /// Link to the suppliers website
     link: Option<String>,
@@ -50,10 +62,26 @@ struct SupplierUpdateRequest {
     group: String,
 }
 
+impl Validate for SupplierUpdateRequest {
+    fn validate(&self, v: &mut Validator) {
+        if self.old_name.is_some() {
+            v.new_name("name", &self.name);
+        } else {
+            v.name("name", &self.name);
+        }
+        v.optional_name("old_name", self.old_name.as_deref())
+            .text("link", self.link.as_deref())
+            .text("notes", self.notes.as_deref())
+            .text("username", self.username.as_deref())
+            .text("password", self.password.as_deref())
+            .name("group", &self.group);
+    }
+}
+
 /// Info used to delete a supplier
 #[derive(Debug, Deserialize, IntoParams)]
 struct SupplierDeleteQuery {
-    /// The suppliers name
+    /// The suppliers name or id
     name: String,
 }
 
@@ -76,14 +104,17 @@ pub(crate) fn config() -> impl FnOnce(&mut ServiceConfig) {
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -108,18 +139,32 @@ pub(crate) async fn get_suppliers(
     responses(
         (
             status = StatusCode::OK,
-            description = "Success"
+            body = String,
+            description = "The id of the supplier"
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -131,7 +176,7 @@ async fn create_supplier(
     permissions: web::ReqData<Vec<HivePermission>>,
     groups: web::ReqData<Vec<Group>>,
 ) -> Result<HttpResponse, Error> {
-    let supplier: SupplierCreateRequest = serde_json::from_str(&body)?;
+    let supplier: SupplierCreateRequest = validate::parse(&body)?;
 
     check_auth(
         CheckType::SupplierCreate {
@@ -154,7 +199,7 @@ async fn create_supplier(
     )
     .await?;
 
-    Ok(HttpResponse::Ok().finish())
+    Ok(HttpResponse::Ok().json(db::id::supplier_id(&db, &supplier.name).await?))
 }
 
 #[utoipa::path(
@@ -167,14 +212,32 @@ async fn create_supplier(
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            body = ErrorResponse,
+            description = "Conflict"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -186,7 +249,11 @@ async fn update_supplier(
     permissions: web::ReqData<Vec<HivePermission>>,
     groups: web::ReqData<Vec<Group>>,
 ) -> Result<HttpResponse, Error> {
-    let supplier: SupplierUpdateRequest = serde_json::from_str(&body)?;
+    let mut supplier: SupplierUpdateRequest = validate::parse(&body)?;
+    match &mut supplier.old_name {
+        Some(old_name) => db::id::resolve_supplier(&db, old_name).await?,
+        None => db::id::resolve_supplier(&db, &mut supplier.name).await?,
+    }
 
     check_auth(
         CheckType::Supplier {
@@ -223,14 +290,27 @@ async fn update_supplier(
         ),
         (
             status = StatusCode::BAD_REQUEST,
+            body = ErrorResponse,
             description = "Bad Request"
         ),
         (
             status = StatusCode::UNAUTHORIZED,
+            body = ErrorResponse,
             description = "Unauthorized"
         ),
+        (
+            status = StatusCode::FORBIDDEN,
+            body = ErrorResponse,
+            description = "Forbidden"
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            body = ErrorResponse,
+            description = "Not Found"
+        ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = ErrorResponse,
             description = "Internal Server Error"
         )
     )
@@ -242,6 +322,9 @@ async fn delete_supplier(
     permissions: web::ReqData<Vec<HivePermission>>,
     groups: web::ReqData<Vec<Group>>,
 ) -> Result<HttpResponse, Error> {
+    let mut query = query.into_inner();
+    db::id::resolve_supplier(&db, &mut query.name).await?;
+
     check_auth(
         CheckType::Supplier {
             mandates: &groups,
@@ -252,6 +335,8 @@ async fn delete_supplier(
     )
     .await?;
 
-    db::supplier::destroy(&db, &query.name).await?;
+    db::supplier::destroy(&db, &query.name)
+        .await
+        .map_err(Error::removing)?;
     Ok(HttpResponse::Ok().finish())
 }
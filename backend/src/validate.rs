@@ -0,0 +1,349 @@
+use std::mem;
+
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+
+use crate::{
+    db::{barcode, id, interval::Interval},
+    error::{Error, FieldError},
+};
+
+/// Microseconds in a day, postgres counts a month as 30 days when comparing intervals
+const DAY: i64 = 24 * 60 * 60 * 1_000_000;
+
+/// A request body that states what its fields must look like
+pub trait Validate {
+    fn validate(&self, v: &mut Validator);
+}
+
+/// Collects every invalid field of a request, so they can all be reported at once
+#[derive(Debug, Default)]
+pub struct Validator {
+    /// Where in the request the fields are, ex. items[2]
+    path: String,
+    errors: Vec<FieldError>,
+}
+
+/// Parses a request body and checks all of its fields
+pub fn parse<T: DeserializeOwned + Validate>(body: &str) -> Result<T, Error> {
+    let value: T = serde_json::from_str(body)?;
+
+    let mut validator = Validator::default();
+    value.validate(&mut validator);
+    validator.finish()?;
+
+    Ok(value)
+}
+
+/// Tells a field set to null apart from one that is left out, used with `#[serde(default)]` on an
+/// `Option<Option<T>>` it is `Some(None)` when null and `None` when missing
+pub fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+impl Validator {
+    fn field(&self, field: &str) -> String {
+        match self.path.as_str() {
+            "" => field.to_string(),
+            path => format!("{path}.{field}"),
+        }
+    }
+
+    pub fn error(&mut self, field: &str, message: &str) -> &mut Self {
+        let field = self.field(field);
+        self.errors.push(FieldError::new(&field, message));
+        self
+    }
+
+    /// Not blank and without whitespace around it
+    pub fn name(&mut self, field: &str, name: &str) -> &mut Self {
+        if name.trim().is_empty() {
+            self.error(field, "must not be blank")
+        } else {
+            self.container(field, name)
+        }
+    }
+
+    pub fn optional_name(&mut self, field: &str, name: Option<&str>) -> &mut Self {
+        match name {
+            Some(name) => self.name(field, name),
+            None => self,
+        }
+    }
+
+    /// A name that is being given to something with an id, it can't look like an id as the API
+    /// takes both in the same fields
+    pub fn new_name(&mut self, field: &str, name: &str) -> &mut Self {
+        if id::is_id(name) {
+            self.error(field, "must not look like an id")
+        } else {
+            self.name(field, name)
+        }
+    }
+
+    pub fn optional_new_name(&mut self, field: &str, name: Option<&str>) -> &mut Self {
+        match name {
+            Some(name) => self.new_name(field, name),
+            None => self,
+        }
+    }
+
+    /// A container in a storage, empty for the container of the storage itself
+    pub fn container(&mut self, field: &str, name: &str) -> &mut Self {
+        if name.trim() != name {
+            self.error(field, "must not start or end with whitespace");
+        }
+        self
+    }
+
+    pub fn optional_container(&mut self, field: &str, name: Option<&str>) -> &mut Self {
+        match name {
+            Some(name) => self.container(field, name),
+            None => self,
+        }
+    }
+
+    /// Free text such as notes or passwords, left as it is as long as it is not blank
+    pub fn text(&mut self, field: &str, text: Option<&str>) -> &mut Self {
+        if text.is_some_and(|text| text.trim().is_empty()) {
+            self.error(field, "must not be blank");
+        }
+        self
+    }
+
+    /// A scanned code, if it is an EAN or UPC the check digit has to match
+    pub fn barcode(&mut self, field: &str, code: &str) -> &mut Self {
+        self.name(field, code);
+        if !barcode::check_digit_valid(code) {
+            self.error(field, "has the wrong check digit");
+        }
+        self
+    }
+
+    /// An item given either by its name or by one of its barcodes
+    pub fn item(&mut self, name: Option<&str>, code: Option<&str>) -> &mut Self {
+        match (name, code) {
+            (Some(_), Some(_)) => self.error("barcode", "must not be set together with name"),
+            (None, None) => self.error("name", "must be set if barcode is not"),
+            (Some(name), None) => self.name("name", name),
+            (None, Some(code)) => self.barcode("barcode", code),
+        }
+    }
+
+    /// A finite amount of at least zero
+    pub fn amount(&mut self, field: &str, amount: f32) -> &mut Self {
+        if !amount.is_finite() {
+            self.error(field, "must be a finite number")
+        } else if amount < 0.0 {
+            self.error(field, "must not be negative")
+        } else {
+            self
+        }
+    }
+
+    /// A finite amount above zero
+    pub fn positive(&mut self, field: &str, amount: f32) -> &mut Self {
+        if !amount.is_finite() {
+            self.error(field, "must be a finite number")
+        } else if amount <= 0.0 {
+            self.error(field, "must be positive")
+        } else {
+            self
+        }
+    }
+
+    /// The min and max of a stored item, either both or neither and max above min
+    pub fn limits(&mut self, min: Option<f32>, max: Option<f32>) -> &mut Self {
+        match (min, max) {
+            (Some(min), Some(max)) => {
+                self.amount("min", min);
+                if !max.is_finite() {
+                    self.error("max", "must be a finite number");
+                } else if max <= min {
+                    self.error("max", "must be greater than min");
+                }
+                self
+            }
+            (Some(_), None) => self.error("max", "must be set together with min"),
+            (None, Some(_)) => self.error("min", "must be set together with max"),
+            (None, None) => self,
+        }
+    }
+
+    /// How often something is inventoried, at least once a day
+    pub fn interval(&mut self, field: &str, interval: Option<&Interval>) -> &mut Self {
+        let Some(interval) = interval else {
+            return self;
+        };
+
+        // In i128 as months and days of an interval can be far more microseconds than fit in i64
+        let day = i128::from(DAY);
+        let length = i128::from(interval.months) * 30 * day
+            + i128::from(interval.days) * day
+            + i128::from(interval.microseconds);
+        if length < day {
+            self.error(field, "must be at least a day");
+        }
+        self
+    }
+
+    /// A storage and container that are either both given or both left out
+    pub fn destination(&mut self, storage: Option<&str>, container: Option<&str>) -> &mut Self {
+        match (storage, container) {
+            (Some(_), None) => self.error("container", "must be set together with storage"),
+            (None, Some(_)) => self.error("storage", "must be set together with container"),
+            _ => self
+                .optional_name("storage", storage)
+                .optional_container("container", container),
+        }
+    }
+
+    pub fn not_empty<T>(&mut self, field: &str, items: &[T]) -> &mut Self {
+        if items.is_empty() {
+            self.error(field, "must not be empty");
+        }
+        self
+    }
+
+    /// Checks every element of a list, their fields are reported as ex. items[0].amount
+    pub fn each<T: Validate>(&mut self, field: &str, items: &[T]) -> &mut Self {
+        for (index, item) in items.iter().enumerate() {
+            let path = format!("{}[{index}]", self.field(field));
+            let path = mem::replace(&mut self.path, path);
+            item.validate(self);
+            self.path = path;
+        }
+        self
+    }
+
+    pub fn finish(self) -> Result<(), Error> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Validation(self.errors))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Validate, Validator};
+    use crate::{
+        db::interval::Interval,
+        error::{Error, FieldError},
+    };
+
+    struct Line {
+        name: String,
+        amount: f32,
+    }
+
+    impl Validate for Line {
+        fn validate(&self, v: &mut Validator) {
+            v.name("name", &self.name).amount("amount", self.amount);
+        }
+    }
+
+    fn fields(validator: Validator) -> Vec<String> {
+        match validator.finish() {
+            Ok(()) => Vec::new(),
+            Err(Error::Validation(fields)) => fields.into_iter().map(|f| f.field).collect(),
+            Err(error) => panic!("{error}"),
+        }
+    }
+
+    #[test]
+    fn every_field_is_reported() {
+        let mut v = Validator::default();
+        v.name("name", "tejp")
+            .name("storage", " ")
+            .container("container", "")
+            .container("from_container", "låda ")
+            .amount("amount", -1.0)
+            .positive("moved", 0.0)
+            .amount("counted", f32::INFINITY)
+            .text("notes", Some("  "))
+            .text("password", Some(" hunter2 "));
+        assert_eq!(
+            fields(v),
+            [
+                "storage",
+                "from_container",
+                "amount",
+                "moved",
+                "counted",
+                "notes"
+            ]
+        );
+    }
+
+    #[test]
+    fn names_are_not_ids() {
+        let mut v = Validator::default();
+        v.new_name("name", "tejp")
+            .name("item", "67e55044-10b1-426f-9247-bb680e5fe0c8")
+            .new_name("new_name", "67e55044-10b1-426f-9247-bb680e5fe0c8")
+            .optional_new_name("storage", Some("67e5504410b1426f9247bb680e5fe0c8"));
+        assert_eq!(fields(v), ["new_name", "storage"]);
+    }
+
+    #[test]
+    fn limits() {
+        let check = |min, max| {
+            let mut v = Validator::default();
+            v.limits(min, max);
+            fields(v)
+        };
+
+        assert!(check(None, None).is_empty());
+        assert!(check(Some(0.0), Some(1.0)).is_empty());
+        assert_eq!(check(Some(1.0), None), ["max"]);
+        assert_eq!(check(None, Some(1.0)), ["min"]);
+        assert_eq!(check(Some(2.0), Some(2.0)), ["max"]);
+        assert_eq!(check(Some(-3.0), Some(-2.0)), ["min"]);
+    }
+
+    #[test]
+    fn intervals() {
+        let check = |interval: Interval| {
+            let mut v = Validator::default();
+            v.interval("inventory_interval", Some(&interval));
+            fields(v)
+        };
+
+        assert!(check(Interval::new(0, 1, 0)).is_empty());
+        assert!(check(Interval::new(1, -20, 0)).is_empty());
+        assert_eq!(check(Interval::new(0, 0, 3_600_000_000)).len(), 1);
+        assert_eq!(check(Interval::new(0, -1, 0)).len(), 1);
+        assert!(check(Interval::new(i32::MAX, i32::MAX, i64::MAX)).is_empty());
+        assert_eq!(check(Interval::new(i32::MIN, i32::MIN, i64::MIN)).len(), 1);
+    }
+
+    #[test]
+    fn lists() {
+        let line = |name: &str, amount| Line {
+            name: name.to_string(),
+            amount,
+        };
+
+        let mut v = Validator::default();
+        v.each("items", &[line("tejp", 1.0), line("", -1.0)]);
+        let Err(Error::Validation(errors)) = v.finish() else {
+            panic!("invalid lines to be reported");
+        };
+        assert_eq!(
+            errors,
+            [
+                FieldError::new("items[1].name", "must not be blank"),
+                FieldError::new("items[1].amount", "must not be negative"),
+            ]
+        );
+
+        let mut v = Validator::default();
+        v.not_empty::<Line>("items", &[]).each::<Line>("items", &[]);
+        assert_eq!(fields(v), ["items"]);
+    }
+}
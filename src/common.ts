@@ -25,6 +25,17 @@ export async function fetchOrRedirect(
     return Promise.reject(new Error('Redirecting...'))
   }
 
+  // Not logged in, the body says where to log in
+  if (response.status === 401) {
+    const body = await response.json().catch(() => undefined)
+    const login = body?.login
+      ? new URL(body.login, window.location.href).href
+      : url
+    window.location.href = login
+    console.log('Redirecting to:', login)
+    return Promise.reject(new Error('Redirecting...'))
+  }
+
   if (response.status >= 300 && response.status < 400) {
     // If CORS allows, we can read the Location header:
     const location = response.headers.get('Location')
@@ -40,6 +51,29 @@ export async function fetchOrRedirect(
   return response
 }
 
+// The message of an error response, the body is json with error, message, any invalid fields,
+// what was missing if forbidden and the locations a stock update was refused for
+export async function errorMessage(response: Response): Promise<string> {
+  const text = await response.text()
+  try {
+    const body = JSON.parse(text)
+    const fields = (body.fields ?? []).map(
+      (field: { field: string; message: string }) =>
+        `${field.field}: ${field.message}`,
+    )
+    const refused = body.refused
+      ? [`Missing ${body.refused.permission} ${body.refused.scope.join(' / ')}`.trim()]
+      : []
+    const locations = (body.locations ?? []).map(
+      (location: { storage: string; container: string }) =>
+        `Not allowed in ${location.storage} ${location.container}`.trim(),
+    )
+    return [body.message, ...fields, ...refused, ...locations].join('\n')
+  } catch {
+    return text
+  }
+}
+
 export function stateEmoji(state: StateEnum): string {
   switch (state) {
     case 'Good':
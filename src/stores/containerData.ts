@@ -6,6 +6,7 @@ import type {
   Notification,
 } from '@/types'
 import { useNotificationsStore } from './notifications'
+import { errorMessage } from '@/common'
 
 export async function createContainer(
   container: ContainerCreateRequest,
@@ -22,7 +23,7 @@ export async function createContainer(
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -50,7 +51,7 @@ export async function deleteContainer(
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -81,7 +82,7 @@ export async function updateContainer(
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -112,7 +113,7 @@ export async function moveContainer(
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
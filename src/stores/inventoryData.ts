@@ -1,5 +1,6 @@
 import type { ShortageGetResponse, Notification } from '@/types'
 import { useNotificationsStore } from './notifications'
+import { errorMessage } from '@/common'
 
 export async function getShortage(): Promise<ShortageGetResponse> {
   const res = await fetch('/api/shortage')
@@ -8,7 +9,7 @@ export async function getShortage(): Promise<ShortageGetResponse> {
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -13,7 +13,7 @@ import type {
   ItemEditLinkSupplierRequest,
 } from '@/types'
 import { useNotificationsStore } from './notifications'
-import { fetchOrRedirect } from '@/common'
+import { errorMessage, fetchOrRedirect } from '@/common'
 
 export async function getItemByName(name: string): Promise<ItemGetResponse> {
   const query = new URLSearchParams({ name: name })
@@ -25,7 +25,7 @@ export async function getItemByName(name: string): Promise<ItemGetResponse> {
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -74,7 +74,7 @@ export async function getItems(
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -98,7 +98,7 @@ export async function createItem(item: ItemAddRequest): Promise<void> {
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -126,7 +126,7 @@ export async function moveItem(item: ItemMoveRequest): Promise<void> {
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -151,7 +151,7 @@ export async function deleteItem(item: ItemDeleteRequest): Promise<void> {
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -181,7 +181,7 @@ export async function supplierLinkItem(
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -210,7 +210,7 @@ export async function supplierUnlinkItem(
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -240,7 +240,7 @@ export async function supplierEditLinkItem(
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -268,7 +268,7 @@ export async function changeItemName(body: ItemEditRequest): Promise<void> {
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -298,7 +298,7 @@ export async function editItemStorage(
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
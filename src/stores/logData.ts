@@ -1,5 +1,6 @@
 import type { LogQueryParams, Notification } from '@/types'
 import { useNotificationsStore } from './notifications'
+import { errorMessage } from '@/common'
 
 export async function getLog(item: LogQueryParams): Promise<any> {
   const query = new URLSearchParams(item)
@@ -10,7 +11,7 @@ export async function getLog(item: LogQueryParams): Promise<any> {
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
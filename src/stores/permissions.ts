@@ -2,7 +2,7 @@ import { ref } from 'vue'
 import { defineStore } from 'pinia'
 import { useNotificationsStore } from './notifications'
 import type { UserInfoGetResponse, Notification } from '@/types'
-import { fetchOrRedirect } from '@/common'
+import { errorMessage, fetchOrRedirect } from '@/common'
 
 export const usePermsStore = defineStore('permissions', () => {
   const perms = ref<UserInfoGetResponse>()
@@ -14,7 +14,7 @@ export const usePermsStore = defineStore('permissions', () => {
       const noti: Notification = {
         id: Date.now(),
         title: res.statusText,
-        message: await res.text(),
+        message: await errorMessage(res),
         severity: 'error',
       }
       notificationsStore.add(noti)
@@ -1,5 +1,6 @@
 import type { StatsGetResponse, Notification } from '@/types'
 import { useNotificationsStore } from './notifications'
+import { errorMessage } from '@/common'
 
 export async function getStats(): Promise<StatsGetResponse> {
   const res = await fetch('/api/stats', { method: 'GET' })
@@ -8,7 +9,7 @@ export async function getStats(): Promise<StatsGetResponse> {
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -4,6 +4,7 @@ import type {
   StockUpdateRequest,
 } from '@/types'
 import { useNotificationsStore } from './notifications'
+import { errorMessage } from '@/common'
 
 export async function getStockTree(): Promise<StockTreeGetResponse> {
   const res = await fetch('/api/inventory', { method: 'GET' })
@@ -12,7 +13,7 @@ export async function getStockTree(): Promise<StockTreeGetResponse> {
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -36,7 +37,7 @@ export async function takeStock(body: StockUpdateRequest): Promise<void> {
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -10,6 +10,7 @@ import type {
 } from '@/types'
 import { useNotificationsStore } from './notifications'
 import { usePermsStore } from './permissions'
+import { errorMessage } from '@/common'
 
 export async function getStorageContainers(
   filter?: boolean,
@@ -20,7 +21,7 @@ export async function getStorageContainers(
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -54,7 +55,7 @@ export async function getStorageTree(
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -72,7 +73,7 @@ export async function getStorages(): Promise<StoragesGetResponse> {
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -98,7 +99,7 @@ export async function createStorage(
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -127,7 +128,7 @@ export async function deleteStorage(
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -157,7 +158,7 @@ export async function editStorage(body: StorageEditRequest): Promise<void> {
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -6,6 +6,7 @@ import type {
   SupplierEditRequest,
 } from '@/types'
 import { useNotificationsStore } from './notifications'
+import { errorMessage } from '@/common'
 
 export async function getSuppliers(): Promise<SupplierGetResponse> {
   const res = await fetch('/api/supplier', { method: 'GET' })
@@ -14,7 +15,7 @@ export async function getSuppliers(): Promise<SupplierGetResponse> {
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -40,7 +41,7 @@ export async function createSupplier(
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -68,7 +69,7 @@ export async function deleteSupplier(
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)
@@ -99,7 +100,7 @@ export async function updateSupplier(
     const noti: Notification = {
       id: Date.now(),
       title: res.statusText,
-      message: await res.text(),
+      message: await errorMessage(res),
       severity: 'error',
     }
     notificationsStore.add(noti)